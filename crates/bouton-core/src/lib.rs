@@ -1,4 +1,6 @@
 pub mod control;
+pub mod motion;
+pub mod touch;
 
 use serde::{Deserialize, Serialize};
 use control::GamepadControl;
@@ -21,12 +23,56 @@ pub struct ControlAxis {
     pub value: i32,
 }
 
+/// One finger on the touchpad, identified by its multi-touch slot.
+/// `position` is `None` once the finger lifts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ControlTouch {
+    pub slot: u8,
+    pub position: Option<(i32, i32)>,
+}
+
+/// One motion-sensor sample: raw gyro rates and accelerometer readings on
+/// the x, y and z axes. With hid-playstation gyro x is pitch, y is yaw and
+/// z is roll.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct ControlMotion {
+    pub gyro: [i32; 3],
+    pub accel: [i32; 3],
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ControlEvent {
     #[serde(rename = "button")]
     Button(ControlButton),
     #[serde(rename = "axis")]
     Axis(ControlAxis),
+    #[serde(rename = "touch")]
+    Touch(ControlTouch),
+    #[serde(rename = "motion")]
+    Motion(ControlMotion),
+}
+
+/// Feedback the server sends back to the client it receives events from,
+/// so the client can show mapping state that only the server knows.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ServerEvent {
+    /// Sector a radial-menu stick points at, out of `sectors`, and the key
+    /// bound to it; `None` while the stick rests in the centre.
+    #[serde(rename = "radial_sector")]
+    RadialSector {
+        control: GamepadControl,
+        sector: Option<u8>,
+        sectors: u8,
+        key: Option<String>,
+    },
+    /// Raw and smoothed value of an axis with smoothing, for previewing the
+    /// filter while tuning it.
+    #[serde(rename = "axis_smoothed")]
+    AxisSmoothed {
+        control: GamepadControl,
+        raw: i32,
+        value: i32,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -91,6 +137,19 @@ mod tests {
         assert!(json.contains("Press"));
     }
 
+    #[test]
+    fn server_event_serializes() {
+        let event = ServerEvent::RadialSector {
+            control: GamepadControl::RightStickX,
+            sector: Some(2),
+            sectors: 8,
+            key: Some("E".to_string()),
+        };
+        let json = serde_json::to_string(&event).unwrap();
+        assert!(json.contains("radial_sector"));
+        assert_eq!(serde_json::from_str::<ServerEvent>(&json).unwrap(), event);
+    }
+
     #[test]
     fn key_action_press_and_release_are_distinct() {
         let press = KeyAction::Press;
@@ -0,0 +1,63 @@
+use crate::ControlMotion;
+
+pub const ABS_X: u16 = 0x00;
+pub const ABS_Y: u16 = 0x01;
+pub const ABS_Z: u16 = 0x02;
+pub const ABS_RX: u16 = 0x03;
+pub const ABS_RY: u16 = 0x04;
+pub const ABS_RZ: u16 = 0x05;
+
+/// Collects the axes of a motion-sensor node, which reports the
+/// accelerometer on ABS_X/Y/Z and the gyro on ABS_RX/RY/RZ, into one
+/// `ControlMotion` per frame.
+#[derive(Debug, Default)]
+pub struct MotionTracker {
+    sample: ControlMotion,
+    changed: bool,
+}
+
+impl MotionTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds one absolute axis event.
+    pub fn axis(&mut self, code: u16, value: i32) {
+        let slot = match code {
+            ABS_X => &mut self.sample.accel[0],
+            ABS_Y => &mut self.sample.accel[1],
+            ABS_Z => &mut self.sample.accel[2],
+            ABS_RX => &mut self.sample.gyro[0],
+            ABS_RY => &mut self.sample.gyro[1],
+            ABS_RZ => &mut self.sample.gyro[2],
+            _ => return,
+        };
+        *slot = value;
+        self.changed = true;
+    }
+
+    /// Ends a frame (`SYN_REPORT`) and returns the sample if anything
+    /// changed in it.
+    pub fn sync(&mut self) -> Option<ControlMotion> {
+        std::mem::take(&mut self.changed).then_some(self.sample)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_keep_unchanged_axes() {
+        let mut tracker = MotionTracker::new();
+        tracker.axis(ABS_RY, 2048);
+        tracker.axis(ABS_Z, 8192);
+        let sample = tracker.sync().unwrap();
+        assert_eq!(sample.gyro, [0, 2048, 0]);
+        assert_eq!(sample.accel, [0, 0, 8192]);
+        assert_eq!(tracker.sync(), None);
+
+        tracker.axis(ABS_RX, -100);
+        assert_eq!(tracker.sync().unwrap().gyro, [-100, 2048, 0]);
+    }
+}
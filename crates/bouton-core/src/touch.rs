@@ -0,0 +1,175 @@
+use crate::ControlTouch;
+
+pub const ABS_MT_SLOT: u16 = 0x2f;
+pub const ABS_MT_POSITION_X: u16 = 0x35;
+pub const ABS_MT_POSITION_Y: u16 = 0x36;
+pub const ABS_MT_TRACKING_ID: u16 = 0x39;
+
+const MAX_SLOTS: usize = 10;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Slot {
+    active: bool,
+    x: i32,
+    y: i32,
+    changed: bool,
+}
+
+/// Follows the evdev multi-touch slot protocol of a touchpad node and turns
+/// each frame into one `ControlTouch` per finger that changed.
+#[derive(Debug, Default)]
+pub struct TouchTracker {
+    slot: usize,
+    slots: [Slot; MAX_SLOTS],
+}
+
+impl TouchTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feeds one absolute axis event. Codes other than the multi-touch ones
+    /// are ignored.
+    pub fn axis(&mut self, code: u16, value: i32) {
+        if code == ABS_MT_SLOT {
+            self.slot = value.clamp(0, MAX_SLOTS as i32 - 1) as usize;
+            return;
+        }
+
+        let slot = &mut self.slots[self.slot];
+        match code {
+            ABS_MT_TRACKING_ID => slot.active = value >= 0,
+            ABS_MT_POSITION_X => slot.x = value,
+            ABS_MT_POSITION_Y => slot.y = value,
+            _ => return,
+        }
+        slot.changed = true;
+    }
+
+    /// Ends a frame (`SYN_REPORT`) and returns the fingers that changed in it.
+    pub fn sync(&mut self) -> Vec<ControlTouch> {
+        let mut touches = Vec::new();
+        for (index, slot) in self.slots.iter_mut().enumerate() {
+            if !slot.changed {
+                continue;
+            }
+            slot.changed = false;
+            touches.push(ControlTouch {
+                slot: index as u8,
+                position: slot.active.then_some((slot.x, slot.y)),
+            });
+        }
+        touches
+    }
+}
+
+/// One line of a recorded touch trace: milliseconds since the recording
+/// started, the slot, then `x y`, or `-` once the finger lifts.
+pub fn trace_line(ms: u64, touch: &ControlTouch) -> String {
+    match touch.position {
+        Some((x, y)) => format!("{} {} {} {}", ms, touch.slot, x, y),
+        None => format!("{} {} -", ms, touch.slot),
+    }
+}
+
+/// Reads a trace written with `trace_line`. Blank lines and lines starting
+/// with `#` are skipped.
+pub fn parse_trace(trace: &str) -> Result<Vec<(u64, ControlTouch)>, String> {
+    let mut touches = Vec::new();
+    for (number, line) in trace.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let touch =
+            parse_trace_fields(&fields).map_err(|e| format!("line {}: {}", number + 1, e))?;
+        touches.push(touch);
+    }
+    Ok(touches)
+}
+
+fn parse_trace_fields(fields: &[&str]) -> Result<(u64, ControlTouch), String> {
+    let (ms, slot, position) = match fields {
+        [ms, slot, "-"] => (ms, slot, None),
+        [ms, slot, x, y] => (ms, slot, Some((parse_field(x)?, parse_field(y)?))),
+        _ => return Err("expected `ms slot x y` or `ms slot -`".to_string()),
+    };
+    let touch = ControlTouch {
+        slot: parse_field(slot)?,
+        position,
+    };
+    Ok((parse_field(ms)?, touch))
+}
+
+fn parse_field<T: std::str::FromStr>(field: &str) -> Result<T, String> {
+    field.parse().map_err(|_| format!("bad number {:?}", field))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_report_changed_fingers() {
+        let mut tracker = TouchTracker::new();
+        tracker.axis(ABS_MT_SLOT, 0);
+        tracker.axis(ABS_MT_TRACKING_ID, 7);
+        tracker.axis(ABS_MT_POSITION_X, 100);
+        tracker.axis(ABS_MT_POSITION_Y, 200);
+        assert_eq!(
+            tracker.sync(),
+            vec![ControlTouch {
+                slot: 0,
+                position: Some((100, 200)),
+            }]
+        );
+        assert!(tracker.sync().is_empty());
+
+        // Second finger lands while the first one moves
+        tracker.axis(ABS_MT_POSITION_X, 110);
+        tracker.axis(ABS_MT_SLOT, 1);
+        tracker.axis(ABS_MT_TRACKING_ID, 8);
+        tracker.axis(ABS_MT_POSITION_X, 500);
+        tracker.axis(ABS_MT_POSITION_Y, 600);
+        assert_eq!(tracker.sync().len(), 2);
+
+        tracker.axis(ABS_MT_SLOT, 0);
+        tracker.axis(ABS_MT_TRACKING_ID, -1);
+        assert_eq!(
+            tracker.sync(),
+            vec![ControlTouch {
+                slot: 0,
+                position: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn traces_read_back_what_was_written() {
+        let touches = vec![
+            (
+                0,
+                ControlTouch {
+                    slot: 0,
+                    position: Some((960, 540)),
+                },
+            ),
+            (
+                35,
+                ControlTouch {
+                    slot: 1,
+                    position: None,
+                },
+            ),
+        ];
+        let trace: String = touches
+            .iter()
+            .map(|(ms, touch)| trace_line(*ms, touch) + "\n")
+            .collect();
+        assert_eq!(trace, "0 0 960 540\n35 1 -\n");
+        assert_eq!(parse_trace(&format!("# comment\n\n{}", trace)), Ok(touches));
+        assert!(parse_trace("10 0 5").is_err());
+        assert!(parse_trace("10 x -").is_err());
+    }
+}
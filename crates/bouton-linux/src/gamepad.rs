@@ -1,25 +1,34 @@
+use bouton_core::motion::MotionTracker;
+use bouton_core::touch::{self, TouchTracker};
 use bouton_core::{GamepadEvent, ControlEvent};
-use evdev::Device;
-use std::path::Path;
+use evdev::{Device, EventType};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 use tokio::sync::mpsc;
 
 pub struct GamepadReader {
+    name: Option<String>,
+    tx: mpsc::UnboundedSender<ControlEvent>,
     rx: mpsc::UnboundedReceiver<ControlEvent>,
 }
 
 impl GamepadReader {
     pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
         let mut device = Device::open(path)?;
+        let name = device.name().map(str::to_string);
         let (tx, rx) = mpsc::unbounded_channel();
-        
+        let device_tx = tx.clone();
+
         tokio::spawn(async move {
             loop {
                 if let Ok(events) = device.fetch_events() {
                     for event in events {
-                        if let Some(gamepad_event) = GamepadEvent::from_evdev(event) {
-                            if let Some(control_event) = gamepad_event.to_control() {
-                                let _ = tx.send(control_event);
-                            }
+                        if let Some(gamepad_event) = GamepadEvent::from_evdev(event)
+                            && let Some(control_event) = gamepad_event.to_control()
+                        {
+                            let _ = device_tx.send(control_event);
                         }
                     }
                 }
@@ -27,8 +36,94 @@ impl GamepadReader {
                 tokio::time::sleep(tokio::time::Duration::from_millis(1)).await;
             }
         });
-        
-        Ok(Self { rx })
+
+        Ok(Self { name, tx, rx })
+    }
+
+    /// The touchpad node hid-playstation creates next to the gamepad, named
+    /// after it with a " Touchpad" suffix.
+    pub fn find_touchpad(&self) -> Option<PathBuf> {
+        self.find_sibling("Touchpad")
+    }
+
+    /// The motion-sensor node, named after the gamepad with a
+    /// " Motion Sensors" suffix.
+    pub fn find_motion_sensors(&self) -> Option<PathBuf> {
+        self.find_sibling("Motion Sensors")
+    }
+
+    fn find_sibling(&self, suffix: &str) -> Option<PathBuf> {
+        let sibling_name = format!("{} {}", self.name.as_ref()?, suffix);
+        evdev::enumerate()
+            .find(|(_, device)| device.name() == Some(sibling_name.as_str()))
+            .map(|(path, _)| path)
+    }
+
+    /// Reads finger positions from a touchpad node into the same event
+    /// stream as the gamepad. With a `trace` file every finger is also
+    /// written to it as a touch trace, timed by the kernel's event clock.
+    pub fn open_touchpad(&self, path: impl AsRef<Path>, mut trace: Option<File>) -> std::io::Result<()> {
+        let mut device = Device::open(path)?;
+        let tx = self.tx.clone();
+
+        tokio::spawn(async move {
+            // The node also reports single-touch ABS_X/ABS_Y, which share
+            // their codes with the left stick, so only multi-touch is read
+            let mut tracker = TouchTracker::new();
+            let mut trace_start: Option<SystemTime> = None;
+            loop {
+                if let Ok(events) = device.fetch_events() {
+                    for event in events {
+                        match event.event_type() {
+                            EventType::ABSOLUTE => tracker.axis(event.code(), event.value()),
+                            EventType::SYNCHRONIZATION => {
+                                for touch in tracker.sync() {
+                                    if let Some(file) = trace.as_mut() {
+                                        let start = *trace_start.get_or_insert(event.timestamp());
+                                        let elapsed = event.timestamp().duration_since(start).unwrap_or_default();
+                                        let _ = writeln!(file, "{}", touch::trace_line(elapsed.as_millis() as u64, &touch));
+                                    }
+                                    let _ = tx.send(ControlEvent::Touch(touch));
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                tokio::time::sleep(tokio::time::Duration::from_millis(1)).await;
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Reads gyro and accelerometer samples from a motion-sensor node into
+    /// the same event stream, one event per sensor report.
+    pub fn open_motion_sensors(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let mut device = Device::open(path)?;
+        let tx = self.tx.clone();
+
+        tokio::spawn(async move {
+            let mut tracker = MotionTracker::new();
+            loop {
+                if let Ok(events) = device.fetch_events() {
+                    for event in events {
+                        match event.event_type() {
+                            EventType::ABSOLUTE => tracker.axis(event.code(), event.value()),
+                            EventType::SYNCHRONIZATION => {
+                                if let Some(motion) = tracker.sync() {
+                                    let _ = tx.send(ControlEvent::Motion(motion));
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                tokio::time::sleep(tokio::time::Duration::from_millis(1)).await;
+            }
+        });
+
+        Ok(())
     }
 
     pub fn try_recv(&mut self) -> Vec<ControlEvent> {
@@ -12,6 +12,7 @@ use gamepad::GamepadReader;
 use ratatui::backend::CrosstermBackend;
 use ratatui::Terminal;
 use socket_client::SocketClient;
+use std::fs::File;
 use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
@@ -20,10 +21,16 @@ use ui::GamepadState;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args: Vec<String> = std::env::args().collect();
+    let mut args: Vec<String> = std::env::args().collect();
+
+    // `--record-touch <file>` also writes touchpad fingers to a trace file
+    let record_touch = args.iter().position(|arg| arg == "--record-touch").map(|index| {
+        args.remove(index);
+        (index < args.len()).then(|| args.remove(index))
+    });
     
-    if args.len() < 2 {
-        eprintln!("Usage: {} <gamepad_device> [server_addr]", args[0]);
+    if args.len() < 2 || record_touch == Some(None) {
+        eprintln!("Usage: {} <gamepad_device> [server_addr] [--record-touch <file>]", args[0]);
         eprintln!("Example: {} /dev/input/event0 127.0.0.1:8000", args[0]);
         std::process::exit(1);
     }
@@ -46,6 +53,35 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     };
 
+    let touch_trace = match record_touch.flatten() {
+        Some(trace_path) => match File::create(&trace_path) {
+            Ok(file) => Some(file),
+            Err(e) => {
+                eprintln!("Error creating touch trace {}: {}", trace_path, e);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    // Forward touchpad fingers too when the driver exposes a touchpad node
+    if let Some(touchpad_path) = gamepad.find_touchpad() {
+        match gamepad.open_touchpad(&touchpad_path, touch_trace) {
+            Ok(()) => println!("Reading touchpad at {}", touchpad_path.display()),
+            Err(e) => eprintln!("Error opening touchpad at {}: {}", touchpad_path.display(), e),
+        }
+    } else if touch_trace.is_some() {
+        eprintln!("No touchpad found, nothing to record");
+    }
+
+    // And gyro and accelerometer samples from the motion-sensor node
+    if let Some(motion_path) = gamepad.find_motion_sensors() {
+        match gamepad.open_motion_sensors(&motion_path) {
+            Ok(()) => println!("Reading motion sensors at {}", motion_path.display()),
+            Err(e) => eprintln!("Error opening motion sensors at {}: {}", motion_path.display(), e),
+        }
+    }
+
     // Setup terminal first
     enable_raw_mode()?;
     let mut stdout = std::io::stdout();
@@ -78,12 +114,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Main loop
     loop {
         // Handle input events
-        if event::poll(std::time::Duration::from_millis(100))? {
-            if let Event::Key(key) = event::read()? {
-                if key.code == KeyCode::Char('q') || key.code == KeyCode::Esc {
-                    break;
-                }
-            }
+        if event::poll(std::time::Duration::from_millis(100))?
+            && let Event::Key(key) = event::read()?
+            && (key.code == KeyCode::Char('q') || key.code == KeyCode::Esc)
+        {
+            break;
         }
 
         // Update server connection status from background task
@@ -120,16 +155,23 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 state.update(&event);
 
                 let mut client_guard = client.lock().await;
-                if let Some(ref mut c) = client_guard.as_mut() {
-                    if c.send_event(event).await.is_err() {
-                        // Server disconnected
-                        *client_guard = None;
-                        *state_server_status.lock().await = ui::ConnectionState::Error;
-                    }
+                if let Some(ref mut c) = client_guard.as_mut()
+                    && c.send_event(event).await.is_err()
+                {
+                    // Server disconnected
+                    *client_guard = None;
+                    *state_server_status.lock().await = ui::ConnectionState::Error;
                 }
             }
         }
 
+        // Read feedback from the server, e.g. radial-menu selections
+        if let Some(c) = client.lock().await.as_mut() {
+            while let Some(server_event) = c.try_recv_event() {
+                state.update_server(&server_event);
+            }
+        }
+
         // Render UI (always, even if no events)
         terminal.draw(|f| {
             ui::draw(f, &state);
@@ -1,4 +1,4 @@
-use bouton_core::ControlEvent;
+use bouton_core::{ControlEvent, ServerEvent};
 use std::net::SocketAddr;
 use tokio::net::UdpSocket;
 
@@ -20,4 +20,14 @@ impl SocketClient {
         self.socket.send_to(&bytes, self.server_addr).await?;
         Ok(())
     }
+
+    /// Next feedback datagram from the server, if one is waiting.
+    pub fn try_recv_event(&mut self) -> Option<ServerEvent> {
+        let mut buf = [0u8; 1024];
+        let (n, addr) = self.socket.try_recv_from(&mut buf).ok()?;
+        if addr != self.server_addr {
+            return None;
+        }
+        bincode::deserialize(&buf[..n]).ok()
+    }
 }
@@ -1,4 +1,4 @@
-use bouton_core::{ControlEvent, KeyAction, control::GamepadControl};
+use bouton_core::{ControlEvent, ControlMotion, KeyAction, ServerEvent, control::GamepadControl};
 use ratatui::{
     Frame,
     layout::{Constraint, Direction, Layout, Rect},
@@ -6,7 +6,7 @@ use ratatui::{
     text::{Line, Span},
     widgets::{Block, Borders, Paragraph},
 };
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::collections::VecDeque;
 
 const MAX_LOG_LINES: usize = 50;
@@ -21,6 +21,15 @@ pub enum ConnectionState {
 pub struct GamepadState {
     pub buttons: HashMap<GamepadControl, bool>,
     pub axes: HashMap<GamepadControl, i32>,
+    /// Fingers on the touchpad by slot
+    pub touches: BTreeMap<u8, (i32, i32)>,
+    /// Latest motion-sensor sample
+    pub motion: Option<ControlMotion>,
+    /// Radial-menu sticks reported by the server: selected sector and key,
+    /// and sector count
+    pub radial: HashMap<GamepadControl, (Option<(u8, String)>, u8)>,
+    /// Raw and smoothed values of the axes the server smooths
+    pub smoothed: HashMap<GamepadControl, (i32, i32)>,
     pub log: VecDeque<String>,
     pub gamepad_state: ConnectionState,
     pub gamepad_error: Option<String>,
@@ -33,6 +42,10 @@ impl GamepadState {
         Self {
             buttons: HashMap::new(),
             axes: HashMap::new(),
+            touches: BTreeMap::new(),
+            motion: None,
+            radial: HashMap::new(),
+            smoothed: HashMap::new(),
             log: VecDeque::new(),
             gamepad_state: ConnectionState::Connecting,
             gamepad_error: None,
@@ -61,6 +74,43 @@ impl GamepadState {
                     axis.control, axis.value
                 ));
             }
+            ControlEvent::Touch(touch) => {
+                match touch.position {
+                    Some((x, y)) => {
+                        self.touches.insert(touch.slot, (x, y));
+                        self.add_log(format!("Touch {}: {}, {}", touch.slot, x, y));
+                    }
+                    None => {
+                        self.touches.remove(&touch.slot);
+                        self.add_log(format!("Touch {}: lifted", touch.slot));
+                    }
+                }
+            }
+            ControlEvent::Motion(motion) => {
+                // Too frequent to log, shown in the axes panel instead
+                self.motion = Some(*motion);
+            }
+        }
+    }
+
+    pub fn update_server(&mut self, event: &ServerEvent) {
+        match event {
+            ServerEvent::RadialSector {
+                control,
+                sector,
+                sectors,
+                key,
+            } => {
+                let selected = sector.zip(key.clone());
+                self.radial.insert(*control, (selected, *sectors));
+            }
+            ServerEvent::AxisSmoothed {
+                control,
+                raw,
+                value,
+            } => {
+                self.smoothed.insert(*control, (*raw, *value));
+            }
         }
     }
 
@@ -194,13 +244,62 @@ fn draw_axes(f: &mut Frame, state: &GamepadState, area: Rect) {
     let mut text = vec![];
 
     for control in axes {
-        let value = state.axes.get(&control).copied().unwrap_or(0);
+        // Smoothed axes show the server's raw and filtered values side by
+        // side, for tuning the filter
+        let line = match state.smoothed.get(&control) {
+            Some((raw, value)) => Span::styled(
+                format!("  {}: {:6} -> {:6}", control, raw, value),
+                Style::default().fg(Color::Magenta),
+            ),
+            None => Span::styled(
+                format!("  {}: {:6}", control, state.axes.get(&control).copied().unwrap_or(0)),
+                Style::default().fg(Color::Cyan),
+            ),
+        };
+        text.push(Line::from(line));
+    }
+
+    for (slot, (x, y)) in &state.touches {
         text.push(Line::from(Span::styled(
-            format!("  {}: {:6}", control, value),
+            format!("  Touch {}: {:5}, {:5}", slot, x, y),
             Style::default().fg(Color::Cyan),
         )));
     }
 
+    if let Some(motion) = &state.motion {
+        let [pitch, yaw, roll] = motion.gyro;
+        let [x, y, z] = motion.accel;
+        text.push(Line::from(Span::styled(
+            format!("  Gyro: {:6}, {:6}, {:6}", pitch, yaw, roll),
+            Style::default().fg(Color::Cyan),
+        )));
+        text.push(Line::from(Span::styled(
+            format!("  Accel: {:6}, {:6}, {:6}", x, y, z),
+            Style::default().fg(Color::Cyan),
+        )));
+    }
+
+    for control in [GamepadControl::LeftStickX, GamepadControl::RightStickX] {
+        let Some((selected, sectors)) = state.radial.get(&control) else {
+            continue;
+        };
+        let stick = match control {
+            GamepadControl::LeftStickX => "Left Stick",
+            _ => "Right Stick",
+        };
+        let line = match selected {
+            Some((sector, key)) => Span::styled(
+                format!("  {} menu: {}/{} {}", stick, sector + 1, sectors, key),
+                Style::default().fg(Color::Green),
+            ),
+            None => Span::styled(
+                format!("  {} menu: centre", stick),
+                Style::default().fg(Color::Gray),
+            ),
+        };
+        text.push(Line::from(line));
+    }
+
     let block = Block::default().title("Axes").borders(Borders::ALL);
     let paragraph = Paragraph::new(text).block(block);
     f.render_widget(paragraph, area);
@@ -71,24 +71,21 @@ fn get_wsl_ip() -> String {
 
     // Run 'ip route show' in WSL to find the default gateway (Windows host IP)
     let output = Command::new("wsl")
-        .args(&["sh", "-c", "ip route show | grep default"])
+        .args(["sh", "-c", "ip route show | grep default"])
         .output();
 
-    match output {
-        Ok(result) => {
-            if result.status.success() {
-                let line = String::from_utf8_lossy(&result.stdout);
-                let parts: Vec<&str> = line.trim().split_whitespace().collect();
-                if parts.len() >= 3 {
-                    let ip = parts[2];
-                    if !ip.is_empty() {
-                        println!("Detected Windows IP: {}\n", ip);
-                        return ip.to_string();
-                    }
+    if let Ok(result) = output {
+        if result.status.success() {
+            let line = String::from_utf8_lossy(&result.stdout);
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() >= 3 {
+                let ip = parts[2];
+                if !ip.is_empty() {
+                    println!("Detected Windows IP: {}\n", ip);
+                    return ip.to_string();
                 }
             }
         }
-        Err(_) => {}
     }
 
     // Fallback to manual entry
@@ -120,7 +117,7 @@ fn attach_usb_device(busid: &str) {
     println!("Attaching USB device {}...\n", busid);
 
     let output = Command::new("usbipd")
-        .args(&["attach", "--wsl", &format!("--busid={}", busid)])
+        .args(["attach", "--wsl", &format!("--busid={}", busid)])
         .output();
 
     match output {
@@ -149,38 +146,35 @@ fn get_linux_event_device() -> String {
         println!("Running 'ls /dev/input/event*' on WSL...\n");
 
         let output = Command::new("wsl")
-            .args(&["ls", "-1", "/dev/input/event*"])
+            .args(["ls", "-1", "/dev/input/event*"])
             .output();
 
-        match output {
-            Ok(result) => {
-                if result.status.success() {
-                    let devices = String::from_utf8_lossy(&result.stdout);
-                    let event_list: Vec<&str> = devices.lines().collect();
-
-                    if !event_list.is_empty() {
-                        println!("Found {} event device(s):", event_list.len());
-                        for (i, dev) in event_list.iter().enumerate() {
-                            println!("  [{}] {}", i + 1, dev);
-                        }
-
-                        print!("\nSelect device [1]: ");
-                        io::stdout().flush().unwrap();
-
-                        let mut input = String::new();
-                        io::stdin().read_line(&mut input).unwrap();
-
-                        let idx = input.trim().parse::<usize>().unwrap_or(1) - 1;
-                        println!();
-                        if idx < event_list.len() {
-                            return event_list[idx].to_string();
-                        } else {
-                            return event_list[0].to_string();
-                        }
+        if let Ok(result) = output {
+            if result.status.success() {
+                let devices = String::from_utf8_lossy(&result.stdout);
+                let event_list: Vec<&str> = devices.lines().collect();
+
+                if !event_list.is_empty() {
+                    println!("Found {} event device(s):", event_list.len());
+                    for (i, dev) in event_list.iter().enumerate() {
+                        println!("  [{}] {}", i + 1, dev);
+                    }
+
+                    print!("\nSelect device [1]: ");
+                    io::stdout().flush().unwrap();
+
+                    let mut input = String::new();
+                    io::stdin().read_line(&mut input).unwrap();
+
+                    let idx = input.trim().parse::<usize>().unwrap_or(1) - 1;
+                    println!();
+                    if idx < event_list.len() {
+                        return event_list[idx].to_string();
+                    } else {
+                        return event_list[0].to_string();
                     }
                 }
             }
-            Err(_) => {}
         }
 
         println!("No event devices found.");
@@ -209,7 +203,7 @@ fn launch_windows_server(config_path: &str) {
     println!("Launching bouton-windows.exe...\n");
 
     let status = Command::new("cmd")
-        .args(&["/C", "start", "bouton-windows.exe", config_path])
+        .args(["/C", "start", "bouton-windows.exe", config_path])
         .status();
 
     match status {
@@ -234,7 +228,7 @@ fn launch_linux_client(event_device: &str, wsl_ip: &str) {
 
     // Find bouton-linux using which
     let which_output = Command::new("wsl")
-        .args(&["which", "bouton-linux"])
+        .args(["which", "bouton-linux"])
         .output();
 
     let client_path = if let Ok(result) = which_output {
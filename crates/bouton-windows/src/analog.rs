@@ -0,0 +1,580 @@
+use serde::{Deserialize, Serialize};
+
+/// How the inner deadzone of a stick is applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeadzoneShape {
+    /// Each axis has its own deadzone, giving a cross-shaped dead area.
+    Axial,
+    /// A circular deadzone; outside it the raw deflection passes through.
+    Radial,
+    /// A circular deadzone with the remaining range rescaled to start at 0.
+    ScaledRadial,
+    /// Per-axis deadzone that widens as the other axis is deflected, so
+    /// pushing straight up doesn't also catch left or right.
+    Adaptive,
+}
+
+/// Maps deflection (0 to 1, after deadzones) onto output strength.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseCurve {
+    #[default]
+    Linear,
+    /// `output = input ^ exponent`; above 1 gives finer control near center.
+    Exponential(f32),
+    /// Piecewise-linear curve through `[input, output]` points.
+    Points(Vec<[f32; 2]>),
+}
+
+impl ResponseCurve {
+    pub fn apply(&self, input: f32) -> f32 {
+        match self {
+            ResponseCurve::Linear => input,
+            ResponseCurve::Exponential(exponent) => input.powf(*exponent),
+            ResponseCurve::Points(points) => {
+                let Some(first) = points.first() else {
+                    return input;
+                };
+                if input <= first[0] {
+                    return first[1];
+                }
+                for pair in points.windows(2) {
+                    let ([x0, y0], [x1, y1]) = (pair[0], pair[1]);
+                    if input <= x1 {
+                        if x1 <= x0 {
+                            return y1;
+                        }
+                        return y0 + (y1 - y0) * (input - x0) / (x1 - x0);
+                    }
+                }
+                points[points.len() - 1][1]
+            }
+        }
+    }
+}
+
+/// The part of the pipeline shared by sticks and triggers: everything after
+/// the inner deadzone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    /// Deflection (0 to 1) past which output is saturated
+    pub outer_deadzone: f32,
+    /// Smallest non-zero output, to step over a game's own deadzone
+    pub anti_deadzone: f32,
+    pub curve: ResponseCurve,
+}
+
+impl Default for Response {
+    fn default() -> Self {
+        Self {
+            outer_deadzone: 1.0,
+            anti_deadzone: 0.0,
+            curve: ResponseCurve::Linear,
+        }
+    }
+}
+
+impl Response {
+    /// Rescales `magnitude` from `inner..outer_deadzone` to `0..1`, then
+    /// applies the curve and anti-deadzone.
+    fn apply(&self, magnitude: f32, inner: f32) -> f32 {
+        let span = (self.outer_deadzone - inner).max(f32::EPSILON);
+        let scaled = ((magnitude - inner) / span).clamp(0.0, 1.0);
+        let curved = self.curve.apply(scaled).clamp(0.0, 1.0);
+        self.anti_deadzone + (1.0 - self.anti_deadzone) * curved
+    }
+}
+
+/// Turns raw stick axis values into a processed deflection in `-1..=1` per
+/// axis, with `y` positive pointing down. A component is exactly 0 while it
+/// is inside the deadzone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StickPipeline {
+    /// Raw value range reported by the device
+    pub range: [i32; 2],
+    pub shape: DeadzoneShape,
+    /// Inner deadzone as a fraction of full deflection
+    pub deadzone: f32,
+    /// Deadzone used while the axis is already held, at or below `deadzone`,
+    /// so a stick resting on the edge doesn't chatter
+    pub release_deadzone: f32,
+    /// How far the adaptive deadzone grows at full perpendicular deflection
+    pub adaptive_range: f32,
+    pub response: Response,
+    pub invert_x: bool,
+    pub invert_y: bool,
+    pub circle_to_square: bool,
+}
+
+impl StickPipeline {
+    /// Raw value the stick rests at.
+    pub fn center(&self) -> i32 {
+        (self.range[0] + self.range[1]) / 2
+    }
+
+    pub fn process(&self, x: i32, y: i32) -> (f32, f32) {
+        self.process_held([x, y], [false, false])
+    }
+
+    /// Distance from the centre as a fraction of full deflection, before
+    /// any deadzone or curve.
+    pub fn deflection(&self, [x, y]: [i32; 2]) -> f32 {
+        normalize_centered(x, self.range)
+            .hypot(normalize_centered(y, self.range))
+            .min(1.0)
+    }
+
+    /// Like `process`, but an axis that is `held` only drops back to 0 once
+    /// it falls inside the release deadzone.
+    pub fn process_held(&self, [x, y]: [i32; 2], held: [bool; 2]) -> (f32, f32) {
+        let band = self.deadzone - self.release_deadzone;
+        let mut nx = normalize_centered(x, self.range);
+        let mut ny = normalize_centered(y, self.range);
+        if self.invert_x {
+            nx = -nx;
+        }
+        if self.invert_y {
+            ny = -ny;
+        }
+
+        let (mut out_x, mut out_y) = match self.shape {
+            DeadzoneShape::Axial => (
+                self.axis(nx, self.held_deadzone(held[0])),
+                self.axis(ny, self.held_deadzone(held[1])),
+            ),
+            DeadzoneShape::Adaptive => {
+                // Each axis's deadzone scales based on the other axis's
+                // deviation, and shrinks by the hysteresis band while held
+                let x_band = if held[0] { band } else { 0.0 };
+                let y_band = if held[1] { band } else { 0.0 };
+                let x_deadzone = self.deadzone.max(self.adaptive_range * ny.abs()) - x_band;
+                let y_deadzone = self.deadzone.max(self.adaptive_range * nx.abs()) - y_band;
+                let out_x = if compute_adaptive_deadzone(
+                    nx.abs() + x_band,
+                    ny.abs(),
+                    self.deadzone,
+                    self.adaptive_range,
+                ) {
+                    0.0
+                } else {
+                    nx.signum() * self.response.apply(nx.abs(), x_deadzone)
+                };
+                let out_y = if compute_adaptive_deadzone(
+                    ny.abs() + y_band,
+                    nx.abs(),
+                    self.deadzone,
+                    self.adaptive_range,
+                ) {
+                    0.0
+                } else {
+                    ny.signum() * self.response.apply(ny.abs(), y_deadzone)
+                };
+                (out_x, out_y)
+            }
+            DeadzoneShape::Radial | DeadzoneShape::ScaledRadial => {
+                let magnitude = nx.hypot(ny);
+                let deadzone = self.held_deadzone(held[0] || held[1]);
+                if magnitude < deadzone || magnitude == 0.0 {
+                    (0.0, 0.0)
+                } else {
+                    let inner = if self.shape == DeadzoneShape::ScaledRadial {
+                        deadzone
+                    } else {
+                        0.0
+                    };
+                    let scale = self.response.apply(magnitude, inner) / magnitude;
+                    (nx * scale, ny * scale)
+                }
+            }
+        };
+
+        if self.circle_to_square {
+            // Stretch the round gate so diagonals can reach full deflection
+            // on both axes
+            let largest = out_x.abs().max(out_y.abs());
+            if largest > 0.0 {
+                let scale = out_x.hypot(out_y) / largest;
+                out_x = (out_x * scale).clamp(-1.0, 1.0);
+                out_y = (out_y * scale).clamp(-1.0, 1.0);
+            }
+        }
+
+        (out_x, out_y)
+    }
+
+    fn held_deadzone(&self, held: bool) -> f32 {
+        if held {
+            self.release_deadzone
+        } else {
+            self.deadzone
+        }
+    }
+
+    fn axis(&self, value: f32, deadzone: f32) -> f32 {
+        if value.abs() < deadzone {
+            0.0
+        } else {
+            value.signum() * self.response.apply(value.abs(), deadzone)
+        }
+    }
+}
+
+/// Turns a raw trigger value into a processed pull in `0..=1`, exactly 0
+/// while at or below the deadzone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TriggerPipeline {
+    pub range: [i32; 2],
+    /// Deadzone as a fraction of the full pull
+    pub deadzone: f32,
+    /// Deadzone used while the trigger is already held
+    pub release_deadzone: f32,
+    pub response: Response,
+    pub invert: bool,
+}
+
+impl TriggerPipeline {
+    /// Pull from 0 to 1 past `deadzone`, a stage threshold or release, after
+    /// the response curve.
+    pub fn process_past(&self, value: i32, deadzone: f32) -> f32 {
+        let pull = self.pull(value);
+
+        if pull <= deadzone {
+            0.0
+        } else {
+            self.response.apply(pull, deadzone)
+        }
+    }
+
+    /// How far the trigger is pulled, 0 to 1, before any deadzone.
+    pub fn pull(&self, value: i32) -> f32 {
+        let [min, max] = self.range;
+        let span = (max - min).max(1) as f32;
+        let pull = ((value - min) as f32 / span).clamp(0.0, 1.0);
+        if self.invert { 1.0 - pull } else { pull }
+    }
+
+    /// Hysteresis band between the press and release deadzones.
+    pub fn band(&self) -> f32 {
+        self.deadzone - self.release_deadzone
+    }
+}
+
+/// One key of a multi-stage trigger, reached once the pull passes
+/// `threshold` and left again at or below `release`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TriggerStage {
+    pub key: u32,
+    pub threshold: f32,
+    pub release: f32,
+    /// Let go of this stage's key once the next stage is reached
+    pub release_on_next: bool,
+}
+
+/// How many stages, in threshold order, `pull` has reached. Stages already
+/// reached (`previous`) only drop out at their release threshold.
+pub fn stage_level(stages: &[TriggerStage], pull: f32, previous: usize) -> usize {
+    stages
+        .iter()
+        .enumerate()
+        .take_while(|(index, stage)| {
+            let threshold = if *index < previous {
+                stage.release
+            } else {
+                stage.threshold
+            };
+            pull > threshold
+        })
+        .count()
+}
+
+/// Keys held at a given stage level.
+pub fn stage_keys(stages: &[TriggerStage], level: usize) -> Vec<u32> {
+    let mut keys = Vec::new();
+    for (index, stage) in stages.iter().take(level).enumerate() {
+        let is_last = index + 1 == level;
+        if (is_last || !stage.release_on_next) && !keys.contains(&stage.key) {
+            keys.push(stage.key);
+        }
+    }
+    keys
+}
+
+/// Maps a raw value onto `-1..=1` around the middle of `range`.
+fn normalize_centered(value: i32, range: [i32; 2]) -> f32 {
+    let [min, max] = range;
+    let half = ((max - min) as f32 / 2.0).max(1.0);
+    let center = min as f32 + half;
+    ((value as f32 - center) / half).clamp(-1.0, 1.0)
+}
+
+/// True when `axis_diff` is inside the adaptive deadzone, which grows with
+/// the perpendicular axis's deflection up to `max_dynamic_deadzone`. All
+/// values are fractions of full deflection.
+pub fn compute_adaptive_deadzone(
+    axis_diff: f32,
+    perpendicular_diff: f32,
+    base_deadzone: f32,
+    max_dynamic_deadzone: f32,
+) -> bool {
+    let dynamic_deadzone = max_dynamic_deadzone * perpendicular_diff;
+
+    // Use whichever is larger: configurable base or dynamic
+    let effective_deadzone = base_deadzone.max(dynamic_deadzone);
+    axis_diff < effective_deadzone
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Raw units out of the 128 either side of center, as a fraction
+    fn units(value: i16) -> f32 {
+        value as f32 / 128.0
+    }
+
+    fn stick(shape: DeadzoneShape) -> StickPipeline {
+        StickPipeline {
+            range: [0, 255],
+            shape,
+            deadzone: 0.2,
+            release_deadzone: 0.2,
+            adaptive_range: units(50),
+            response: Response::default(),
+            invert_x: false,
+            invert_y: false,
+            circle_to_square: false,
+        }
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-3
+    }
+
+    #[test]
+    fn test_adaptive_deadzone_at_center() {
+        let base_deadzone = units(20);
+        let center_diff = 0.0;
+        let perpendicular_diff = 0.0;
+
+        let in_deadzone =
+            compute_adaptive_deadzone(center_diff, perpendicular_diff, base_deadzone, units(50));
+        assert!(in_deadzone, "At center (0, 0), should be in deadzone");
+    }
+
+    #[test]
+    fn test_adaptive_deadzone_just_outside_base() {
+        let base_deadzone = units(20);
+        let axis_diff = units(21);
+        let perpendicular_diff = 0.0;
+
+        let in_deadzone =
+            compute_adaptive_deadzone(axis_diff, perpendicular_diff, base_deadzone, units(50));
+        assert!(
+            !in_deadzone,
+            "At 21 with base deadzone of 20 and perpendicular at center, should be outside deadzone"
+        );
+    }
+
+    #[test]
+    fn test_adaptive_deadzone_scales_all_directions() {
+        let base_deadzone = units(20);
+        let max_deflection = units(128); // Maximum perpendicular deflection
+
+        // Dynamic deadzone at max perpendicular: 50 * (128/128) = 50
+        // Effective = max(20, 50) = 50
+        // So axis_diff of 49 should be inside (49 < 50)
+        let inside_dynamic =
+            compute_adaptive_deadzone(units(49), max_deflection, base_deadzone, units(50));
+        assert!(inside_dynamic, "49 should be inside dynamic deadzone of 50");
+
+        // axis_diff of 50 should be outside (50 < 50 is false)
+        let outside_dynamic =
+            compute_adaptive_deadzone(units(50), max_deflection, base_deadzone, units(50));
+        assert!(
+            !outside_dynamic,
+            "50 should be outside dynamic deadzone of 50"
+        );
+
+        // Test all four directions: forward, backward, left, right
+        for direction in &["forward", "backward", "left", "right"] {
+            let in_deadzone =
+                compute_adaptive_deadzone(units(49), max_deflection, base_deadzone, units(50));
+            assert!(
+                in_deadzone,
+                "When pushing {}, deflection of 49 should be in dynamic deadzone",
+                direction
+            );
+        }
+    }
+
+    #[test]
+    fn test_base_deadzone_at_center() {
+        let base_deadzone = units(20);
+
+        // At center, dynamic deadzone = 0, so effective = max(20, 0) = 20
+        assert!(
+            compute_adaptive_deadzone(units(19), 0.0, base_deadzone, units(50)),
+            "19 should be in deadzone"
+        );
+        assert!(
+            !compute_adaptive_deadzone(units(20), 0.0, base_deadzone, units(50)),
+            "20 should be outside deadzone"
+        );
+    }
+
+    #[test]
+    fn axial_deadzone_rescales_each_axis() {
+        let pipeline = stick(DeadzoneShape::Axial);
+        assert_eq!(pipeline.process(128, 140), (0.0, 0.0));
+
+        let (x, y) = pipeline.process(255, 128);
+        assert!(close(x, 1.0));
+        assert_eq!(y, 0.0);
+
+        // Half way between the deadzone edge and full deflection
+        let (x, _) = pipeline.process((127.5 + 0.6 * 127.5) as i32, 128);
+        assert!(close(x, 0.5), "got {}", x);
+    }
+
+    #[test]
+    fn radial_deadzone_keeps_small_diagonals_together() {
+        let pipeline = stick(DeadzoneShape::Radial);
+        // 0.15 on both axes is inside an axial deadzone of 0.2 but has a
+        // magnitude of ~0.21
+        let raw = (127.5 + 0.15 * 127.5) as i32;
+        let (x, y) = pipeline.process(raw, raw);
+        assert!(x > 0.0 && y > 0.0);
+        assert!(close(x, y));
+
+        let (x, y) = stick(DeadzoneShape::ScaledRadial).process(raw, raw);
+        assert!(x > 0.0 && x < 0.1 && close(x, y));
+    }
+
+    #[test]
+    fn adaptive_shape_ignores_slight_wobble_while_pushing_up() {
+        let pipeline = stick(DeadzoneShape::Adaptive);
+        let (x, y) = pipeline.process(155, 0);
+        assert_eq!(x, 0.0);
+        assert!(y < 0.0);
+    }
+
+    #[test]
+    fn outer_and_anti_deadzone() {
+        let mut pipeline = stick(DeadzoneShape::Axial);
+        pipeline.response.outer_deadzone = 0.9;
+        pipeline.response.anti_deadzone = 0.25;
+
+        let (x, _) = pipeline.process((127.5 + 0.92 * 127.5) as i32, 128);
+        assert_eq!(x, 1.0);
+
+        // Just past the deadzone edge jumps to the anti-deadzone
+        let (x, _) = pipeline.process((127.5 + 0.21 * 127.5) as i32, 128);
+        assert!((0.25..0.3).contains(&x), "got {}", x);
+    }
+
+    #[test]
+    fn curves() {
+        assert!(close(ResponseCurve::Exponential(2.0).apply(0.5), 0.25));
+
+        let points = ResponseCurve::Points(vec![[0.0, 0.0], [0.5, 0.2], [1.0, 1.0]]);
+        assert!(close(points.apply(0.25), 0.1));
+        assert!(close(points.apply(0.75), 0.6));
+        assert!(close(points.apply(1.5), 1.0));
+    }
+
+    #[test]
+    fn inversion_flips_axes() {
+        let mut pipeline = stick(DeadzoneShape::Axial);
+        pipeline.invert_y = true;
+        let (_, y) = pipeline.process(128, 0);
+        assert!(close(y, 1.0));
+    }
+
+    #[test]
+    fn circle_to_square_reaches_corners() {
+        let mut pipeline = stick(DeadzoneShape::Radial);
+        pipeline.circle_to_square = true;
+        // Full deflection at 45 degrees on a round gate
+        let raw = (127.5 + std::f32::consts::FRAC_1_SQRT_2 * 127.5).round() as i32;
+        let (x, y) = pipeline.process(raw, raw);
+        assert!(x > 0.99 && y > 0.99, "got {} {}", x, y);
+    }
+
+    #[test]
+    fn trigger_deadzone_matches_threshold() {
+        let pipeline = TriggerPipeline {
+            range: [0, 255],
+            deadzone: 30.0 / 255.0,
+            release_deadzone: 30.0 / 255.0,
+            response: Response::default(),
+            invert: false,
+        };
+        assert_eq!(pipeline.process_past(30, pipeline.deadzone), 0.0);
+        assert!(pipeline.process_past(31, pipeline.deadzone) > 0.0);
+        assert!(close(pipeline.process_past(255, pipeline.deadzone), 1.0));
+    }
+
+    #[test]
+    fn held_trigger_releases_below_band() {
+        let pipeline = TriggerPipeline {
+            range: [0, 255],
+            deadzone: 30.0 / 255.0,
+            release_deadzone: 20.0 / 255.0,
+            response: Response::default(),
+            invert: false,
+        };
+        assert_eq!(pipeline.process_past(25, pipeline.deadzone), 0.0);
+        assert!(pipeline.process_past(25, pipeline.release_deadzone) > 0.0);
+        assert_eq!(pipeline.process_past(20, pipeline.release_deadzone), 0.0);
+    }
+
+    #[test]
+    fn trigger_stages_hold_or_replace() {
+        let stages = [
+            TriggerStage {
+                key: 1,
+                threshold: 0.3,
+                release: 0.25,
+                release_on_next: false,
+            },
+            TriggerStage {
+                key: 2,
+                threshold: 0.95,
+                release: 0.9,
+                release_on_next: false,
+            },
+        ];
+        assert_eq!(stage_level(&stages, 0.2, 0), 0);
+        assert_eq!(stage_level(&stages, 0.5, 0), 1);
+        assert_eq!(stage_level(&stages, 1.0, 1), 2);
+        assert_eq!(stage_keys(&stages, 2), vec![1, 2]);
+
+        // Hysteresis: 0.92 keeps the second stage once reached
+        assert_eq!(stage_level(&stages, 0.92, 1), 1);
+        assert_eq!(stage_level(&stages, 0.92, 2), 2);
+        assert_eq!(stage_level(&stages, 0.27, 1), 1);
+
+        let mut replacing = stages;
+        replacing[0].release_on_next = true;
+        assert_eq!(stage_keys(&replacing, 1), vec![1]);
+        assert_eq!(stage_keys(&replacing, 2), vec![2]);
+    }
+
+    #[test]
+    fn held_stick_axis_uses_release_deadzone() {
+        for shape in [
+            DeadzoneShape::Axial,
+            DeadzoneShape::Adaptive,
+            DeadzoneShape::Radial,
+        ] {
+            let mut pipeline = stick(shape);
+            pipeline.release_deadzone = 0.1;
+            // 0.15 right of center: between the release and press deadzones
+            let raw = (127.5_f32 + 0.15 * 127.5).round() as i32;
+            let center = pipeline.center();
+            assert_eq!(pipeline.process_held([raw, center], [false, false]).0, 0.0);
+            assert!(pipeline.process_held([raw, center], [true, false]).0 > 0.0);
+        }
+    }
+}
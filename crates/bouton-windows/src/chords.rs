@@ -0,0 +1,186 @@
+use crate::config::ChordCodeConfig;
+use bouton_core::KeyAction;
+use bouton_core::control::GamepadControl;
+
+/// A finished chord: its buttons joined with `+`, and the text bound to it
+/// if any.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chord {
+    pub name: String,
+    pub text: Option<String>,
+}
+
+/// Stenotype-style chord entry. Every chord button pressed from the first
+/// press until all of them are up again makes one chord, so the buttons
+/// don't have to go down or come up at the same moment. The toggle button,
+/// if there is one, switches chord entry on and off.
+pub struct ChordRecognizer {
+    config: ChordCodeConfig,
+    active: bool,
+    /// Chord buttons down right now, one bit per button
+    held: u8,
+    /// Every button pressed during the current chord
+    chord: u8,
+}
+
+impl ChordRecognizer {
+    pub fn new(config: ChordCodeConfig) -> Self {
+        let active = config.toggle.is_none();
+        Self {
+            config,
+            active,
+            held: 0,
+            chord: 0,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Buttons chord entry takes over from their normal bindings while on.
+    pub fn buttons(&self) -> &[GamepadControl] {
+        &self.config.buttons
+    }
+
+    /// Whether chord entry takes `control` instead of its normal binding.
+    pub fn consumes(&self, control: GamepadControl) -> bool {
+        Some(control) == self.config.toggle || (self.active && self.bit(control).is_some())
+    }
+
+    /// Takes a press or release of a button it consumes. Returns the chord
+    /// once its last button is let go.
+    pub fn button(&mut self, control: GamepadControl, action: KeyAction) -> Option<Chord> {
+        if Some(control) == self.config.toggle {
+            if action == KeyAction::Press {
+                self.active = !self.active;
+                self.held = 0;
+                self.chord = 0;
+            }
+            return None;
+        }
+
+        let bit = self.bit(control)?;
+        match action {
+            KeyAction::Press => {
+                self.held |= bit;
+                self.chord |= bit;
+                None
+            }
+            KeyAction::Release => {
+                // A release without its press, e.g. from before the mode
+                // was switched on
+                if self.held & bit == 0 {
+                    return None;
+                }
+                self.held &= !bit;
+                if self.held != 0 {
+                    return None;
+                }
+                let chord = std::mem::take(&mut self.chord);
+                Some(Chord {
+                    name: self.name(chord),
+                    text: self.config.table.get(&chord).cloned(),
+                })
+            }
+        }
+    }
+
+    fn bit(&self, control: GamepadControl) -> Option<u8> {
+        self.config
+            .buttons
+            .iter()
+            .position(|&button| button == control)
+            .map(|index| 1 << index)
+    }
+
+    fn name(&self, chord: u8) -> String {
+        self.config
+            .buttons
+            .iter()
+            .enumerate()
+            .filter(|&(index, _)| chord & (1 << index) != 0)
+            .map(|(_, button)| format!("{:?}", button))
+            .collect::<Vec<String>>()
+            .join("+")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ChordConfig;
+    use GamepadControl::{Circle, Cross, L1, Square};
+    use KeyAction::{Press, Release};
+
+    fn recognizer(config: &str) -> ChordRecognizer {
+        let config: ChordConfig = toml::from_str(config).unwrap();
+        ChordRecognizer::new(config.codes().unwrap())
+    }
+
+    fn play(
+        recognizer: &mut ChordRecognizer,
+        events: &[(GamepadControl, KeyAction)],
+    ) -> Vec<Chord> {
+        events
+            .iter()
+            .filter_map(|&(control, action)| recognizer.button(control, action))
+            .collect()
+    }
+
+    #[test]
+    fn staggered_presses_and_releases_make_one_chord() {
+        let mut chords = recognizer("");
+        let typed = play(
+            &mut chords,
+            &[
+                (Cross, Press),
+                (Circle, Press),
+                (Cross, Release),
+                (Square, Press),
+                (Circle, Release),
+                (Square, Release),
+            ],
+        );
+        assert_eq!(
+            typed,
+            vec![Chord {
+                name: "Square+Cross+Circle".to_string(),
+                text: Some("b".to_string()),
+            }]
+        );
+
+        let typed = play(&mut chords, &[(Cross, Press), (Cross, Release)]);
+        assert_eq!(typed[0].text.as_deref(), Some("e"));
+    }
+
+    #[test]
+    fn table_entries_override_the_default() {
+        let mut chords = recognizer(
+            "toggle = \"Select\"\n[table]\n\"Cross+L1\" = \"hello \"\n\"Cross\" = \"E\"",
+        );
+        // Off until toggled on
+        assert!(!chords.consumes(Cross));
+        chords.button(GamepadControl::Select, Press);
+        assert!(chords.is_active());
+
+        let typed = play(
+            &mut chords,
+            &[(L1, Press), (Cross, Press), (L1, Release), (Cross, Release)],
+        );
+        assert_eq!(typed[0].text.as_deref(), Some("hello "));
+        let typed = play(&mut chords, &[(Cross, Press), (Cross, Release)]);
+        assert_eq!(typed[0].text.as_deref(), Some("E"));
+        // Still in the bundled table
+        let typed = play(&mut chords, &[(Circle, Press), (Circle, Release)]);
+        assert_eq!(typed[0].text.as_deref(), Some("t"));
+    }
+
+    #[test]
+    fn rejects_unknown_buttons_in_the_table() {
+        let config: ChordConfig = toml::from_str("[table]\n\"Cross+Start\" = \"x\"").unwrap();
+        assert!(config.codes().is_err());
+        let config: ChordConfig = toml::from_str("buttons = [\"Cross\", \"L2\"]").unwrap();
+        assert!(config.codes().is_err());
+    }
+}
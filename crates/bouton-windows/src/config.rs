@@ -2,12 +2,41 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
-use crate::keycode::KeyCode;
+use std::time::Duration;
+use bouton_core::control::GamepadControl;
+use crate::analog::{
+    DeadzoneShape, Response, ResponseCurve, StickPipeline, TriggerPipeline, TriggerStage,
+};
+use crate::filters::InputFilter;
+use crate::keycode::{KeyCode, code_to_name};
+use crate::smoothing::Smoothing;
+use crate::motion_inputs::MotionPattern;
+use crate::output::Repeat;
+use crate::pwm::Pwm;
+use crate::socd::{SocdMode, SocdPair};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
     pub server: ServerConfig,
     pub keys: KeyMappingConfig,
+    #[serde(default)]
+    pub socd: Vec<SocdConfig>,
+    #[serde(default)]
+    pub motion_inputs: Vec<MotionInputConfig>,
+    #[serde(default)]
+    pub filters: FiltersConfig,
+    /// Smoothing per stick or trigger, keyed like `keys.joysticks` and
+    /// `keys.triggers`
+    #[serde(default)]
+    pub smoothing: HashMap<String, SmoothingConfig>,
+    /// Switch-access scanning through a list of actions
+    pub scanning: Option<ScanningConfig>,
+    /// On-screen keyboard for typing text
+    pub keyboard: Option<KeyboardConfig>,
+    /// Chorded text entry
+    pub chords: Option<ChordConfig>,
+    /// Morse code typed on one button
+    pub morse: Option<MorseConfig>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -18,28 +47,367 @@ pub struct ServerConfig {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct KeyMappingConfig {
-    pub buttons: HashMap<String, KeyCode>,
+    pub buttons: HashMap<String, ButtonConfig>,
     #[serde(default)]
     pub joysticks: HashMap<String, JoystickConfig>,
     #[serde(default)]
     pub triggers: HashMap<String, TriggerConfig>,
     #[serde(default)]
     pub dpad: HashMap<String, DPadConfig>,
+    /// Sticks used as radial menus instead of direction keys
+    #[serde(default)]
+    pub radial: HashMap<String, RadialConfig>,
+    /// Sticks that turn the camera with the mouse instead of direction keys
+    #[serde(default)]
+    pub flick_stick: HashMap<String, FlickStickConfig>,
+    /// DualSense touchpad used as a mouse trackpad
+    pub touchpad: Option<TouchpadConfig>,
+    /// Motion sensor turned into mouse movement
+    pub gyro: Option<GyroConfig>,
+    /// Tilting, shaking or twisting the gamepad sends keys
+    pub motion: Option<MotionConfig>,
+    /// Modifier bindings latch until the next key instead of being held
+    #[serde(default)]
+    pub sticky_modifiers: bool,
+}
+
+/// A button binding, either just a key (`Square = "A"`), a table with
+/// extra options (`Square = { key = "A", toggle = true }`), or text typed on
+/// every press (`Aux1 = { text = "me@example.com" }`). Text takes no other
+/// options, so a table mixing it with `key`, `toggle` or `repeat` is an
+/// error rather than silently losing half of it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum ButtonConfig {
+    Key(KeyCode),
+    Binding {
+        key: KeyCode,
+        #[serde(default)]
+        toggle: bool,
+        repeat: Option<RepeatConfig>,
+    },
+    Text {
+        text: String,
+    },
+}
+
+impl ButtonConfig {
+    pub fn action(&self) -> ButtonAction {
+        match self {
+            ButtonConfig::Key(key) | ButtonConfig::Binding { key, .. } => {
+                ButtonAction::Key(key.code())
+            }
+            ButtonConfig::Text { text } => ButtonAction::Text(text.clone()),
+        }
+    }
+
+    pub fn toggle(&self) -> bool {
+        match self {
+            ButtonConfig::Binding { toggle, .. } => *toggle,
+            ButtonConfig::Key(_) | ButtonConfig::Text { .. } => false,
+        }
+    }
+
+    pub fn repeat(&self) -> Option<Repeat> {
+        match self {
+            ButtonConfig::Binding { repeat, .. } => repeat.as_ref().map(RepeatConfig::repeat),
+            ButtonConfig::Key(_) | ButtonConfig::Text { .. } => None,
+        }
+    }
+}
+
+/// What a bound button sends.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ButtonAction {
+    /// A key held for as long as the button
+    Key(u32),
+    /// Text typed as Unicode characters when the button goes down
+    Text(String),
+}
+
+/// Typematic repeat: a held key is sent down again and again, like a
+/// keyboard key held in a text field.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RepeatConfig {
+    /// Time held before the first repeat (default 500)
+    pub delay_ms: Option<u64>,
+    /// Repeats per second after that (default 30)
+    pub rate: Option<f32>,
+}
+
+impl RepeatConfig {
+    pub fn repeat(&self) -> Repeat {
+        Repeat {
+            delay: Duration::from_millis(self.delay_ms.unwrap_or(500)),
+            interval: Duration::from_micros((1_000_000.0 / self.rate.unwrap_or(30.0).max(0.1)) as u64),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JoystickConfig {
     pub deadzone: Option<u8>,
+    /// Deadzone a held direction has to fall back into before it releases
+    pub release_deadzone: Option<u8>,
+    /// Alternative to `release_deadzone`: how far below `deadzone` it sits
+    pub hysteresis: Option<u8>,
+    /// Shortest time a key state is kept before it may change again
+    pub min_hold_ms: Option<u64>,
     pub up: KeyCode,
     pub down: KeyCode,
     pub left: KeyCode,
     pub right: KeyCode,
+    #[serde(default)]
+    pub toggle: bool,
+    #[serde(flatten)]
+    pub eight_way: EightWayConfig,
+    #[serde(default)]
+    pub analog: StickAnalogConfig,
+    /// Second stage past a larger radius, e.g. holding SHIFT to run
+    pub run: Option<RunConfig>,
+    /// Pulse direction keys in proportion to deflection
+    pub pwm: Option<PwmConfig>,
+    pub repeat: Option<RepeatConfig>,
+}
+
+impl JoystickConfig {
+    pub fn pipeline(&self) -> StickPipeline {
+        // 8-way picks a sector from the whole deflection, so it defaults to
+        // a round deadzone instead of the per-axis adaptive one
+        let default_shape = if self.eight_way.eight_way {
+            DeadzoneShape::Radial
+        } else {
+            DeadzoneShape::Adaptive
+        };
+
+        let deadzone = self.deadzone.unwrap_or(20);
+        self.analog.pipeline(
+            deadzone,
+            release_deadzone(deadzone, self.release_deadzone, self.hysteresis),
+            default_shape,
+        )
+    }
+
+    pub fn pwm_codes(&self) -> Result<Option<Pwm>, String> {
+        pwm_codes(self.pwm.as_ref(), self.toggle)
+    }
+
+    pub fn run_codes(&self) -> Option<RunCodeConfig> {
+        let run = self.run.as_ref()?;
+        let range = self.analog.response.range();
+        let half_range = ((range[1] - range[0]) as f32 / 2.0).max(1.0);
+        let radius = run.radius.unwrap_or(100);
+
+        let replacements = [
+            (self.up, run.up),
+            (self.down, run.down),
+            (self.left, run.left),
+            (self.right, run.right),
+        ]
+        .into_iter()
+        .filter_map(|(walk, run)| run.map(|run| (walk.code(), run.code())))
+        .collect();
+
+        Some(RunCodeConfig {
+            radius: radius as f32 / half_range,
+            release_radius: release_deadzone(radius, run.release_radius, run.hysteresis) as f32
+                / half_range,
+            key: run.key.map(|key| key.code()),
+            replacements,
+        })
+    }
+}
+
+/// Run stage of a stick: past `radius` the stick also holds `key`, and any
+/// direction with its own run key uses that instead of the walk key.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RunConfig {
+    /// Raw units from the centre (default 100)
+    pub radius: Option<u8>,
+    pub release_radius: Option<u8>,
+    pub hysteresis: Option<u8>,
+    /// Modifier held alongside the direction keys
+    pub key: Option<KeyCode>,
+    pub up: Option<KeyCode>,
+    pub down: Option<KeyCode>,
+    pub left: Option<KeyCode>,
+    pub right: Option<KeyCode>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TriggerConfig {
-    pub key: KeyCode,
+    /// Single-stage key, pressed past `deadzone`
+    pub key: Option<KeyCode>,
+    /// Multi-stage keys, used instead of `key`
+    #[serde(default)]
+    pub stages: Vec<TriggerStageConfig>,
+    /// Skip every stage but the last, for users who can't modulate pressure
+    #[serde(default)]
+    pub full_pull_only: bool,
     pub deadzone: Option<u8>,
+    pub release_deadzone: Option<u8>,
+    pub hysteresis: Option<u8>,
+    pub min_hold_ms: Option<u64>,
+    #[serde(default)]
+    pub toggle: bool,
+    #[serde(default)]
+    pub analog: TriggerAnalogConfig,
+    /// Pulse the trigger's keys in proportion to pull
+    pub pwm: Option<PwmConfig>,
+    pub repeat: Option<RepeatConfig>,
+}
+
+impl TriggerConfig {
+    pub fn pwm_codes(&self) -> Result<Option<Pwm>, String> {
+        pwm_codes(self.pwm.as_ref(), self.toggle)
+    }
+
+    pub fn pipeline(&self) -> TriggerPipeline {
+        let deadzone = self.deadzone.unwrap_or(127);
+        let range = self.analog.response.range();
+        let full_range = ((range[1] - range[0]) as f32).max(1.0);
+
+        TriggerPipeline {
+            range,
+            deadzone: deadzone as f32 / full_range,
+            release_deadzone: release_deadzone(deadzone, self.release_deadzone, self.hysteresis)
+                as f32
+                / full_range,
+            response: self.analog.response.response(),
+            invert: self.analog.invert,
+        }
+    }
+
+    /// Stages in threshold order. A plain `key` is a single stage at the
+    /// deadzone; empty when neither `key` nor `stages` is set.
+    pub fn stages(&self) -> Vec<TriggerStage> {
+        let pipeline = self.pipeline();
+        let band = pipeline.band();
+
+        let mut stages: Vec<TriggerStage> = if self.stages.is_empty() {
+            self.key
+                .iter()
+                .map(|key| TriggerStage {
+                    key: key.code(),
+                    threshold: pipeline.deadzone,
+                    release: pipeline.release_deadzone,
+                    release_on_next: false,
+                })
+                .collect()
+        } else {
+            self.stages
+                .iter()
+                .map(|stage| TriggerStage {
+                    key: stage.key.code(),
+                    threshold: stage.threshold,
+                    release: (stage.threshold - band).max(0.0),
+                    release_on_next: stage.release_on_next,
+                })
+                .collect()
+        };
+        stages.sort_by(|a, b| a.threshold.total_cmp(&b.threshold));
+
+        if self.full_pull_only {
+            stages.drain(..stages.len().saturating_sub(1));
+        }
+        stages
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TriggerStageConfig {
+    pub key: KeyCode,
+    /// Fraction of the full pull (0 to 1) that reaches this stage
+    pub threshold: f32,
+    /// Release this stage's key when the next stage is reached, instead of
+    /// keeping both held
+    #[serde(default)]
+    pub release_on_next: bool,
+}
+
+/// Release threshold from an explicit value or a hysteresis band below the
+/// press threshold, never above the press threshold itself.
+fn release_deadzone(deadzone: u8, release: Option<u8>, hysteresis: Option<u8>) -> u8 {
+    release
+        .unwrap_or_else(|| deadzone.saturating_sub(hysteresis.unwrap_or(0)))
+        .min(deadzone)
+}
+
+/// Converts an optional `*_ms` setting into a duration, 0 when unset.
+pub fn millis(ms: Option<u64>) -> Duration {
+    Duration::from_millis(ms.unwrap_or(0))
+}
+
+/// Processing shared by sticks and triggers, applied after the deadzone.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ResponseConfig {
+    /// Raw `[min, max]` reported by the device (default `[0, 255]`)
+    pub range: Option<[i32; 2]>,
+    /// Fraction of full deflection past which output is maxed out
+    pub outer_deadzone: Option<f32>,
+    /// Smallest non-zero output, as a fraction
+    pub anti_deadzone: Option<f32>,
+    #[serde(default)]
+    pub curve: ResponseCurve,
+}
+
+impl ResponseConfig {
+    fn range(&self) -> [i32; 2] {
+        self.range.unwrap_or([0, 255])
+    }
+
+    fn response(&self) -> Response {
+        Response {
+            outer_deadzone: self.outer_deadzone.unwrap_or(1.0),
+            anti_deadzone: self.anti_deadzone.unwrap_or(0.0),
+            curve: self.curve.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StickAnalogConfig {
+    pub deadzone_shape: Option<DeadzoneShape>,
+    /// Raw units the adaptive deadzone grows by at full perpendicular
+    /// deflection (default 50)
+    pub adaptive_range: Option<u8>,
+    #[serde(default)]
+    pub invert_x: bool,
+    #[serde(default)]
+    pub invert_y: bool,
+    #[serde(default)]
+    pub circle_to_square: bool,
+    #[serde(flatten)]
+    pub response: ResponseConfig,
+}
+
+impl StickAnalogConfig {
+    /// Pipeline for raw `deadzone` and `release_deadzone` values, using
+    /// `default_shape` unless the table picks one.
+    fn pipeline(&self, deadzone: u8, release_deadzone: u8, default_shape: DeadzoneShape) -> StickPipeline {
+        let range = self.response.range();
+        let half_range = ((range[1] - range[0]) as f32 / 2.0).max(1.0);
+
+        StickPipeline {
+            range,
+            shape: self.deadzone_shape.unwrap_or(default_shape),
+            deadzone: deadzone as f32 / half_range,
+            release_deadzone: release_deadzone as f32 / half_range,
+            adaptive_range: self.adaptive_range.unwrap_or(50) as f32 / half_range,
+            response: self.response.response(),
+            invert_x: self.invert_x,
+            invert_y: self.invert_y,
+            circle_to_square: self.circle_to_square,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TriggerAnalogConfig {
+    #[serde(default)]
+    pub invert: bool,
+    #[serde(flatten)]
+    pub response: ResponseConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -48,29 +416,1161 @@ pub struct DPadConfig {
     pub down: KeyCode,
     pub left: KeyCode,
     pub right: KeyCode,
+    #[serde(default)]
+    pub toggle: bool,
+    #[serde(flatten)]
+    pub eight_way: EightWayConfig,
+    pub repeat: Option<RepeatConfig>,
 }
 
-#[derive(Debug, Clone, Copy)]
+/// A stick split into one sector per key, clockwise from straight up.
+/// Pointing into a sector selects it; the key fires when the stick returns
+/// to the centre or when `confirm` is pressed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RadialConfig {
+    /// 4 to 12 keys, the first centred on straight up
+    pub sectors: Vec<KeyCode>,
+    /// Raw units from the centre needed to select a sector (default 80)
+    pub deadzone: Option<u8>,
+    pub release_deadzone: Option<u8>,
+    pub hysteresis: Option<u8>,
+    #[serde(default)]
+    pub fire: RadialFire,
+    /// Button that fires the selected sector when `fire = "confirm"`
+    pub confirm: Option<GamepadControl>,
+    #[serde(default)]
+    pub analog: StickAnalogConfig,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RadialFire {
+    /// Flick to a sector and let the stick spring back
+    #[default]
+    Return,
+    /// Point at a sector and press the confirm button
+    Confirm,
+}
+
+pub const RADIAL_SECTORS: std::ops::RangeInclusive<usize> = 4..=12;
+
+impl RadialConfig {
+    pub fn pipeline(&self) -> StickPipeline {
+        let deadzone = self.deadzone.unwrap_or(80);
+        self.analog.pipeline(
+            deadzone,
+            release_deadzone(deadzone, self.release_deadzone, self.hysteresis),
+            DeadzoneShape::Radial,
+        )
+    }
+}
+
+/// Flick stick: pushing the stick turns the camera to face that direction
+/// in one quick mouse movement, and rotating it while pushed keeps turning.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlickStickConfig {
+    /// Horizontal mouse counts that turn the game camera a full circle
+    pub counts_per_360: f32,
+    /// Raw units from the centre needed to flick (default 90)
+    pub deadzone: Option<u8>,
+    pub release_deadzone: Option<u8>,
+    pub hysteresis: Option<u8>,
+    /// Time a flick's turn is spread over (default 100)
+    pub flick_ms: Option<u64>,
+    #[serde(default)]
+    pub analog: StickAnalogConfig,
+}
+
+impl FlickStickConfig {
+    pub fn codes(&self) -> FlickStickCodeConfig {
+        let deadzone = self.deadzone.unwrap_or(90);
+        FlickStickCodeConfig {
+            pipeline: self.analog.pipeline(
+                deadzone,
+                release_deadzone(deadzone, self.release_deadzone, self.hysteresis),
+                DeadzoneShape::Radial,
+            ),
+            counts_per_360: self.counts_per_360,
+            flick_time: Duration::from_millis(self.flick_ms.unwrap_or(100)),
+        }
+    }
+}
+
+/// The touchpad as a trackpad (one finger moves the cursor, two scroll,
+/// taps click) or as a set of gesture keys.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TouchpadConfig {
+    #[serde(default)]
+    pub mode: TouchpadMode,
+    /// Cursor pixels per touchpad unit (default 0.5)
+    pub sensitivity: Option<f32>,
+    /// Touchpad units of finger travel per wheel notch (default 50)
+    pub scroll_step: Option<f32>,
+    /// Scroll the content along with the fingers
+    #[serde(default)]
+    pub natural_scroll: bool,
+    /// Longest touch that still counts as a tap (default 180)
+    pub tap_ms: Option<u64>,
+    /// Furthest a tap may travel, in touchpad units (default 30)
+    pub tap_distance: Option<f32>,
+    /// Raw `[min, max]` on each axis (default `[0, 1919]` and `[0, 1079]`)
+    pub range_x: Option<[i32; 2]>,
+    pub range_y: Option<[i32; 2]>,
+    /// Width of the right edge strip where one finger scrolls vertically,
+    /// as a fraction of the pad (default 0, off)
+    pub edge_scroll_right: Option<f32>,
+    /// Height of the bottom edge strip where one finger scrolls
+    /// horizontally (default 0, off)
+    pub edge_scroll_bottom: Option<f32>,
+    #[serde(default)]
+    pub gestures: TouchGesturesConfig,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TouchpadMode {
+    #[default]
+    Trackpad,
+    Gestures,
+}
+
+/// Keys for touchpad gestures: a tap in a grid region, or a quick swipe
+/// with one or two fingers.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TouchGesturesConfig {
+    /// `[columns, rows]` the pad is split into (default `[2, 2]`)
+    pub grid: Option<[usize; 2]>,
+    /// Tap keys, row by row from the top left
+    #[serde(default)]
+    pub regions: Vec<KeyCode>,
+    /// Shortest travel that counts as a swipe, in touchpad units (default 300)
+    pub swipe_distance: Option<f32>,
+    /// Longest touch that still counts as a swipe (default 500)
+    pub swipe_ms: Option<u64>,
+    #[serde(default)]
+    pub swipe: SwipeKeysConfig,
+    #[serde(default)]
+    pub two_finger_swipe: SwipeKeysConfig,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SwipeKeysConfig {
+    pub up: Option<KeyCode>,
+    pub down: Option<KeyCode>,
+    pub left: Option<KeyCode>,
+    pub right: Option<KeyCode>,
+}
+
+impl SwipeKeysConfig {
+    fn codes(&self) -> [Option<u32>; 4] {
+        [self.up, self.down, self.left, self.right].map(|key| key.map(|key| key.code()))
+    }
+}
+
+impl TouchpadConfig {
+    pub fn codes(&self) -> TouchpadCodeConfig {
+        TouchpadCodeConfig {
+            sensitivity: self.sensitivity.unwrap_or(0.5),
+            scroll_step: self.scroll_step.unwrap_or(50.0),
+            natural_scroll: self.natural_scroll,
+            tap_time: Duration::from_millis(self.tap_ms.unwrap_or(180)),
+            tap_distance: self.tap_distance.unwrap_or(30.0),
+            range_x: self.range_x.unwrap_or([0, 1919]),
+            range_y: self.range_y.unwrap_or([0, 1079]),
+            edge_scroll_right: self.edge_scroll_right.unwrap_or(0.0),
+            edge_scroll_bottom: self.edge_scroll_bottom.unwrap_or(0.0),
+            mode: self.mode,
+            gestures: TouchGesturesCodeConfig {
+                grid: self.gestures.grid.unwrap_or([2, 2]).map(|cells| cells.max(1)),
+                regions: self.gestures.regions.iter().map(|key| key.code()).collect(),
+                swipe_distance: self.gestures.swipe_distance.unwrap_or(300.0),
+                swipe_time: Duration::from_millis(self.gestures.swipe_ms.unwrap_or(500)),
+                swipes: self.gestures.swipe.codes(),
+                two_finger_swipes: self.gestures.two_finger_swipe.codes(),
+            },
+        }
+    }
+}
+
+/// Gyro aiming: turning the gamepad moves the mouse.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GyroConfig {
+    /// Cursor pixels per degree turned (default 10)
+    pub sensitivity: Option<f32>,
+    /// Rotation that moves the cursor sideways (default yaw)
+    #[serde(default)]
+    pub horizontal: GyroHorizontal,
+    #[serde(default)]
+    pub invert_x: bool,
+    #[serde(default)]
+    pub invert_y: bool,
+    /// Turns slower than this many degrees per second are scaled down,
+    /// hiding hand shake without a hard cutoff (default 0, off)
+    pub tightening: Option<f32>,
+    /// Turns slower than this many degrees per second are averaged over
+    /// `smoothing_samples` (default 0, off)
+    pub smoothing: Option<f32>,
+    /// Samples averaged by smoothing (default 8)
+    pub smoothing_samples: Option<usize>,
+    /// Raw units per degree per second (default 1024, hid-playstation)
+    pub resolution: Option<f32>,
+    /// Button that gates the gyro; without one the gyro is always on
+    pub button: Option<GamepadControl>,
+    #[serde(default)]
+    pub button_mode: GyroButtonMode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GyroHorizontal {
+    /// Turning left and right, like a flashlight
+    #[default]
+    Yaw,
+    /// Tilting left and right, like a steering wheel
+    Roll,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GyroButtonMode {
+    /// The gyro only moves the mouse while the button is held
+    #[default]
+    Enable,
+    /// The gyro is paused while the button is held, to re-centre the
+    /// gamepad like lifting a mouse off the desk
+    Ratchet,
+}
+
+impl GyroConfig {
+    pub fn codes(&self) -> GyroCodeConfig {
+        GyroCodeConfig {
+            sensitivity: self.sensitivity.unwrap_or(10.0),
+            horizontal: self.horizontal,
+            invert_x: self.invert_x,
+            invert_y: self.invert_y,
+            tightening: self.tightening.unwrap_or(0.0),
+            smoothing: self.smoothing.unwrap_or(0.0),
+            smoothing_samples: self.smoothing_samples.unwrap_or(8).max(1),
+            resolution: self.resolution.unwrap_or(1024.0),
+            button: self.button,
+            button_mode: self.button_mode,
+        }
+    }
+}
+
+/// Motion gestures: tilt the gamepad away from its neutral orientation to
+/// hold a key, shake it or twist it quickly to tap one.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MotionConfig {
+    /// Button that makes the current orientation the neutral one. The
+    /// orientation when the first sample arrives is used until then.
+    pub calibrate: Option<GamepadControl>,
+    #[serde(default)]
+    pub tilt: TiltConfig,
+    pub shake: Option<ShakeConfig>,
+    #[serde(default)]
+    pub twist: TwistConfig,
+    /// Raw accelerometer units per g (default 8192, hid-playstation)
+    pub accel_resolution: Option<f32>,
+    /// Raw gyro units per degree per second (default 1024)
+    pub gyro_resolution: Option<f32>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TiltConfig {
+    /// Degrees from neutral that press a tilt key (default 30)
+    pub angle: Option<f32>,
+    /// Degrees back towards neutral before it releases (default 5)
+    pub hysteresis: Option<f32>,
+    pub left: Option<KeyCode>,
+    pub right: Option<KeyCode>,
+    pub forward: Option<KeyCode>,
+    pub back: Option<KeyCode>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShakeConfig {
+    pub key: KeyCode,
+    /// Acceleration beyond gravity that counts as a shake, in g (default 1.5)
+    pub threshold: Option<f32>,
+    /// Time before another shake can fire (default 500)
+    pub cooldown_ms: Option<u64>,
+}
+
+/// Quick rotations: turning left or right, or tipping the front up or down.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TwistConfig {
+    /// Degrees per second a twist has to reach (default 360)
+    pub speed: Option<f32>,
+    /// Time before another twist can fire (default 300)
+    pub cooldown_ms: Option<u64>,
+    pub left: Option<KeyCode>,
+    pub right: Option<KeyCode>,
+    pub up: Option<KeyCode>,
+    pub down: Option<KeyCode>,
+}
+
+impl MotionConfig {
+    pub fn codes(&self) -> MotionCodeConfig {
+        let code = |key: Option<KeyCode>| key.map(|key| key.code());
+        let tilt_angle = self.tilt.angle.unwrap_or(30.0);
+        MotionCodeConfig {
+            calibrate: self.calibrate,
+            tilt_angle,
+            tilt_release: tilt_angle - self.tilt.hysteresis.unwrap_or(5.0),
+            tilts: [self.tilt.left, self.tilt.right, self.tilt.forward, self.tilt.back].map(code),
+            shake: self.shake.as_ref().map(|shake| shake.key.code()),
+            shake_threshold: self
+                .shake
+                .as_ref()
+                .and_then(|shake| shake.threshold)
+                .unwrap_or(1.5),
+            shake_cooldown: Duration::from_millis(
+                self.shake
+                    .as_ref()
+                    .and_then(|shake| shake.cooldown_ms)
+                    .unwrap_or(500),
+            ),
+            twist_speed: self.twist.speed.unwrap_or(360.0),
+            twist_cooldown: Duration::from_millis(self.twist.cooldown_ms.unwrap_or(300)),
+            twists: [self.twist.left, self.twist.right, self.twist.up, self.twist.down].map(code),
+            accel_resolution: self.accel_resolution.unwrap_or(8192.0),
+            gyro_resolution: self.gyro_resolution.unwrap_or(1024.0),
+        }
+    }
+}
+
+/// 8-way options shared by joysticks and the D-Pad. With `eight_way = true`
+/// diagonals hold two keys, or the dedicated diagonal key when one is bound.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EightWayConfig {
+    #[serde(default)]
+    pub eight_way: bool,
+    /// Width of each diagonal sector in degrees (joysticks only, default 45).
+    pub diagonal_angle: Option<f32>,
+    /// Degrees a held direction extends past its sector edge (joysticks only)
+    pub sector_hysteresis: Option<f32>,
+    pub up_left: Option<KeyCode>,
+    pub up_right: Option<KeyCode>,
+    pub down_left: Option<KeyCode>,
+    pub down_right: Option<KeyCode>,
+}
+
+impl EightWayConfig {
+    pub fn codes(&self) -> Option<EightWayCodeConfig> {
+        self.eight_way.then(|| EightWayCodeConfig {
+            diagonal_angle: self.diagonal_angle.unwrap_or(45.0),
+            sector_hysteresis: self.sector_hysteresis.unwrap_or(0.0),
+            up_left: self.up_left.map(|key| key.code()),
+            up_right: self.up_right.map(|key| key.code()),
+            down_left: self.down_left.map(|key| key.code()),
+            down_right: self.down_right.map(|key| key.code()),
+        })
+    }
+}
+
+/// Pulse-width modulation of analog keys.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PwmConfig {
+    /// Length of one on/off cycle (default 100)
+    pub period_ms: Option<u64>,
+    /// Shortest pulse or gap sent (default 20)
+    pub min_pulse_ms: Option<u64>,
+}
+
+impl PwmConfig {
+    pub fn pwm(&self) -> Pwm {
+        Pwm {
+            period: Duration::from_millis(self.period_ms.unwrap_or(100)),
+            min_pulse: Duration::from_millis(self.min_pulse_ms.unwrap_or(20)),
+        }
+    }
+}
+
+/// Every pulse would latch or unlatch a toggle binding, so the two can't be
+/// combined.
+fn pwm_codes(pwm: Option<&PwmConfig>, toggle: bool) -> Result<Option<Pwm>, String> {
+    match pwm {
+        Some(_) if toggle => Err("pwm doesn't work with toggle".to_string()),
+        pwm => Ok(pwm.map(PwmConfig::pwm)),
+    }
+}
+
+/// A fighting-game motion like a quarter circle, written in numpad notation
+/// and watched for on a stick or the D-Pad.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MotionInputConfig {
+    /// "LeftStick", "RightStick" or "DPad"
+    pub source: String,
+    /// Directions in numpad notation ("236", "623", "[4]6" to charge back,
+    /// or "360" for a full rotation)
+    pub pattern: String,
+    pub key: Option<KeyCode>,
+    /// Keys tapped one after another, as a macro
+    #[serde(default)]
+    pub keys: Vec<KeyCode>,
+    /// How long each key is held down, so games that poll once a frame see
+    /// it (default 50)
+    pub hold_ms: Option<u64>,
+    /// Longest time the whole motion may take (default 500)
+    pub window_ms: Option<u64>,
+    /// How long a `[charge]` direction has to be held (default 500)
+    pub charge_ms: Option<u64>,
+    /// Raw units from the centre a stick has to move to leave neutral
+    /// (default 64)
+    pub deadzone: Option<u8>,
+}
+
+impl MotionInputConfig {
+    pub fn codes(&self) -> Result<MotionInputCodeConfig, String> {
+        let source = match self.source.as_str() {
+            "LeftStick" => GamepadControl::LeftStickX,
+            "RightStick" => GamepadControl::RightStickX,
+            "DPad" => GamepadControl::DPadX,
+            other => return Err(format!("unknown source {}", other)),
+        };
+        let pattern = MotionPattern::parse(&self.pattern)?;
+        let keys: Vec<u32> = self
+            .key
+            .iter()
+            .chain(&self.keys)
+            .map(|key| key.code())
+            .collect();
+        if keys.is_empty() {
+            return Err("no key or keys".to_string());
+        }
+
+        let deadzone = self.deadzone.unwrap_or(64);
+        Ok(MotionInputCodeConfig {
+            source,
+            pattern,
+            keys,
+            hold: Duration::from_millis(self.hold_ms.unwrap_or(50)),
+            window: Duration::from_millis(self.window_ms.unwrap_or(500)),
+            charge: Duration::from_millis(self.charge_ms.unwrap_or(500)),
+            pipeline: StickAnalogConfig::default().pipeline(
+                deadzone,
+                deadzone,
+                DeadzoneShape::Radial,
+            ),
+        })
+    }
+}
+
+/// Timing filters for buttons, applied before any mapping: a table per
+/// button, plus `all` for the buttons without one. Sticks, triggers and the
+/// D-Pad arrive as axes and are not filtered.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FiltersConfig {
+    pub all: Option<FilterConfig>,
+    #[serde(flatten)]
+    pub controls: HashMap<GamepadControl, FilterConfig>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FilterConfig {
+    /// A press has to last this long to count
+    pub slow_ms: Option<u64>,
+    /// Presses this soon after a release are ignored
+    pub bounce_ms: Option<u64>,
+    /// Shortest time a press is kept down
+    pub min_hold_ms: Option<u64>,
+}
+
+impl FilterConfig {
+    pub fn filter(&self) -> InputFilter {
+        InputFilter {
+            slow: millis(self.slow_ms),
+            bounce: millis(self.bounce_ms),
+            min_hold: millis(self.min_hold_ms),
+        }
+    }
+}
+
+impl FiltersConfig {
+    pub fn codes(&self) -> Result<FiltersCodeConfig, String> {
+        if let Some(&control) = self.controls.keys().find(|&&control| is_axis(control)) {
+            return Err(format!("{} is not a button", control));
+        }
+        Ok(FiltersCodeConfig {
+            all: self.all.as_ref().map(FilterConfig::filter),
+            controls: self
+                .controls
+                .iter()
+                .map(|(&control, filter)| (control, filter.filter()))
+                .collect(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmoothingFilter {
+    /// Exponential moving average
+    #[default]
+    Ema,
+    /// Adapts to speed: smooth when still, responsive when moving
+    OneEuro,
+    /// Median of the last few values, removing spikes
+    Median,
+}
+
+/// Smoothing for the raw values of a stick or trigger, against tremor and
+/// sensor noise.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SmoothingConfig {
+    #[serde(default)]
+    pub filter: SmoothingFilter,
+    /// EMA: time to close 63% of the gap to a new value
+    pub time_constant_ms: Option<u64>,
+    /// One Euro: cutoff in Hz while the axis is still
+    pub min_cutoff: Option<f32>,
+    /// One Euro: how much the cutoff rises per raw unit per second of speed
+    pub beta: Option<f32>,
+    /// One Euro: cutoff in Hz for the speed estimate
+    pub derivative_cutoff: Option<f32>,
+    /// Median: number of values the median is taken over
+    pub samples: Option<usize>,
+}
+
+impl SmoothingConfig {
+    pub fn smoothing(&self) -> Smoothing {
+        match self.filter {
+            SmoothingFilter::Ema => Smoothing::Ema {
+                time_constant: Duration::from_millis(self.time_constant_ms.unwrap_or(50)),
+            },
+            SmoothingFilter::OneEuro => Smoothing::OneEuro {
+                min_cutoff: self.min_cutoff.unwrap_or(1.0),
+                beta: self.beta.unwrap_or(0.01),
+                derivative_cutoff: self.derivative_cutoff.unwrap_or(1.0),
+            },
+            SmoothingFilter::Median => Smoothing::Median {
+                samples: self.samples.unwrap_or(5),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScanMode {
+    /// The highlight moves on its own and one switch selects
+    #[default]
+    Auto,
+    /// One switch moves the highlight and another selects
+    Step,
+}
+
+/// Switch-access scanning: a highlight cycles through `items` and the
+/// select button fires the highlighted one. With `columns` the items form
+/// rows, scanned row by row first and then item by item within the chosen
+/// row.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanningConfig {
+    #[serde(default)]
+    pub mode: ScanMode,
+    pub select: GamepadControl,
+    /// Button that moves the highlight in step mode
+    pub step: Option<GamepadControl>,
+    /// How long the highlight stays on each row or item (default 1000)
+    pub dwell_ms: Option<u64>,
+    /// Dwell on the first row or item of a pass (default `dwell_ms`)
+    pub first_dwell_ms: Option<u64>,
+    /// How long select has to be held to count (default 0)
+    pub accept_ms: Option<u64>,
+    /// Full passes without a selection before auto scanning stops
+    /// (default 3)
+    pub loops: Option<usize>,
+    pub columns: Option<usize>,
+    pub items: Vec<ScanItemConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanItemConfig {
+    /// Shown in the TUI instead of the key names
+    pub label: Option<String>,
+    pub key: Option<KeyCode>,
+    /// Keys tapped one after another, as a macro
+    #[serde(default)]
+    pub keys: Vec<KeyCode>,
+}
+
+impl ScanningConfig {
+    pub fn codes(&self) -> Result<ScanCodeConfig, String> {
+        if self.mode == ScanMode::Step && self.step.is_none() {
+            return Err("step mode needs a step button".to_string());
+        }
+        if self.columns == Some(0) {
+            return Err("columns has to be at least 1".to_string());
+        }
+        let items = self
+            .items
+            .iter()
+            .map(|item| {
+                let keys: Vec<u32> = item
+                    .key
+                    .iter()
+                    .chain(&item.keys)
+                    .map(|key| key.code())
+                    .collect();
+                if keys.is_empty() {
+                    return Err("an item has no key or keys".to_string());
+                }
+                let label = item.label.clone().unwrap_or_else(|| {
+                    keys.iter()
+                        .map(|&key| code_to_name(key))
+                        .collect::<Vec<String>>()
+                        .join(" ")
+                });
+                Ok(ScanItem { label, keys })
+            })
+            .collect::<Result<Vec<ScanItem>, String>>()?;
+        if items.is_empty() {
+            return Err("no items".to_string());
+        }
+
+        let dwell = Duration::from_millis(self.dwell_ms.unwrap_or(1000));
+        Ok(ScanCodeConfig {
+            mode: self.mode,
+            select: self.select,
+            step: self.step,
+            dwell,
+            first_dwell: self.first_dwell_ms.map_or(dwell, Duration::from_millis),
+            accept: millis(self.accept_ms),
+            loops: self.loops.unwrap_or(3),
+            columns: self.columns,
+            items,
+        })
+    }
+}
+
+/// On-screen keyboard typed on with a stick or the D-Pad and the face
+/// buttons.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyboardConfig {
+    /// Button that opens and closes the keyboard
+    pub toggle: GamepadControl,
+    /// "LeftStick", "RightStick" or "DPad" (default)
+    pub navigate: Option<String>,
+    /// Raw units from the centre a stick has to move (default 64)
+    pub deadzone: Option<u8>,
+    pub select: Option<GamepadControl>,
+    pub backspace: Option<GamepadControl>,
+    pub shift: Option<GamepadControl>,
+    pub space: Option<GamepadControl>,
+    /// Characters row by row
+    pub rows: Option<Vec<String>>,
+    /// Rows while shift is on (default the rows in upper case)
+    pub shift_rows: Option<Vec<String>>,
+}
+
+const KEYBOARD_ROWS: [&str; 4] = ["1234567890", "qwertyuiop", "asdfghjkl@", "zxcvbnm,.-"];
+const KEYBOARD_SHIFT_ROWS: [&str; 4] = ["!?#$%&*()_", "QWERTYUIOP", "ASDFGHJKL:", "ZXCVBNM;/+"];
+
+impl KeyboardConfig {
+    pub fn codes(&self) -> Result<KeyboardCodeConfig, String> {
+        let source = match self.navigate.as_deref() {
+            Some("LeftStick") => GamepadControl::LeftStickX,
+            Some("RightStick") => GamepadControl::RightStickX,
+            Some("DPad") | None => GamepadControl::DPadX,
+            Some(other) => return Err(format!("unknown navigate {}", other)),
+        };
+        let grid = |rows: &[&str]| -> Vec<Vec<char>> {
+            rows.iter().map(|row| row.chars().collect()).collect()
+        };
+        let (rows, shift_rows) = match (&self.rows, &self.shift_rows) {
+            (Some(rows), shift_rows) => {
+                let upper: Vec<String> = rows.iter().map(|row| row.to_uppercase()).collect();
+                let shift_rows = shift_rows.as_ref().unwrap_or(&upper);
+                (
+                    grid(&rows.iter().map(String::as_str).collect::<Vec<&str>>()),
+                    grid(&shift_rows.iter().map(String::as_str).collect::<Vec<&str>>()),
+                )
+            }
+            (None, _) => (grid(&KEYBOARD_ROWS), grid(&KEYBOARD_SHIFT_ROWS)),
+        };
+
+        let deadzone = self.deadzone.unwrap_or(64);
+        Ok(KeyboardCodeConfig {
+            toggle: self.toggle,
+            source,
+            select: self.select.unwrap_or(GamepadControl::Cross),
+            backspace: self.backspace.unwrap_or(GamepadControl::Square),
+            shift: self.shift.unwrap_or(GamepadControl::Triangle),
+            space: self.space.unwrap_or(GamepadControl::Circle),
+            backspace_key: KeyCode::Backspace.code(),
+            rows,
+            shift_rows,
+            pipeline: StickAnalogConfig::default().pipeline(
+                deadzone,
+                deadzone,
+                DeadzoneShape::Radial,
+            ),
+        })
+    }
+}
+
+/// Chorded text entry: buttons pressed together and let go type the text
+/// bound to that combination.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ChordConfig {
+    /// Button that switches chord entry on and off; without one it is
+    /// always on
+    pub toggle: Option<GamepadControl>,
+    /// Up to 8 buttons chords are made from (default the face buttons, L1
+    /// and R1)
+    pub buttons: Option<Vec<GamepadControl>>,
+    /// Start from the bundled table (default true)
+    pub bundled_table: Option<bool>,
+    /// Chords like "Cross+Circle" and the text they type, added to or
+    /// replacing bundled entries
+    #[serde(default)]
+    pub table: HashMap<String, String>,
+}
+
+const CHORD_BUTTONS: [GamepadControl; 6] = [
+    GamepadControl::Square,
+    GamepadControl::Cross,
+    GamepadControl::Circle,
+    GamepadControl::Triangle,
+    GamepadControl::L1,
+    GamepadControl::R1,
+];
+
+/// The bundled chord table: the most common letters on single buttons and
+/// pairs of face buttons, digits on L1+R1 with face buttons.
+const BUNDLED_CHORDS: [(&str, &str); 52] = [
+    ("Cross", "e"),
+    ("Circle", "t"),
+    ("Square", "a"),
+    ("Triangle", "o"),
+    ("L1", "i"),
+    ("R1", " "),
+    ("Cross+Circle", "n"),
+    ("Cross+Square", "s"),
+    ("Cross+Triangle", "h"),
+    ("Circle+Square", "r"),
+    ("Circle+Triangle", "d"),
+    ("Square+Triangle", "l"),
+    ("L1+Cross", "c"),
+    ("L1+Circle", "u"),
+    ("L1+Square", "m"),
+    ("L1+Triangle", "w"),
+    ("R1+Cross", "f"),
+    ("R1+Circle", "g"),
+    ("R1+Square", "y"),
+    ("R1+Triangle", "p"),
+    ("Cross+Circle+Square", "b"),
+    ("Cross+Circle+Triangle", "v"),
+    ("Cross+Square+Triangle", "k"),
+    ("Circle+Square+Triangle", "j"),
+    ("Cross+Circle+Square+Triangle", "x"),
+    ("L1+Cross+Circle", "q"),
+    ("L1+Cross+Square", "z"),
+    ("R1+Cross+Circle", "."),
+    ("R1+Cross+Square", ","),
+    ("R1+Circle+Square", "?"),
+    ("R1+Cross+Triangle", "!"),
+    ("R1+Circle+Triangle", "'"),
+    ("R1+Square+Triangle", "-"),
+    ("L1+Circle+Square", "@"),
+    ("L1+Circle+Triangle", ":"),
+    ("L1+Square+Triangle", "/"),
+    ("L1+R1", "\u{8}"),
+    ("L1+R1+Cross", "\n"),
+    ("L1+R1+Circle", "1"),
+    ("L1+R1+Square", "2"),
+    ("L1+R1+Triangle", "3"),
+    ("L1+R1+Cross+Circle", "4"),
+    ("L1+R1+Cross+Square", "5"),
+    ("L1+R1+Cross+Triangle", "6"),
+    ("L1+R1+Circle+Square", "7"),
+    ("L1+R1+Circle+Triangle", "8"),
+    ("L1+R1+Square+Triangle", "9"),
+    ("L1+R1+Cross+Circle+Square", "0"),
+    ("L1+Cross+Triangle", ";"),
+    ("R1+Cross+Circle+Square", "("),
+    ("R1+Cross+Circle+Triangle", ")"),
+    ("L1+R1+Cross+Circle+Square+Triangle", "\t"),
+];
+
+impl ChordConfig {
+    pub fn codes(&self) -> Result<ChordCodeConfig, String> {
+        let buttons = self.buttons.clone().unwrap_or(CHORD_BUTTONS.to_vec());
+        if buttons.is_empty() || buttons.len() > 8 {
+            return Err("chords take 1 to 8 buttons".to_string());
+        }
+        if let Some(axis) = buttons.iter().find(|&&button| is_axis(button)) {
+            return Err(format!("{:?} is not a button", axis));
+        }
+
+        let chord = |name: &str| -> Result<u8, String> {
+            name.split('+').try_fold(0u8, |chord, button| {
+                let control = GamepadControl::deserialize(
+                    serde::de::IntoDeserializer::<serde::de::value::Error>::into_deserializer(
+                        button.trim(),
+                    ),
+                )
+                .map_err(|_| format!("unknown button {} in chord {}", button, name))?;
+                let index = buttons
+                    .iter()
+                    .position(|&candidate| candidate == control)
+                    .ok_or_else(|| format!("{} in chord {} is not a chord button", button, name))?;
+                Ok(chord | 1 << index)
+            })
+        };
+
+        let mut table = HashMap::new();
+        if self.bundled_table.unwrap_or(true) {
+            // Bundled chords using buttons left out of `buttons` are skipped
+            for (name, text) in BUNDLED_CHORDS {
+                if let Ok(chord) = chord(name) {
+                    table.insert(chord, text.to_string());
+                }
+            }
+        }
+        for (name, text) in &self.table {
+            table.insert(chord(name)?, text.clone());
+        }
+
+        Ok(ChordCodeConfig {
+            toggle: self.toggle,
+            buttons,
+            table,
+        })
+    }
+}
+
+/// Morse code on a single button: short presses are dots, long ones dashes,
+/// and pauses end the letter and the word.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MorseConfig {
+    pub button: GamepadControl,
+    /// Presses at least this long are dashes (default 250)
+    pub dash_ms: Option<u64>,
+    /// Rest after which the letter is typed (default 700)
+    pub letter_gap_ms: Option<u64>,
+    /// Rest after which a space is typed (default 1800)
+    pub word_gap_ms: Option<u64>,
+    /// Let the timing follow the user's speed
+    #[serde(default)]
+    pub adaptive: bool,
+}
+
+impl MorseConfig {
+    pub fn codes(&self) -> Result<MorseCodeConfig, String> {
+        if is_axis(self.button) {
+            return Err(format!("{:?} is not a button", self.button));
+        }
+        let letter_gap = Duration::from_millis(self.letter_gap_ms.unwrap_or(700));
+        let word_gap = Duration::from_millis(self.word_gap_ms.unwrap_or(1800));
+        if word_gap <= letter_gap {
+            return Err("word_gap_ms has to be longer than letter_gap_ms".to_string());
+        }
+        Ok(MorseCodeConfig {
+            button: self.button,
+            dash: Duration::from_millis(self.dash_ms.unwrap_or(250)),
+            letter_gap,
+            word_gap,
+            adaptive: self.adaptive,
+        })
+    }
+}
+
+/// Whether `control` is a stick, trigger or D-Pad axis rather than a button.
+fn is_axis(control: GamepadControl) -> bool {
+    matches!(
+        control,
+        GamepadControl::LeftStickX
+            | GamepadControl::LeftStickY
+            | GamepadControl::RightStickX
+            | GamepadControl::RightStickY
+            | GamepadControl::L2
+            | GamepadControl::R2
+            | GamepadControl::DPadX
+            | GamepadControl::DPadY
+    )
+}
+
+/// A pair of opposing keys that must never be sent down together.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SocdConfig {
+    pub keys: [KeyCode; 2],
+    #[serde(default)]
+    pub mode: SocdMode,
+}
+
+impl SocdConfig {
+    pub fn pair(&self) -> SocdPair {
+        SocdPair {
+            keys: [self.keys[0].code(), self.keys[1].code()],
+            mode: self.mode,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ButtonCodeConfig {
+    pub action: ButtonAction,
+    pub toggle: bool,
+    pub repeat: Option<Repeat>,
+}
+
+#[derive(Debug, Clone)]
 pub struct JoystickCodeConfig {
-    pub deadzone: u8,
+    pub pipeline: StickPipeline,
     pub up: u32,
     pub down: u32,
     pub left: u32,
     pub right: u32,
+    pub toggle: bool,
+    pub eight_way: Option<EightWayCodeConfig>,
+    pub run: Option<RunCodeConfig>,
+    pub pwm: Option<Pwm>,
+    pub repeat: Option<Repeat>,
+    pub min_hold: Duration,
 }
 
 #[derive(Debug, Clone)]
-pub struct TriggerCodeConfig {
-    pub key: u32,
-    pub deadzone: u8,
+pub struct RunCodeConfig {
+    pub radius: f32,
+    pub release_radius: f32,
+    pub key: Option<u32>,
+    /// Walk keys and the run keys that replace them
+    pub replacements: Vec<(u32, u32)>,
+}
+
+impl RunCodeConfig {
+    /// Direction keys for the run stage.
+    pub fn keys(&self, walk_keys: &[u32]) -> Vec<u32> {
+        let mut keys: Vec<u32> = walk_keys.iter().map(|&key| self.replace(key)).collect();
+        if let Some(key) = self.key
+            && !keys.contains(&key)
+        {
+            keys.push(key);
+        }
+        keys
+    }
+
+    /// True when `keys` holds `walk_key` or the run key replacing it.
+    pub fn holds(&self, keys: &[u32], walk_key: u32) -> bool {
+        keys.contains(&walk_key) || keys.contains(&self.replace(walk_key))
+    }
+
+    fn replace(&self, walk_key: u32) -> u32 {
+        self.replacements
+            .iter()
+            .find(|(walk, _)| *walk == walk_key)
+            .map_or(walk_key, |(_, run)| *run)
+    }
 }
 
 #[derive(Debug, Clone)]
+pub struct TriggerCodeConfig {
+    pub stages: Vec<TriggerStage>,
+    pub pipeline: TriggerPipeline,
+    pub toggle: bool,
+    pub pwm: Option<Pwm>,
+    pub repeat: Option<Repeat>,
+    pub min_hold: Duration,
+}
+
+#[derive(Debug, Clone, Copy)]
 pub struct DPadCodeConfig {
     pub up: u32,
     pub down: u32,
     pub left: u32,
     pub right: u32,
+    pub toggle: bool,
+    pub eight_way: Option<EightWayCodeConfig>,
+    pub repeat: Option<Repeat>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct EightWayCodeConfig {
+    pub diagonal_angle: f32,
+    pub sector_hysteresis: f32,
+    pub up_left: Option<u32>,
+    pub up_right: Option<u32>,
+    pub down_left: Option<u32>,
+    pub down_right: Option<u32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RadialCodeConfig {
+    pub pipeline: StickPipeline,
+    pub sectors: Vec<u32>,
+    pub fire: RadialFire,
+    pub confirm: Option<GamepadControl>,
+}
+
+#[derive(Debug, Clone)]
+pub struct FlickStickCodeConfig {
+    pub pipeline: StickPipeline,
+    pub counts_per_360: f32,
+    pub flick_time: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct TouchpadCodeConfig {
+    pub sensitivity: f32,
+    pub scroll_step: f32,
+    pub natural_scroll: bool,
+    pub tap_time: Duration,
+    pub tap_distance: f32,
+    pub range_x: [i32; 2],
+    pub range_y: [i32; 2],
+    pub edge_scroll_right: f32,
+    pub edge_scroll_bottom: f32,
+    pub mode: TouchpadMode,
+    pub gestures: TouchGesturesCodeConfig,
+}
+
+#[derive(Debug, Clone)]
+pub struct TouchGesturesCodeConfig {
+    pub grid: [usize; 2],
+    pub regions: Vec<u32>,
+    pub swipe_distance: f32,
+    pub swipe_time: Duration,
+    /// Keys for swipes up, down, left and right
+    pub swipes: [Option<u32>; 4],
+    pub two_finger_swipes: [Option<u32>; 4],
+}
+
+#[derive(Debug, Clone)]
+pub struct GyroCodeConfig {
+    pub sensitivity: f32,
+    pub horizontal: GyroHorizontal,
+    pub invert_x: bool,
+    pub invert_y: bool,
+    pub tightening: f32,
+    pub smoothing: f32,
+    pub smoothing_samples: usize,
+    pub resolution: f32,
+    pub button: Option<GamepadControl>,
+    pub button_mode: GyroButtonMode,
+}
+
+#[derive(Debug, Clone)]
+pub struct MotionCodeConfig {
+    pub calibrate: Option<GamepadControl>,
+    pub tilt_angle: f32,
+    pub tilt_release: f32,
+    /// Keys for tilting left, right, forward and back
+    pub tilts: [Option<u32>; 4],
+    pub shake: Option<u32>,
+    pub shake_threshold: f32,
+    pub shake_cooldown: Duration,
+    pub twist_speed: f32,
+    pub twist_cooldown: Duration,
+    /// Keys for twisting left, right, up and down
+    pub twists: [Option<u32>; 4],
+    pub accel_resolution: f32,
+    pub gyro_resolution: f32,
+}
+
+#[derive(Debug, Clone)]
+pub struct MotionInputCodeConfig {
+    /// X axis of the stick, or `DPadX`
+    pub source: GamepadControl,
+    pub pattern: MotionPattern,
+    pub keys: Vec<u32>,
+    /// How long each key is held down
+    pub hold: Duration,
+    pub window: Duration,
+    pub charge: Duration,
+    /// Turns stick positions into directions; unused for the D-Pad
+    pub pipeline: StickPipeline,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FiltersCodeConfig {
+    /// Filter for buttons without their own
+    pub all: Option<InputFilter>,
+    pub controls: HashMap<GamepadControl, InputFilter>,
+}
+
+impl FiltersCodeConfig {
+    pub fn filter(&self, control: GamepadControl) -> Option<InputFilter> {
+        self.controls.get(&control).copied().or(self.all)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MorseCodeConfig {
+    pub button: GamepadControl,
+    pub dash: Duration,
+    pub letter_gap: Duration,
+    pub word_gap: Duration,
+    pub adaptive: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ChordCodeConfig {
+    pub toggle: Option<GamepadControl>,
+    pub buttons: Vec<GamepadControl>,
+    /// Text per chord, one bit per button in `buttons`
+    pub table: HashMap<u8, String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct KeyboardCodeConfig {
+    pub toggle: GamepadControl,
+    /// X axis of the navigating stick, or `DPadX`
+    pub source: GamepadControl,
+    pub select: GamepadControl,
+    pub backspace: GamepadControl,
+    pub shift: GamepadControl,
+    pub space: GamepadControl,
+    pub backspace_key: u32,
+    pub rows: Vec<Vec<char>>,
+    pub shift_rows: Vec<Vec<char>>,
+    /// Turns stick positions into directions; unused for the D-Pad
+    pub pipeline: StickPipeline,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanItem {
+    pub label: String,
+    pub keys: Vec<u32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ScanCodeConfig {
+    pub mode: ScanMode,
+    pub select: GamepadControl,
+    pub step: Option<GamepadControl>,
+    pub dwell: Duration,
+    pub first_dwell: Duration,
+    pub accept: Duration,
+    pub loops: usize,
+    pub columns: Option<usize>,
+    pub items: Vec<ScanItem>,
+}
+
+/// Resolved bindings handed to the server, keyed by gamepad control.
+#[derive(Debug, Clone, Default)]
+pub struct Bindings {
+    pub buttons: HashMap<GamepadControl, ButtonCodeConfig>,
+    pub joysticks: HashMap<GamepadControl, JoystickCodeConfig>,
+    pub triggers: HashMap<GamepadControl, TriggerCodeConfig>,
+    pub dpad: Option<DPadCodeConfig>,
+    pub radial: HashMap<GamepadControl, RadialCodeConfig>,
+    pub flick_sticks: HashMap<GamepadControl, FlickStickCodeConfig>,
+    pub touchpad: Option<TouchpadCodeConfig>,
+    pub gyro: Option<GyroCodeConfig>,
+    pub motion: Option<MotionCodeConfig>,
+    /// Opposing key pairs cleaned on the output side, whatever produced them
+    pub socd: Vec<SocdPair>,
+    pub motion_inputs: Vec<MotionInputCodeConfig>,
+    pub filters: FiltersCodeConfig,
+    /// Smoothing per axis; a stick's entry covers both of its axes
+    pub smoothing: HashMap<GamepadControl, Smoothing>,
+    pub sticky_modifiers: bool,
+    pub scanning: Option<ScanCodeConfig>,
+    pub keyboard: Option<KeyboardCodeConfig>,
+    pub chords: Option<ChordCodeConfig>,
+    pub morse: Option<MorseCodeConfig>,
 }
 
 impl Config {
@@ -84,3 +1584,238 @@ impl Config {
         include_str!("../default.toml")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_config_parses() {
+        let config: Config = toml::from_str(Config::default_toml()).unwrap();
+        assert!(!config.keys.buttons.is_empty());
+    }
+
+    #[test]
+    fn filters_per_button_or_all() {
+        let filters: FiltersConfig =
+            toml::from_str("all = { bounce_ms = 100 }\nCross = { slow_ms = 200 }").unwrap();
+        let codes = filters.codes().unwrap();
+        assert_eq!(
+            codes.filter(GamepadControl::Cross).unwrap().slow,
+            Duration::from_millis(200)
+        );
+        assert_eq!(
+            codes.filter(GamepadControl::Square).unwrap().bounce,
+            Duration::from_millis(100)
+        );
+
+        // The D-Pad and triggers are axes, which the filters never see
+        let filters: FiltersConfig = toml::from_str("DPadX = { slow_ms = 200 }").unwrap();
+        assert!(filters.codes().is_err());
+        let filters: FiltersConfig = toml::from_str("R2 = { bounce_ms = 100 }").unwrap();
+        assert!(filters.codes().is_err());
+    }
+
+    #[test]
+    fn smoothing_defaults() {
+        let smoothing: SmoothingConfig = toml::from_str("filter = \"one_euro\"").unwrap();
+        assert_eq!(
+            smoothing.smoothing(),
+            Smoothing::OneEuro {
+                min_cutoff: 1.0,
+                beta: 0.01,
+                derivative_cutoff: 1.0,
+            }
+        );
+        let smoothing: SmoothingConfig = toml::from_str("samples = 7\nfilter = \"median\"").unwrap();
+        assert_eq!(smoothing.smoothing(), Smoothing::Median { samples: 7 });
+    }
+
+    #[test]
+    fn repeat_defaults_and_rate() {
+        let buttons: HashMap<String, ButtonConfig> = toml::from_str(
+            "Square = \"A\"\nCross = { key = \"DOWN\", repeat = { rate = 20 } }",
+        )
+        .unwrap();
+        assert_eq!(buttons["Square"].repeat(), None);
+        assert_eq!(
+            buttons["Cross"].repeat(),
+            Some(Repeat {
+                delay: Duration::from_millis(500),
+                interval: Duration::from_millis(50),
+            })
+        );
+    }
+
+    #[test]
+    fn button_accepts_key_or_table() {
+        let buttons: HashMap<String, ButtonConfig> =
+            toml::from_str("Square = \"A\"\nCross = { key = \"SPACE\", toggle = true }").unwrap();
+        assert!(!buttons["Square"].toggle());
+        assert_eq!(
+            buttons["Cross"].action(),
+            ButtonAction::Key(KeyCode::Space.code())
+        );
+        assert!(buttons["Cross"].toggle());
+    }
+
+    #[test]
+    fn button_types_text() {
+        let buttons: HashMap<String, ButtonConfig> =
+            toml::from_str("Aux1 = { text = \"me@example.com\" }\nAux2 = { text = \"👍\" }")
+                .unwrap();
+        assert_eq!(
+            buttons["Aux1"].action(),
+            ButtonAction::Text("me@example.com".to_string())
+        );
+        assert_eq!(
+            buttons["Aux2"].action(),
+            ButtonAction::Text("👍".to_string())
+        );
+        assert_eq!(buttons["Aux2"].repeat(), None);
+
+        // Options that only make sense for keys, or a key as well as text
+        for mixed in [
+            "{ text = \"x\", repeat = { rate = 20 } }",
+            "{ text = \"x\", toggle = true }",
+            "{ key = \"A\", text = \"x\" }",
+        ] {
+            let button: Result<HashMap<String, ButtonConfig>, _> =
+                toml::from_str(&format!("Aux1 = {}", mixed));
+            assert!(button.is_err(), "{}", mixed);
+        }
+    }
+
+    #[test]
+    fn joystick_reads_flattened_eight_way_options() {
+        let stick: JoystickConfig = toml::from_str(
+            "up = \"W\"\ndown = \"S\"\nleft = \"A\"\nright = \"D\"\neight_way = true\ndiagonal_angle = 30\nup_left = \"Q\"",
+        )
+        .unwrap();
+        let codes = stick.eight_way.codes().unwrap();
+        assert_eq!(codes.diagonal_angle, 30.0);
+        assert_eq!(codes.up_left, Some(KeyCode::Q.code()));
+        assert_eq!(codes.up_right, None);
+    }
+
+    #[test]
+    fn run_stage_adds_modifier_or_replaces_keys() {
+        let stick: JoystickConfig = toml::from_str(
+            "up = \"W\"\ndown = \"S\"\nleft = \"A\"\nright = \"D\"\n\n[run]\nradius = 96\nhysteresis = 8\nkey = \"LSHIFT\"\nup = \"R\"",
+        )
+        .unwrap();
+        let run = stick.run_codes().unwrap();
+        assert_eq!(run.radius, 96.0 / 127.5);
+        assert_eq!(run.release_radius, 88.0 / 127.5);
+
+        let (w, a, r, shift) = (
+            KeyCode::W.code(),
+            KeyCode::A.code(),
+            KeyCode::R.code(),
+            KeyCode::LShift.code(),
+        );
+        assert_eq!(run.keys(&[w, a]), vec![r, a, shift]);
+        assert!(run.holds(&[r, shift], w));
+    }
+
+    #[test]
+    fn radial_menu_parses() {
+        let radial: RadialConfig = toml::from_str(
+            "sectors = [\"Q\", \"E\", \"R\", \"F\"]\nfire = \"confirm\"\nconfirm = \"R3\"",
+        )
+        .unwrap();
+        assert_eq!(radial.fire, RadialFire::Confirm);
+        assert_eq!(radial.confirm, Some(GamepadControl::R3));
+        assert_eq!(radial.pipeline().shape, DeadzoneShape::Radial);
+        assert!(RADIAL_SECTORS.contains(&radial.sectors.len()));
+    }
+
+    #[test]
+    fn analog_tables_parse() {
+        let stick: JoystickConfig = toml::from_str(
+            "deadzone = 20\nup = \"W\"\ndown = \"S\"\nleft = \"A\"\nright = \"D\"\n\n[analog]\ndeadzone_shape = \"scaled_radial\"\nouter_deadzone = 0.9\ninvert_y = true\ncurve = { exponential = 2.0 }",
+        )
+        .unwrap();
+        let pipeline = stick.pipeline();
+        assert_eq!(pipeline.shape, DeadzoneShape::ScaledRadial);
+        assert_eq!(pipeline.response.outer_deadzone, 0.9);
+        assert_eq!(pipeline.response.curve, ResponseCurve::Exponential(2.0));
+        assert!(pipeline.invert_y);
+
+        let trigger: TriggerConfig = toml::from_str(
+            "key = \"C\"\ndeadzone = 30\n\n[analog]\nrange = [0, 1023]\ncurve = { points = [[0.0, 0.0], [1.0, 1.0]] }",
+        )
+        .unwrap();
+        assert_eq!(trigger.pipeline().range, [0, 1023]);
+    }
+
+    #[test]
+    fn trigger_stages_from_key_or_list() {
+        let single: TriggerConfig =
+            toml::from_str("key = \"C\"\ndeadzone = 51\nhysteresis = 10").unwrap();
+        let stages = single.stages();
+        assert_eq!(stages.len(), 1);
+        assert_eq!(stages[0].threshold, 0.2);
+
+        let staged: TriggerConfig = toml::from_str(
+            "stages = [{ key = \"F\", threshold = 0.95 }, { key = \"A\", threshold = 0.3, release_on_next = true }]",
+        )
+        .unwrap();
+        let stages = staged.stages();
+        assert_eq!(stages[0].key, KeyCode::A.code());
+        assert!(stages[0].release_on_next);
+        assert_eq!(stages[1].key, KeyCode::F.code());
+
+        let full: TriggerConfig = toml::from_str(
+            "full_pull_only = true\nstages = [{ key = \"A\", threshold = 0.3 }, { key = \"F\", threshold = 0.95 }]",
+        )
+        .unwrap();
+        let stages = full.stages();
+        assert_eq!(stages.len(), 1);
+        assert_eq!(stages[0].key, KeyCode::F.code());
+    }
+
+    #[test]
+    fn pwm_rejects_toggle() {
+        let stick: JoystickConfig = toml::from_str(
+            "up = \"W\"\ndown = \"S\"\nleft = \"A\"\nright = \"D\"\n\n[pwm]\nperiod_ms = 80",
+        )
+        .unwrap();
+        assert_eq!(
+            stick.pwm_codes().unwrap().unwrap().period,
+            Duration::from_millis(80)
+        );
+
+        let stick: JoystickConfig = toml::from_str(
+            "up = \"W\"\ndown = \"S\"\nleft = \"A\"\nright = \"D\"\ntoggle = true\n\n[pwm]",
+        )
+        .unwrap();
+        assert!(stick.pwm_codes().is_err());
+        let trigger: TriggerConfig =
+            toml::from_str("key = \"C\"\ntoggle = true\npwm = {}").unwrap();
+        assert!(trigger.pwm_codes().is_err());
+    }
+
+    #[test]
+    fn keyboard_rejects_unknown_navigate() {
+        let keyboard = |navigate: &str| {
+            let config: KeyboardConfig =
+                toml::from_str(&format!("toggle = \"Touch\"\nnavigate = \"{}\"", navigate))
+                    .unwrap();
+            config.codes().map(|codes| codes.source)
+        };
+        assert_eq!(keyboard("RightStick"), Ok(GamepadControl::RightStickX));
+        assert_eq!(keyboard("DPad"), Ok(GamepadControl::DPadX));
+        assert!(keyboard("Dpad").is_err());
+        assert!(keyboard("leftstick").is_err());
+    }
+
+    #[test]
+    fn release_deadzone_from_band_or_explicit_value() {
+        assert_eq!(release_deadzone(30, None, None), 30);
+        assert_eq!(release_deadzone(30, None, Some(10)), 20);
+        assert_eq!(release_deadzone(30, None, Some(50)), 0);
+        assert_eq!(release_deadzone(30, Some(25), Some(10)), 25);
+        assert_eq!(release_deadzone(30, Some(40), None), 30);
+    }
+}
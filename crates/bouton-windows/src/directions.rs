@@ -0,0 +1,267 @@
+use crate::config::EightWayCodeConfig;
+
+/// One of the eight compass directions a stick or D-Pad can point in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction8 {
+    Up,
+    UpRight,
+    Right,
+    DownRight,
+    Down,
+    DownLeft,
+    Left,
+    UpLeft,
+}
+
+impl Direction8 {
+    /// Sector for a stick deflection, with `dy` positive pointing down.
+    ///
+    /// Diagonal sectors are `diagonal_angle` degrees wide and cardinal sectors
+    /// take the rest, so 45 splits the circle evenly and smaller values make
+    /// diagonals harder to hit.
+    pub fn from_vector(dx: f32, dy: f32, diagonal_angle: f32) -> Self {
+        let diagonal_angle = diagonal_angle.clamp(0.0, 90.0);
+        let cardinal_half_width = (90.0 - diagonal_angle) / 2.0;
+
+        // 0 degrees points right, 90 points up
+        let angle = (-dy).atan2(dx).to_degrees().rem_euclid(360.0);
+
+        let nearest_cardinal = (angle / 90.0).round();
+        if (angle - nearest_cardinal * 90.0).abs() <= cardinal_half_width {
+            return match nearest_cardinal as i32 % 4 {
+                0 => Direction8::Right,
+                1 => Direction8::Up,
+                2 => Direction8::Left,
+                _ => Direction8::Down,
+            };
+        }
+
+        match (angle / 90.0).floor() as i32 {
+            0 => Direction8::UpRight,
+            1 => Direction8::UpLeft,
+            2 => Direction8::DownLeft,
+            _ => Direction8::DownRight,
+        }
+    }
+
+    /// Like `from_vector`, but keeps `previous` until the stick has moved more
+    /// than `margin` degrees past the edge of its sector, so resting on a
+    /// boundary doesn't flip between two directions.
+    pub fn from_vector_held(
+        dx: f32,
+        dy: f32,
+        diagonal_angle: f32,
+        previous: Option<Self>,
+        margin: f32,
+    ) -> Self {
+        if let Some(previous) = previous
+            && margin > 0.0
+        {
+            let diagonal_angle = diagonal_angle.clamp(0.0, 90.0);
+            let half_width = if previous.is_diagonal() {
+                diagonal_angle / 2.0
+            } else {
+                (90.0 - diagonal_angle) / 2.0
+            };
+
+            let angle = (-dy).atan2(dx).to_degrees();
+            let offset = (angle - previous.angle() + 180.0).rem_euclid(360.0) - 180.0;
+            if offset.abs() <= half_width + margin {
+                return previous;
+            }
+        }
+
+        Self::from_vector(dx, dy, diagonal_angle)
+    }
+
+    /// Centre of this direction's sector in degrees, 0 pointing right and
+    /// 90 pointing up.
+    fn angle(self) -> f32 {
+        match self {
+            Direction8::Right => 0.0,
+            Direction8::UpRight => 45.0,
+            Direction8::Up => 90.0,
+            Direction8::UpLeft => 135.0,
+            Direction8::Left => 180.0,
+            Direction8::DownLeft => 225.0,
+            Direction8::Down => 270.0,
+            Direction8::DownRight => 315.0,
+        }
+    }
+
+    fn is_diagonal(self) -> bool {
+        matches!(
+            self,
+            Direction8::UpRight | Direction8::DownRight | Direction8::DownLeft | Direction8::UpLeft
+        )
+    }
+
+    /// Number of this direction in fighting-game numpad notation, where 8
+    /// is up, 6 is right and 5 (never returned) is neutral.
+    pub fn numpad(self) -> u8 {
+        match self {
+            Direction8::Up => 8,
+            Direction8::UpRight => 9,
+            Direction8::Right => 6,
+            Direction8::DownRight => 3,
+            Direction8::Down => 2,
+            Direction8::DownLeft => 1,
+            Direction8::Left => 4,
+            Direction8::UpLeft => 7,
+        }
+    }
+
+    /// Direction for a D-Pad hat, where each axis is -1, 0 or 1.
+    pub fn from_dpad(x: i8, y: i8) -> Option<Self> {
+        match (x.signum(), y.signum()) {
+            (0, -1) => Some(Direction8::Up),
+            (1, -1) => Some(Direction8::UpRight),
+            (1, 0) => Some(Direction8::Right),
+            (1, 1) => Some(Direction8::DownRight),
+            (0, 1) => Some(Direction8::Down),
+            (-1, 1) => Some(Direction8::DownLeft),
+            (-1, 0) => Some(Direction8::Left),
+            (-1, -1) => Some(Direction8::UpLeft),
+            _ => None,
+        }
+    }
+
+    /// Keys to hold for this direction. Diagonals use their dedicated key if
+    /// one is bound and otherwise hold both neighbouring cardinal keys.
+    pub fn keys(
+        self,
+        up: u32,
+        down: u32,
+        left: u32,
+        right: u32,
+        diagonals: &EightWayCodeConfig,
+    ) -> Vec<u32> {
+        let (dedicated, vertical, horizontal) = match self {
+            Direction8::Up => return vec![up],
+            Direction8::Down => return vec![down],
+            Direction8::Left => return vec![left],
+            Direction8::Right => return vec![right],
+            Direction8::UpRight => (diagonals.up_right, up, right),
+            Direction8::DownRight => (diagonals.down_right, down, right),
+            Direction8::DownLeft => (diagonals.down_left, down, left),
+            Direction8::UpLeft => (diagonals.up_left, up, left),
+        };
+
+        match dedicated {
+            Some(key) => vec![key],
+            None => vec![vertical, horizontal],
+        }
+    }
+}
+
+/// Index of the radial-menu sector a stick deflection points into, with
+/// `dy` positive pointing down. Sector 0 is centred on straight up and the
+/// rest follow clockwise, each `360 / sectors` degrees wide.
+pub fn radial_sector(dx: f32, dy: f32, sectors: usize) -> usize {
+    let sectors = sectors.max(1);
+    let width = 360.0 / sectors as f32;
+
+    // 0 degrees points up, 90 points right
+    let angle = dx.atan2(-dy).to_degrees().rem_euclid(360.0);
+    ((angle + width / 2.0) / width).floor() as usize % sectors
+}
+
+/// Wraps an angle difference into -180 to 180 degrees.
+pub fn wrap_degrees(angle: f32) -> f32 {
+    (angle + 180.0).rem_euclid(360.0) - 180.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn even_split_puts_boundaries_at_22_5_degrees() {
+        let at = |degrees: f32| {
+            let radians = degrees.to_radians();
+            Direction8::from_vector(radians.cos(), -radians.sin(), 45.0)
+        };
+
+        assert_eq!(at(0.0), Direction8::Right);
+        assert_eq!(at(22.0), Direction8::Right);
+        assert_eq!(at(23.0), Direction8::UpRight);
+        assert_eq!(at(90.0), Direction8::Up);
+        assert_eq!(at(135.0), Direction8::UpLeft);
+        assert_eq!(at(180.0), Direction8::Left);
+        assert_eq!(at(225.0), Direction8::DownLeft);
+        assert_eq!(at(270.0), Direction8::Down);
+        assert_eq!(at(315.0), Direction8::DownRight);
+        assert_eq!(at(350.0), Direction8::Right);
+    }
+
+    #[test]
+    fn narrow_diagonals_favour_cardinals() {
+        // 20 degree diagonals leave 70 degree cardinals: 35 either side
+        assert_eq!(Direction8::from_vector(1.0, -0.6, 20.0), Direction8::Right);
+        assert_eq!(Direction8::from_vector(1.0, -1.0, 20.0), Direction8::UpRight);
+    }
+
+    #[test]
+    fn diagonal_presses_both_keys_unless_bound() {
+        let mut diagonals = EightWayCodeConfig {
+            diagonal_angle: 45.0,
+            sector_hysteresis: 0.0,
+            up_left: None,
+            up_right: None,
+            down_left: None,
+            down_right: None,
+        };
+        assert_eq!(Direction8::UpLeft.keys(1, 2, 3, 4, &diagonals), vec![1, 3]);
+
+        diagonals.up_left = Some(9);
+        assert_eq!(Direction8::UpLeft.keys(1, 2, 3, 4, &diagonals), vec![9]);
+    }
+
+    #[test]
+    fn held_direction_sticks_past_the_boundary() {
+        let at = |degrees: f32| {
+            let radians = degrees.to_radians();
+            (radians.cos(), -radians.sin())
+        };
+
+        let (dx, dy) = at(25.0);
+        assert_eq!(Direction8::from_vector(dx, dy, 45.0), Direction8::UpRight);
+        assert_eq!(
+            Direction8::from_vector_held(dx, dy, 45.0, Some(Direction8::Right), 5.0),
+            Direction8::Right
+        );
+
+        let (dx, dy) = at(30.0);
+        assert_eq!(
+            Direction8::from_vector_held(dx, dy, 45.0, Some(Direction8::Right), 5.0),
+            Direction8::UpRight
+        );
+
+        // Wraps around 0 degrees
+        let (dx, dy) = at(-25.0);
+        assert_eq!(
+            Direction8::from_vector_held(dx, dy, 45.0, Some(Direction8::Right), 5.0),
+            Direction8::Right
+        );
+    }
+
+    #[test]
+    fn radial_sectors_run_clockwise_from_up() {
+        assert_eq!(radial_sector(0.0, -1.0, 8), 0);
+        assert_eq!(radial_sector(1.0, -1.0, 8), 1);
+        assert_eq!(radial_sector(1.0, 0.0, 8), 2);
+        assert_eq!(radial_sector(0.0, 1.0, 8), 4);
+        assert_eq!(radial_sector(-1.0, 0.0, 8), 6);
+
+        // Sector 0 straddles straight up
+        assert_eq!(radial_sector(-0.1, -1.0, 4), 0);
+        assert_eq!(radial_sector(0.1, 1.0, 12), 6);
+    }
+
+    #[test]
+    fn dpad_hat_maps_to_directions() {
+        assert_eq!(Direction8::from_dpad(0, 0), None);
+        assert_eq!(Direction8::from_dpad(-1, -1), Some(Direction8::UpLeft));
+        assert_eq!(Direction8::from_dpad(1, 0), Some(Direction8::Right));
+    }
+}
@@ -0,0 +1,215 @@
+use crate::config::FiltersCodeConfig;
+use bouton_core::KeyAction;
+use bouton_core::control::GamepadControl;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Timing filters for one button, each off when zero.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct InputFilter {
+    /// Slow keys: a press only counts once it has lasted this long
+    pub slow: Duration,
+    /// Bounce keys: presses this soon after a release are ignored
+    pub bounce: Duration,
+    /// The press is kept down for at least this long
+    pub min_hold: Duration,
+}
+
+#[derive(Debug, Default)]
+struct FilterState {
+    /// Physical press still waiting out the slow-keys time
+    pending_press: Option<Instant>,
+    /// When the press was passed on to the mapping
+    pressed_at: Option<Instant>,
+    /// Release held back until the minimum hold time is up
+    release_due: Option<Instant>,
+    last_release: Option<Instant>,
+    /// The current press bounced, so its release is dropped too
+    bounced: bool,
+}
+
+/// Sits between the button events from the client and the mapping, passing
+/// each press and release on, holding it back, or dropping it.
+pub struct InputFilters {
+    config: FiltersCodeConfig,
+    states: HashMap<GamepadControl, FilterState>,
+}
+
+impl InputFilters {
+    pub fn new(config: FiltersCodeConfig) -> Self {
+        Self {
+            config,
+            states: HashMap::new(),
+        }
+    }
+
+    /// The action to hand to the mapping now, if any. Held-back actions
+    /// come out of `tick` later.
+    pub fn button(
+        &mut self,
+        control: GamepadControl,
+        action: KeyAction,
+        now: Instant,
+    ) -> Option<KeyAction> {
+        let Some(filter) = self.config.filter(control) else {
+            return Some(action);
+        };
+        let state = self.states.entry(control).or_default();
+
+        match action {
+            KeyAction::Press => {
+                // Pressed again before a held-back release went out: the key
+                // simply stays down
+                if state.release_due.take().is_some() {
+                    return None;
+                }
+                if state
+                    .last_release
+                    .is_some_and(|released| now.duration_since(released) < filter.bounce)
+                {
+                    state.bounced = true;
+                    return None;
+                }
+                if !filter.slow.is_zero() {
+                    state.pending_press = Some(now);
+                    return None;
+                }
+                state.pressed_at = Some(now);
+                Some(KeyAction::Press)
+            }
+            KeyAction::Release => {
+                if std::mem::take(&mut state.bounced) {
+                    return None;
+                }
+                // Let go before the slow-keys time: the press never counted
+                if state.pending_press.take().is_some() {
+                    return None;
+                }
+                let pressed_at = state.pressed_at?;
+                if now.duration_since(pressed_at) < filter.min_hold {
+                    state.release_due = Some(pressed_at + filter.min_hold);
+                    return None;
+                }
+                state.pressed_at = None;
+                state.last_release = Some(now);
+                Some(KeyAction::Release)
+            }
+        }
+    }
+
+    /// Presses that have now lasted long enough and releases whose minimum
+    /// hold is up.
+    pub fn tick(&mut self, now: Instant) -> Vec<(GamepadControl, KeyAction)> {
+        let mut actions = Vec::new();
+        for (&control, state) in &mut self.states {
+            let Some(filter) = self.config.filter(control) else {
+                continue;
+            };
+
+            if let Some(since) = state.pending_press
+                && now.duration_since(since) >= filter.slow
+            {
+                state.pending_press = None;
+                state.pressed_at = Some(now);
+                actions.push((control, KeyAction::Press));
+            }
+
+            if let Some(due) = state.release_due
+                && now >= due
+            {
+                state.release_due = None;
+                state.pressed_at = None;
+                state.last_release = Some(now);
+                actions.push((control, KeyAction::Release));
+            }
+        }
+        actions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, Step};
+
+    /// `(milliseconds, action)` events for one button. Returns what reaches
+    /// the mapping and when.
+    fn play(filter: InputFilter, trace: &[(u64, KeyAction)]) -> Vec<(u64, KeyAction)> {
+        let mut filters = InputFilters::new(FiltersCodeConfig {
+            all: Some(filter),
+            controls: HashMap::new(),
+        });
+        let end = trace.last().map_or(0, |&(ms, _)| ms) + 200;
+        let mut out = Vec::new();
+        testing::play(trace, end, |ms, now, step| match step {
+            Step::Event(action) => {
+                if let Some(action) = filters.button(GamepadControl::Cross, action, now) {
+                    out.push((ms, action));
+                }
+            }
+            Step::Tick => {
+                for (_, action) in filters.tick(now) {
+                    out.push((ms, action));
+                }
+            }
+        });
+        out
+    }
+
+    use KeyAction::{Press, Release};
+
+    #[test]
+    fn slow_keys_ignore_short_presses() {
+        let slow = InputFilter {
+            slow: Duration::from_millis(100),
+            ..Default::default()
+        };
+        assert!(play(slow, &[(0, Press), (50, Release)]).is_empty());
+        assert_eq!(
+            play(slow, &[(0, Press), (300, Release)]),
+            vec![(100, Press), (300, Release)]
+        );
+    }
+
+    #[test]
+    fn bounce_keys_ignore_quick_re_presses() {
+        let bounce = InputFilter {
+            bounce: Duration::from_millis(150),
+            ..Default::default()
+        };
+        assert_eq!(
+            play(
+                bounce,
+                &[
+                    (0, Press),
+                    (50, Release),
+                    (100, Press),
+                    (120, Release),
+                    (400, Press),
+                    (450, Release),
+                ]
+            ),
+            vec![(0, Press), (50, Release), (400, Press), (450, Release)]
+        );
+    }
+
+    #[test]
+    fn min_hold_delays_short_releases() {
+        let hold = InputFilter {
+            min_hold: Duration::from_millis(80),
+            ..Default::default()
+        };
+        assert_eq!(
+            play(hold, &[(0, Press), (10, Release)]),
+            vec![(0, Press), (80, Release)]
+        );
+        // A press during the held-back release keeps the key down
+        assert_eq!(
+            play(
+                hold,
+                &[(0, Press), (10, Release), (40, Press), (300, Release)]
+            ),
+            vec![(0, Press), (300, Release)]
+        );
+    }
+}
@@ -0,0 +1,165 @@
+use crate::config::FlickStickCodeConfig;
+use crate::directions::wrap_degrees;
+use crate::key_injector::MouseEvent;
+use std::time::Instant;
+
+#[derive(Debug, Clone, Copy)]
+struct Flick {
+    started: Instant,
+    /// Degrees to turn, clockwise positive
+    angle: f32,
+    /// Degrees of `angle` already sent
+    turned: f32,
+}
+
+/// Flick stick camera control. Pushing the stick past its deadzone turns
+/// the camera to face the pushed direction (up is straight ahead), spread
+/// over `flick_time` so the game doesn't see a single jump. Rotating the
+/// stick while it stays pushed turns by the same angle it sweeps.
+pub struct FlickStick {
+    config: FlickStickCodeConfig,
+    /// Direction the stick points, in degrees clockwise from up, while it
+    /// is past the deadzone
+    angle: Option<f32>,
+    flick: Option<Flick>,
+    remainder: f32,
+}
+
+impl FlickStick {
+    pub fn new(config: FlickStickCodeConfig) -> Self {
+        Self {
+            config,
+            angle: None,
+            flick: None,
+            remainder: 0.0,
+        }
+    }
+
+    pub fn center(&self) -> i32 {
+        self.config.pipeline.center()
+    }
+
+    /// Takes the raw stick position, also called on every tick so a flick
+    /// in progress keeps going while the stick is still.
+    pub fn update(&mut self, [x, y]: [i32; 2], now: Instant) -> Option<MouseEvent> {
+        let (out_x, out_y) = self
+            .config
+            .pipeline
+            .process_held([x, y], [self.angle.is_some(); 2]);
+        let mut turn = 0.0;
+
+        if out_x != 0.0 || out_y != 0.0 {
+            let angle = out_x.atan2(-out_y).to_degrees();
+            match self.angle {
+                Some(previous) => turn += wrap_degrees(angle - previous),
+                None => {
+                    // A new flick finishes the one still in progress at once
+                    if let Some(flick) = self.flick.take() {
+                        turn += flick.angle - flick.turned;
+                    }
+                    self.flick = Some(Flick {
+                        started: now,
+                        angle,
+                        turned: 0.0,
+                    });
+                }
+            }
+            self.angle = Some(angle);
+        } else {
+            self.angle = None;
+        }
+
+        if let Some(flick) = self.flick.as_mut() {
+            let progress = if self.config.flick_time.is_zero() {
+                1.0
+            } else {
+                (now.duration_since(flick.started).as_secs_f32()
+                    / self.config.flick_time.as_secs_f32())
+                .min(1.0)
+            };
+            // Ease out, so the turn starts fast and settles on the target
+            let target = flick.angle * (1.0 - (1.0 - progress).powi(2));
+            turn += target - flick.turned;
+            flick.turned = target;
+            if progress >= 1.0 {
+                self.flick = None;
+            }
+        }
+
+        let counts = turn / 360.0 * self.config.counts_per_360 + self.remainder;
+        self.remainder = counts.fract();
+        let dx = counts.trunc() as i32;
+        (dx != 0).then_some(MouseEvent::Move { dx, dy: 0 })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::FlickStickConfig;
+    use crate::testing::{self, Step};
+    use std::time::Duration;
+
+    fn flick_stick() -> FlickStick {
+        let config: FlickStickConfig =
+            toml::from_str("counts_per_360 = 3600\nflick_ms = 100").unwrap();
+        FlickStick::new(config.codes())
+    }
+
+    /// Total horizontal counts for `(milliseconds, x, y)` raw stick samples.
+    fn play(stick: &mut FlickStick, trace: &[(u64, i32, i32)]) -> i32 {
+        let trace: Vec<_> = trace.iter().map(|&(ms, x, y)| (ms, [x, y])).collect();
+        let end = trace.last().map_or(0, |&(ms, _)| ms);
+        let mut counts = 0;
+        testing::play(&trace, end, |_, now, step| {
+            if let Step::Event(raw) = step
+                && let Some(MouseEvent::Move { dx, .. }) = stick.update(raw, now)
+            {
+                counts += dx;
+            }
+        });
+        counts
+    }
+
+    #[test]
+    fn flick_turns_toward_the_stick_over_time() {
+        let mut stick = flick_stick();
+        // Pushed hard right: a quarter turn, not all at once
+        let start = Instant::now();
+        assert_eq!(stick.update([255, 128], start), None);
+        let first = stick.update([255, 128], start + Duration::from_millis(10));
+        assert!(matches!(first, Some(MouseEvent::Move { dx, .. }) if dx > 0 && dx < 900));
+
+        let mut stick = flick_stick();
+        let right = play(
+            &mut stick,
+            &[
+                (0, 255, 128),
+                (20, 255, 128),
+                (60, 255, 128),
+                (120, 255, 128),
+            ],
+        );
+        assert!((right - 900).abs() <= 3, "{}", right);
+
+        let mut stick = flick_stick();
+        let behind = play(&mut stick, &[(0, 128, 255), (200, 128, 255)]);
+        assert!((behind.abs() - 1800).abs() <= 3, "{}", behind);
+    }
+
+    #[test]
+    fn rotating_while_pushed_keeps_turning() {
+        let mut stick = flick_stick();
+        // Flick up (no turn), then sweep round to the left. The raw range
+        // has no exact centre, so allow a few counts either way
+        let counts = play(
+            &mut stick,
+            &[(0, 128, 0), (200, 37, 37), (220, 0, 128), (240, 128, 128)],
+        );
+        assert!((counts + 900).abs() <= 3, "{}", counts);
+
+        // Releasing and flicking the same way turns again
+        let again = play(&mut stick, &[(300, 0, 128), (500, 0, 128)]);
+        assert!((again + 900).abs() <= 3, "{}", again);
+    }
+}
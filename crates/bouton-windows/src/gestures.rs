@@ -0,0 +1,378 @@
+use crate::config::{TouchGesturesCodeConfig, TouchpadCodeConfig};
+use bouton_core::ControlTouch;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Instant;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwipeDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TouchGesture {
+    /// A one-finger tap in a grid region, numbered row by row
+    Tap(usize),
+    Swipe {
+        fingers: usize,
+        direction: SwipeDirection,
+    },
+}
+
+impl TouchGesture {
+    /// Key bound to this gesture, if any.
+    pub fn key(&self, config: &TouchGesturesCodeConfig) -> Option<u32> {
+        match *self {
+            TouchGesture::Tap(region) => config.regions.get(region).copied(),
+            TouchGesture::Swipe { fingers, direction } => {
+                let swipes = match fingers {
+                    1 => &config.swipes,
+                    _ => &config.two_finger_swipes,
+                };
+                // Swipe keys are stored up, down, left, right like the enum
+                swipes[direction as usize]
+            }
+        }
+    }
+}
+
+impl fmt::Display for TouchGesture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TouchGesture::Tap(region) => write!(f, "tap in region {}", region + 1),
+            TouchGesture::Swipe { fingers, direction } => {
+                write!(f, "{}-finger swipe {:?}", fingers, direction)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Finger {
+    start: (i32, i32),
+    last: (i32, i32),
+}
+
+impl Finger {
+    fn travel(&self) -> (f32, f32) {
+        (
+            (self.last.0 - self.start.0) as f32,
+            (self.last.1 - self.start.1) as f32,
+        )
+    }
+}
+
+/// Recognizes a gesture once every finger has lifted: a short touch that
+/// barely moved is a tap in the region the finger landed in, and a quick
+/// long stroke is a swipe in its main direction.
+pub struct GestureRecognizer {
+    config: TouchpadCodeConfig,
+    fingers: BTreeMap<u8, Finger>,
+    /// Travel of the fingers that already lifted during this touch
+    lifted: Vec<(f32, f32)>,
+    first_position: (i32, i32),
+    touch_started: Option<Instant>,
+    most_fingers: usize,
+    moved: bool,
+}
+
+impl GestureRecognizer {
+    pub fn new(config: TouchpadCodeConfig) -> Self {
+        Self {
+            config,
+            fingers: BTreeMap::new(),
+            lifted: Vec::new(),
+            first_position: (0, 0),
+            touch_started: None,
+            most_fingers: 0,
+            moved: false,
+        }
+    }
+
+    pub fn touch(&mut self, touch: ControlTouch, now: Instant) -> Option<TouchGesture> {
+        let Some(position) = touch.position else {
+            let finger = self.fingers.remove(&touch.slot)?;
+            self.lifted.push(finger.travel());
+            return if self.fingers.is_empty() {
+                self.recognize(now)
+            } else {
+                None
+            };
+        };
+
+        match self.fingers.get_mut(&touch.slot) {
+            Some(finger) => {
+                finger.last = position;
+                let (dx, dy) = finger.travel();
+                if dx.hypot(dy) > self.config.tap_distance {
+                    self.moved = true;
+                }
+            }
+            None => {
+                if self.fingers.is_empty() {
+                    self.lifted.clear();
+                    self.first_position = position;
+                    self.touch_started = Some(now);
+                    self.most_fingers = 0;
+                    self.moved = false;
+                }
+                self.fingers.insert(
+                    touch.slot,
+                    Finger {
+                        start: position,
+                        last: position,
+                    },
+                );
+                self.most_fingers = self.most_fingers.max(self.fingers.len());
+            }
+        }
+        None
+    }
+
+    fn recognize(&self, now: Instant) -> Option<TouchGesture> {
+        let duration = now.duration_since(self.touch_started?);
+
+        if !self.moved {
+            return (self.most_fingers == 1 && duration <= self.config.tap_time)
+                .then(|| TouchGesture::Tap(self.region(self.first_position)));
+        }
+
+        if self.most_fingers > 2 || duration > self.config.gestures.swipe_time {
+            return None;
+        }
+
+        // Average travel over all fingers of the touch
+        let count = self.lifted.len().max(1) as f32;
+        let (dx, dy) = self.lifted.iter().fold((0.0, 0.0), |(x, y), (dx, dy)| {
+            (x + dx / count, y + dy / count)
+        });
+        if dx.hypot(dy) < self.config.gestures.swipe_distance {
+            return None;
+        }
+
+        let direction = if dx.abs() > dy.abs() {
+            if dx > 0.0 {
+                SwipeDirection::Right
+            } else {
+                SwipeDirection::Left
+            }
+        } else if dy > 0.0 {
+            SwipeDirection::Down
+        } else {
+            SwipeDirection::Up
+        };
+
+        Some(TouchGesture::Swipe {
+            fingers: self.most_fingers,
+            direction,
+        })
+    }
+
+    /// Grid region containing `(x, y)`, numbered row by row.
+    fn region(&self, (x, y): (i32, i32)) -> usize {
+        let [columns, rows] = self.config.gestures.grid;
+        let cell = |value: i32, [min, max]: [i32; 2], cells: usize| {
+            let fraction = (value - min) as f32 / (max - min).max(1) as f32;
+            ((fraction * cells as f32) as usize).min(cells - 1)
+        };
+
+        let column = cell(x, self.config.range_x, columns);
+        let row = cell(y, self.config.range_y, rows);
+        row * columns + column
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TouchpadConfig;
+    use crate::socket_server::TICK_INTERVAL;
+    use crate::testing::{self, Step};
+    use bouton_core::touch::parse_trace;
+    use std::fs;
+    use std::path::Path;
+
+    /// `(milliseconds, slot, position)` touches
+    type Trace = [(u64, u8, Option<(i32, i32)>)];
+
+    fn recognizer(grid: &str) -> GestureRecognizer {
+        let config: TouchpadConfig = toml::from_str(&format!(
+            "mode = \"gestures\"\n\n[gestures]\ngrid = {}",
+            grid
+        ))
+        .unwrap();
+        GestureRecognizer::new(config.codes())
+    }
+
+    fn play(recognizer: &mut GestureRecognizer, trace: &Trace) -> Vec<TouchGesture> {
+        let trace: Vec<_> = trace
+            .iter()
+            .map(|&(ms, slot, position)| (ms, ControlTouch { slot, position }))
+            .collect();
+        play_touches(recognizer, &trace)
+    }
+
+    fn play_touches(
+        recognizer: &mut GestureRecognizer,
+        trace: &[(u64, ControlTouch)],
+    ) -> Vec<TouchGesture> {
+        // Recorded touches fall between ticks, so play on to the tick that
+        // delivers the last one
+        let tick = TICK_INTERVAL.as_millis() as u64;
+        let end = trace.last().map_or(0, |&(ms, _)| ms.next_multiple_of(tick));
+        let mut gestures = Vec::new();
+        testing::play(trace, end, |_, now, step| {
+            if let Step::Event(touch) = step {
+                gestures.extend(recognizer.touch(touch, now));
+            }
+        });
+        gestures
+    }
+
+    #[test]
+    fn taps_pick_grid_regions() {
+        let mut grid = recognizer("[3, 3]");
+        let taps = play(
+            &mut grid,
+            &[
+                // Centre
+                (0, 0, Some((960, 540))),
+                (20, 0, Some((965, 542))),
+                (80, 0, None),
+                // Top right
+                (500, 0, Some((1800, 100))),
+                (560, 0, None),
+                // Bottom left
+                (1000, 1, Some((100, 1000))),
+                (1060, 1, None),
+            ],
+        );
+        assert_eq!(
+            taps,
+            vec![
+                TouchGesture::Tap(4),
+                TouchGesture::Tap(2),
+                TouchGesture::Tap(6)
+            ]
+        );
+
+        let mut quadrants = recognizer("[2, 2]");
+        let taps = play(&mut quadrants, &[(0, 0, Some((1500, 900))), (50, 0, None)]);
+        assert_eq!(taps, vec![TouchGesture::Tap(3)]);
+    }
+
+    #[test]
+    fn long_press_is_not_a_tap() {
+        let mut grid = recognizer("[2, 2]");
+        let gestures = play(&mut grid, &[(0, 0, Some((500, 500))), (600, 0, None)]);
+        assert!(gestures.is_empty());
+    }
+
+    #[test]
+    fn quick_strokes_are_swipes() {
+        let mut pad = recognizer("[2, 2]");
+        let right = play(
+            &mut pad,
+            &[
+                (0, 0, Some((300, 500))),
+                (40, 0, Some((600, 520))),
+                (80, 0, Some((900, 540))),
+                (120, 0, None),
+            ],
+        );
+        assert_eq!(
+            right,
+            vec![TouchGesture::Swipe {
+                fingers: 1,
+                direction: SwipeDirection::Right,
+            }]
+        );
+
+        let up = play(
+            &mut pad,
+            &[
+                (0, 0, Some((700, 900))),
+                (10, 1, Some((1100, 900))),
+                (60, 0, Some((700, 500))),
+                (60, 1, Some((1100, 480))),
+                (100, 0, Some((700, 300))),
+                (100, 1, Some((1100, 320))),
+                (150, 1, None),
+                (160, 0, None),
+            ],
+        );
+        assert_eq!(
+            up,
+            vec![TouchGesture::Swipe {
+                fingers: 2,
+                direction: SwipeDirection::Up,
+            }]
+        );
+    }
+
+    #[test]
+    fn slow_or_short_strokes_are_ignored() {
+        let mut pad = recognizer("[2, 2]");
+        let slow = play(
+            &mut pad,
+            &[
+                (0, 0, Some((300, 500))),
+                (500, 0, Some((700, 500))),
+                (900, 0, Some((900, 500))),
+                (950, 0, None),
+            ],
+        );
+        assert!(slow.is_empty());
+
+        let short = play(
+            &mut pad,
+            &[
+                (0, 0, Some((300, 500))),
+                (50, 0, Some((450, 500))),
+                (80, 0, None),
+            ],
+        );
+        assert!(short.is_empty());
+    }
+
+    /// Replays the traces in `fixtures/touch`, recorded from a touchpad with
+    /// `bouton-linux --record-touch`. Each one names its grid in a
+    /// `# grid = [columns, rows]` line and the gestures it should give, comma
+    /// separated, in a `# gestures:` line.
+    #[test]
+    fn recorded_traces_give_their_gestures() {
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/touch");
+        let mut replayed = 0;
+        for entry in fs::read_dir(fixtures).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension() != Some("trace".as_ref()) {
+                continue;
+            }
+            let trace = fs::read_to_string(&path).unwrap();
+            let header = |name: &str| {
+                trace
+                    .lines()
+                    .find_map(|line| line.strip_prefix(name))
+                    .unwrap_or_else(|| panic!("{}: no {:?} line", path.display(), name))
+                    .trim()
+            };
+
+            let mut pad = recognizer(header("# grid ="));
+            let gestures: Vec<String> = play_touches(&mut pad, &parse_trace(&trace).unwrap())
+                .iter()
+                .map(ToString::to_string)
+                .collect();
+            let expected: Vec<&str> = header("# gestures:")
+                .split(',')
+                .map(str::trim)
+                .filter(|gesture| !gesture.is_empty())
+                .collect();
+            assert_eq!(gestures, expected, "{}", path.display());
+            replayed += 1;
+        }
+        assert!(replayed > 0, "no touch traces to replay");
+    }
+}
@@ -0,0 +1,197 @@
+use crate::config::{GyroButtonMode, GyroCodeConfig, GyroHorizontal};
+use crate::key_injector::MouseEvent;
+use bouton_core::control::GamepadControl;
+use bouton_core::{ControlMotion, KeyAction};
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Longest gap between samples that still counts as continuous motion, so
+/// a stalled client doesn't turn its next sample into a huge jump
+const MAX_SAMPLE_GAP: Duration = Duration::from_millis(50);
+
+/// Turns gyro rates into mouse movement. The angle turned since the last
+/// sample, times `sensitivity`, is how far the cursor moves.
+///
+/// Slow turns can be smoothed (averaged over the last few samples, blending
+/// back to the raw rate as the turn speeds up) and tightened (scaled down
+/// towards zero), both of which hide hand shake while aiming.
+pub struct GyroMouse {
+    config: GyroCodeConfig,
+    button_held: bool,
+    last_sample: Option<Instant>,
+    /// Recent rates in degrees per second, for smoothing
+    history: VecDeque<(f32, f32)>,
+    remainder: (f32, f32),
+}
+
+impl GyroMouse {
+    pub fn new(config: GyroCodeConfig) -> Self {
+        Self {
+            config,
+            button_held: false,
+            last_sample: None,
+            history: VecDeque::new(),
+            remainder: (0.0, 0.0),
+        }
+    }
+
+    /// Tracks the gyro button. Returns whether `control` is that button.
+    pub fn button(&mut self, control: GamepadControl, action: KeyAction) -> bool {
+        if self.config.button != Some(control) {
+            return false;
+        }
+        self.button_held = action == KeyAction::Press;
+        true
+    }
+
+    fn active(&self) -> bool {
+        match (self.config.button, self.config.button_mode) {
+            (None, _) => true,
+            (Some(_), GyroButtonMode::Enable) => self.button_held,
+            (Some(_), GyroButtonMode::Ratchet) => !self.button_held,
+        }
+    }
+
+    pub fn motion(&mut self, motion: ControlMotion, now: Instant) -> Option<MouseEvent> {
+        let elapsed = self
+            .last_sample
+            .replace(now)
+            .map(|last| now.duration_since(last).min(MAX_SAMPLE_GAP))?;
+
+        if !self.active() {
+            self.history.clear();
+            self.remainder = (0.0, 0.0);
+            return None;
+        }
+
+        // Positive rates turn left and tilt up, which move the cursor the
+        // opposite way on screen
+        let [pitch, yaw, roll] = motion.gyro.map(|rate| rate as f32 / self.config.resolution);
+        let horizontal = match self.config.horizontal {
+            GyroHorizontal::Yaw => yaw,
+            GyroHorizontal::Roll => roll,
+        };
+        let mut rate = (-horizontal, -pitch);
+        if self.config.invert_x {
+            rate.0 = -rate.0;
+        }
+        if self.config.invert_y {
+            rate.1 = -rate.1;
+        }
+
+        let smoothed = self.smooth(rate);
+        let rate = self.tighten(smoothed);
+
+        let seconds = elapsed.as_secs_f32();
+        let x = rate.0 * seconds * self.config.sensitivity + self.remainder.0;
+        let y = rate.1 * seconds * self.config.sensitivity + self.remainder.1;
+        self.remainder = (x.fract(), y.fract());
+
+        let (dx, dy) = (x.trunc() as i32, y.trunc() as i32);
+        (dx != 0 || dy != 0).then_some(MouseEvent::Move { dx, dy })
+    }
+
+    /// Tiered smoothing: below half the threshold the averaged rate is
+    /// used, above the threshold the raw rate, and in between a blend.
+    fn smooth(&mut self, rate: (f32, f32)) -> (f32, f32) {
+        self.history.push_back(rate);
+        while self.history.len() > self.config.smoothing_samples {
+            self.history.pop_front();
+        }
+
+        let threshold = self.config.smoothing;
+        if threshold <= 0.0 {
+            return rate;
+        }
+
+        let count = self.history.len() as f32;
+        let average = self.history.iter().fold((0.0, 0.0), |(x, y), (dx, dy)| {
+            (x + dx / count, y + dy / count)
+        });
+        let direct = ((rate.0.hypot(rate.1) - threshold / 2.0) / (threshold / 2.0)).clamp(0.0, 1.0);
+        (
+            rate.0 * direct + average.0 * (1.0 - direct),
+            rate.1 * direct + average.1 * (1.0 - direct),
+        )
+    }
+
+    /// Scales turns slower than the tightening threshold by how far below
+    /// it they are, so tiny wobbles barely move the cursor.
+    fn tighten(&self, rate: (f32, f32)) -> (f32, f32) {
+        let threshold = self.config.tightening;
+        let speed = rate.0.hypot(rate.1);
+        if threshold <= 0.0 || speed >= threshold {
+            return rate;
+        }
+        let scale = speed / threshold;
+        (rate.0 * scale, rate.1 * scale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::GyroConfig;
+
+    fn gyro(toml: &str) -> GyroMouse {
+        let config: GyroConfig = toml::from_str(toml).unwrap();
+        GyroMouse::new(config.codes())
+    }
+
+    /// Total cursor travel for `samples` samples 10 ms apart at a constant
+    /// rate, in degrees per second on pitch, yaw and roll.
+    fn turn(gyro: &mut GyroMouse, rate: [f32; 3], samples: u64) -> (i32, i32) {
+        let start = Instant::now();
+        let motion = ControlMotion {
+            gyro: rate.map(|rate| (rate * 1024.0) as i32),
+            accel: [0, 0, 0],
+        };
+        (0..=samples)
+            .filter_map(|sample| gyro.motion(motion, start + Duration::from_millis(sample * 10)))
+            .fold((0, 0), |(x, y), event| match event {
+                MouseEvent::Move { dx, dy } => (x + dx, y + dy),
+                _ => (x, y),
+            })
+    }
+
+    #[test]
+    fn turning_moves_by_angle_times_sensitivity() {
+        // 10 degrees a second for one second, at 10 pixels per degree
+        let (dx, dy) = turn(&mut gyro(""), [0.0, 10.0, 0.0], 100);
+        assert!((dx + 100).abs() <= 1, "{}", dx);
+        assert_eq!(dy, 0);
+
+        let (dx, dy) = turn(&mut gyro("invert_y = true"), [-5.0, 0.0, 0.0], 100);
+        assert_eq!(dx, 0);
+        assert!((dy + 50).abs() <= 1, "{}", dy);
+    }
+
+    #[test]
+    fn roll_can_steer_horizontally() {
+        let (dx, _) = turn(&mut gyro("horizontal = \"roll\""), [0.0, 10.0, -10.0], 100);
+        assert!((dx - 100).abs() <= 1, "{}", dx);
+    }
+
+    #[test]
+    fn tightening_shrinks_slow_turns() {
+        let (slow, _) = turn(&mut gyro("tightening = 4"), [0.0, 2.0, 0.0], 100);
+        assert!((slow + 10).abs() <= 1, "{}", slow);
+
+        let (fast, _) = turn(&mut gyro("tightening = 4"), [0.0, 8.0, 0.0], 100);
+        assert!((fast + 80).abs() <= 1, "{}", fast);
+    }
+
+    #[test]
+    fn button_enables_or_ratchets() {
+        let mut held = gyro("button = \"L1\"");
+        assert_eq!(turn(&mut held, [0.0, 10.0, 0.0], 100), (0, 0));
+        assert!(held.button(GamepadControl::L1, KeyAction::Press));
+        assert_ne!(turn(&mut held, [0.0, 10.0, 0.0], 100), (0, 0));
+
+        let mut ratchet = gyro("button = \"L1\"\nbutton_mode = \"ratchet\"");
+        assert!(!ratchet.button(GamepadControl::R1, KeyAction::Press));
+        assert_ne!(turn(&mut ratchet, [0.0, 10.0, 0.0], 100), (0, 0));
+        ratchet.button(GamepadControl::L1, KeyAction::Press);
+        assert_eq!(turn(&mut ratchet, [0.0, 10.0, 0.0], 100), (0, 0));
+    }
+}
@@ -1,7 +1,81 @@
+use crate::keycode::KeyCode;
 use bouton_core::KeyAction;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseEvent {
+    /// Relative cursor movement in pixels
+    Move { dx: i32, dy: i32 },
+    Button(MouseButton, KeyAction),
+    /// Wheel notches, positive scrolling up (or right when `horizontal`)
+    Wheel { notches: i32, horizontal: bool },
+}
+
+/// One keyboard input that types part of a text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextInput {
+    /// A virtual key pressed and released
+    Key(u32),
+    /// A UTF-16 code unit sent as a Unicode key event
+    Unit(u16),
+}
+
+/// The inputs that type `text`. Line breaks, tabs and `\u{8}` become Enter,
+/// Tab and Backspace, which applications handle more reliably than the
+/// characters, and `\r` is dropped so `\r\n` is a single Enter. Characters
+/// outside the BMP go as a surrogate pair.
+pub fn text_inputs(text: &str) -> Vec<TextInput> {
+    let mut inputs = Vec::new();
+    for character in text.chars() {
+        let key = match character {
+            '\n' => KeyCode::Enter,
+            '\t' => KeyCode::Tab,
+            '\u{8}' => KeyCode::Backspace,
+            '\r' => continue,
+            _ => {
+                let mut units = [0u16; 2];
+                for &unit in character.encode_utf16(&mut units).iter() {
+                    inputs.push(TextInput::Unit(unit));
+                }
+                continue;
+            }
+        };
+        inputs.push(TextInput::Key(key.code()));
+    }
+    inputs
+}
+
+/// Where resolved output ends up. `KeyInjector` is the real one; tests swap
+/// in a recorder so the output layer can be checked on any platform.
+pub trait InputBackend {
+    fn send_key(&mut self, key_code: u32, action: KeyAction) -> Result<(), String>;
+    fn send_mouse(&mut self, event: MouseEvent) -> Result<(), String>;
+    /// Types `text` whatever the keyboard layout
+    fn send_text(&mut self, text: &str) -> Result<(), String>;
+}
+
 pub struct KeyInjector;
 
+impl InputBackend for KeyInjector {
+    fn send_key(&mut self, key_code: u32, action: KeyAction) -> Result<(), String> {
+        KeyInjector::inject(key_code, action)
+    }
+
+    fn send_mouse(&mut self, event: MouseEvent) -> Result<(), String> {
+        KeyInjector::inject_mouse(event)
+    }
+
+    fn send_text(&mut self, text: &str) -> Result<(), String> {
+        KeyInjector::inject_text(text)
+    }
+}
+
 impl KeyInjector {
     pub fn inject(key_code: u32, action: KeyAction) -> Result<(), String> {
         #[cfg(target_os = "windows")]
@@ -47,4 +121,159 @@ impl KeyInjector {
             Ok(())
         }
     }
+
+    /// Types text as Unicode key events, so it doesn't depend on the
+    /// keyboard layout. See `text_inputs` for the characters sent as keys.
+    pub fn inject_text(text: &str) -> Result<(), String> {
+        #[cfg(target_os = "windows")]
+        {
+            use windows::Win32::UI::Input::KeyboardAndMouse::{
+                SendInput, INPUT, INPUT_KEYBOARD, KEYBDINPUT, KEYBD_EVENT_FLAGS,
+                KEYEVENTF_KEYUP, KEYEVENTF_UNICODE, VIRTUAL_KEY,
+            };
+
+            let keyboard_input = |vk: VIRTUAL_KEY, scan: u16, flags: KEYBD_EVENT_FLAGS| {
+                let mut input = INPUT::default();
+                input.r#type = INPUT_KEYBOARD;
+                input.Anonymous.ki = KEYBDINPUT {
+                    wVk: vk,
+                    wScan: scan,
+                    dwFlags: flags,
+                    time: 0,
+                    dwExtraInfo: 0,
+                };
+                input
+            };
+
+            let mut inputs = Vec::new();
+            for input in text_inputs(text) {
+                match input {
+                    TextInput::Key(key_code) => {
+                        let vk = VIRTUAL_KEY(key_code as u16);
+                        inputs.push(keyboard_input(vk, 0, KEYBD_EVENT_FLAGS(0)));
+                        inputs.push(keyboard_input(vk, 0, KEYEVENTF_KEYUP));
+                    }
+                    TextInput::Unit(unit) => {
+                        inputs.push(keyboard_input(VIRTUAL_KEY(0), unit, KEYEVENTF_UNICODE));
+                        inputs.push(keyboard_input(
+                            VIRTUAL_KEY(0),
+                            unit,
+                            KEYEVENTF_UNICODE | KEYEVENTF_KEYUP,
+                        ));
+                    }
+                }
+            }
+            if inputs.is_empty() {
+                return Ok(());
+            }
+
+            unsafe {
+                let sent = SendInput(&inputs, std::mem::size_of::<INPUT>() as i32);
+                if sent as usize != inputs.len() {
+                    return Err("SendInput failed".to_string());
+                }
+            }
+
+            Ok(())
+        }
+
+        #[cfg(not(target_os = "windows"))]
+        {
+            println!("Text (non-Windows): {:?} as {:?}", text, text_inputs(text));
+            Ok(())
+        }
+    }
+
+    pub fn inject_mouse(event: MouseEvent) -> Result<(), String> {
+        #[cfg(target_os = "windows")]
+        {
+            use windows::Win32::UI::Input::KeyboardAndMouse::{
+                SendInput, INPUT, INPUT_MOUSE, MOUSEEVENTF_HWHEEL, MOUSEEVENTF_LEFTDOWN,
+                MOUSEEVENTF_LEFTUP, MOUSEEVENTF_MIDDLEDOWN, MOUSEEVENTF_MIDDLEUP,
+                MOUSEEVENTF_MOVE, MOUSEEVENTF_RIGHTDOWN, MOUSEEVENTF_RIGHTUP,
+                MOUSEEVENTF_WHEEL, MOUSEINPUT,
+            };
+
+            // One wheel notch, WHEEL_DELTA
+            const WHEEL_DELTA: i32 = 120;
+
+            let (dx, dy, mouse_data, flags) = match event {
+                MouseEvent::Move { dx, dy } => (dx, dy, 0, MOUSEEVENTF_MOVE),
+                MouseEvent::Button(button, action) => {
+                    let flags = match (button, action) {
+                        (MouseButton::Left, KeyAction::Press) => MOUSEEVENTF_LEFTDOWN,
+                        (MouseButton::Left, KeyAction::Release) => MOUSEEVENTF_LEFTUP,
+                        (MouseButton::Right, KeyAction::Press) => MOUSEEVENTF_RIGHTDOWN,
+                        (MouseButton::Right, KeyAction::Release) => MOUSEEVENTF_RIGHTUP,
+                        (MouseButton::Middle, KeyAction::Press) => MOUSEEVENTF_MIDDLEDOWN,
+                        (MouseButton::Middle, KeyAction::Release) => MOUSEEVENTF_MIDDLEUP,
+                    };
+                    (0, 0, 0, flags)
+                }
+                MouseEvent::Wheel { notches, horizontal } => {
+                    let flags = if horizontal {
+                        MOUSEEVENTF_HWHEEL
+                    } else {
+                        MOUSEEVENTF_WHEEL
+                    };
+                    (0, 0, (notches * WHEEL_DELTA) as u32, flags)
+                }
+            };
+
+            let mut input = INPUT::default();
+            input.r#type = INPUT_MOUSE;
+
+            unsafe {
+                input.Anonymous.mi = MOUSEINPUT {
+                    dx,
+                    dy,
+                    mouseData: mouse_data,
+                    dwFlags: flags,
+                    time: 0,
+                    dwExtraInfo: 0,
+                };
+
+                let result = SendInput(&[input], std::mem::size_of::<INPUT>() as i32);
+                if result == 0 {
+                    return Err("SendInput failed".to_string());
+                }
+            }
+
+            Ok(())
+        }
+
+        #[cfg(not(target_os = "windows"))]
+        {
+            println!("Mouse event (non-Windows): {:?}", event);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_goes_as_code_units_with_control_characters_as_keys() {
+        assert_eq!(
+            text_inputs("a\r\n\tb\u{8}"),
+            vec![
+                TextInput::Unit('a' as u16),
+                TextInput::Key(KeyCode::Enter.code()),
+                TextInput::Key(KeyCode::Tab.code()),
+                TextInput::Unit('b' as u16),
+                TextInput::Key(KeyCode::Backspace.code()),
+            ]
+        );
+        // Outside the BMP: a surrogate pair
+        assert_eq!(
+            text_inputs("é👍"),
+            vec![
+                TextInput::Unit(0xE9),
+                TextInput::Unit(0xD83D),
+                TextInput::Unit(0xDC4D),
+            ]
+        );
+    }
 }
@@ -0,0 +1,325 @@
+use crate::config::KeyboardCodeConfig;
+use crate::directions::Direction8;
+use bouton_core::KeyAction;
+use bouton_core::control::GamepadControl;
+use std::time::{Duration, Instant};
+
+/// How long a direction is held before the cursor starts repeating
+const REPEAT_DELAY: Duration = Duration::from_millis(400);
+/// Time between repeated cursor moves while a direction stays held
+const REPEAT_INTERVAL: Duration = Duration::from_millis(120);
+
+/// What the keyboard wants typed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyboardOutput {
+    Text(String),
+    /// A virtual key tapped, for backspace
+    Key(u32),
+}
+
+/// What the TUI shows of the keyboard.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyboardView {
+    /// The rows of the current shift state
+    pub rows: Vec<Vec<char>>,
+    /// Row and column of the selected character
+    pub cursor: (usize, usize),
+    pub shift: bool,
+}
+
+/// On-screen keyboard. The toggle button opens and closes it; while it is
+/// open the navigation source moves a cursor over the character grid and
+/// the select, backspace, shift and space buttons type. Shift applies to
+/// the next character only.
+pub struct OnScreenKeyboard {
+    config: KeyboardCodeConfig,
+    open: bool,
+    cursor: (usize, usize),
+    shift: bool,
+    position: (i32, i32),
+    direction: Option<Direction8>,
+    /// When the held direction next moves the cursor again
+    repeat_at: Option<Instant>,
+}
+
+impl OnScreenKeyboard {
+    pub fn new(config: KeyboardCodeConfig) -> Self {
+        let center = config.pipeline.center();
+        let position = match config.source {
+            GamepadControl::DPadX => (0, 0),
+            _ => (center, center),
+        };
+        Self {
+            config,
+            open: false,
+            cursor: (0, 0),
+            shift: false,
+            position,
+            direction: None,
+            repeat_at: None,
+        }
+    }
+
+    pub fn view(&self) -> Option<KeyboardView> {
+        self.open.then(|| KeyboardView {
+            rows: self.rows().to_vec(),
+            cursor: self.cursor,
+            shift: self.shift,
+        })
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// The navigation source and typing buttons, which the open keyboard
+    /// takes over from their normal bindings.
+    pub fn controls(&self) -> [GamepadControl; 5] {
+        let config = &self.config;
+        [
+            config.source,
+            config.select,
+            config.backspace,
+            config.shift,
+            config.space,
+        ]
+    }
+
+    /// Whether the keyboard takes `control` instead of its normal binding:
+    /// the toggle button always, the typing buttons while it is open.
+    pub fn consumes(&self, control: GamepadControl) -> bool {
+        let config = &self.config;
+        control == config.toggle
+            || (self.open
+                && [config.select, config.backspace, config.shift, config.space].contains(&control))
+    }
+
+    /// Whether `control` is a navigation axis while the keyboard is open.
+    pub fn watches(&self, control: GamepadControl) -> bool {
+        if !self.open {
+            return false;
+        }
+        let source = match control {
+            GamepadControl::LeftStickX | GamepadControl::LeftStickY => GamepadControl::LeftStickX,
+            GamepadControl::RightStickX | GamepadControl::RightStickY => {
+                GamepadControl::RightStickX
+            }
+            GamepadControl::DPadX | GamepadControl::DPadY => GamepadControl::DPadX,
+            _ => return false,
+        };
+        source == self.config.source
+    }
+
+    /// Takes a press or release of a button it consumes.
+    pub fn button(&mut self, control: GamepadControl, action: KeyAction) -> Option<KeyboardOutput> {
+        if action != KeyAction::Press {
+            return None;
+        }
+        let config = &self.config;
+        if control == config.toggle {
+            self.open = !self.open;
+            self.set_shift(false);
+            self.direction = None;
+            self.repeat_at = None;
+            None
+        } else if control == config.select {
+            let (row, column) = self.cursor;
+            let character = *self.rows().get(row)?.get(column)?;
+            self.set_shift(false);
+            Some(KeyboardOutput::Text(character.to_string()))
+        } else if control == config.backspace {
+            Some(KeyboardOutput::Key(config.backspace_key))
+        } else if control == config.shift {
+            self.set_shift(!self.shift);
+            None
+        } else if control == config.space {
+            Some(KeyboardOutput::Text(" ".to_string()))
+        } else {
+            None
+        }
+    }
+
+    /// Feeds a navigation axis value, moving the cursor when a new
+    /// direction is pushed. Returns true when the cursor moved.
+    pub fn axis(&mut self, control: GamepadControl, value: i32, now: Instant) -> bool {
+        let is_x_axis = matches!(
+            control,
+            GamepadControl::LeftStickX | GamepadControl::RightStickX | GamepadControl::DPadX
+        );
+        // D-Pad axes arrive as -1, 0 or 1 wrapped into a byte
+        let value = match self.config.source {
+            GamepadControl::DPadX => value as u8 as i8 as i32,
+            _ => value,
+        };
+        if is_x_axis {
+            self.position.0 = value;
+        } else {
+            self.position.1 = value;
+        }
+
+        let (x, y) = self.position;
+        let (dx, dy) = match self.config.source {
+            GamepadControl::DPadX => (x as f32, y as f32),
+            _ => self.config.pipeline.process(x, y),
+        };
+        // Cardinal directions only, so a diagonal D-Pad press moves one way
+        let direction = (dx != 0.0 || dy != 0.0).then(|| Direction8::from_vector(dx, dy, 0.0));
+        if direction == self.direction {
+            return false;
+        }
+        self.direction = direction;
+        self.repeat_at = direction.map(|_| now + REPEAT_DELAY);
+        direction.is_some_and(|direction| self.move_cursor(direction))
+    }
+
+    /// Repeats the cursor move while a direction stays held. Returns true
+    /// when the cursor moved.
+    pub fn tick(&mut self, now: Instant) -> bool {
+        let (Some(direction), Some(repeat_at)) = (self.direction, self.repeat_at) else {
+            return false;
+        };
+        if !self.open || now < repeat_at {
+            return false;
+        }
+        self.repeat_at = Some(now + REPEAT_INTERVAL);
+        self.move_cursor(direction)
+    }
+
+    fn rows(&self) -> &[Vec<char>] {
+        if self.shift {
+            &self.config.shift_rows
+        } else {
+            &self.config.rows
+        }
+    }
+
+    /// Left and right wrap around the row, up and down wrap around the grid.
+    fn move_cursor(&mut self, direction: Direction8) -> bool {
+        let rows = self.rows().len();
+        if rows == 0 {
+            return false;
+        }
+        let (row, column) = self.cursor;
+        let width = self.rows().get(row).map_or(0, Vec::len).max(1);
+        self.cursor = match direction {
+            Direction8::Up => ((row + rows - 1) % rows, column),
+            Direction8::Down => ((row + 1) % rows, column),
+            Direction8::Left => (row, (column + width - 1) % width),
+            Direction8::Right => (row, (column + 1) % width),
+            _ => return false,
+        };
+        self.clamp_cursor();
+        self.cursor != (row, column)
+    }
+
+    /// Switches between the rows and shift rows, which can differ in size.
+    fn set_shift(&mut self, shift: bool) {
+        self.shift = shift;
+        self.clamp_cursor();
+    }
+
+    /// Keeps the cursor on the last character of a shorter row.
+    fn clamp_cursor(&mut self) {
+        let (row, column) = self.cursor;
+        let row = row.min(self.rows().len().saturating_sub(1));
+        let width = self.rows().get(row).map_or(0, Vec::len);
+        self.cursor = (row, column.min(width.saturating_sub(1)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::KeyboardConfig;
+
+    fn keyboard() -> OnScreenKeyboard {
+        let config: KeyboardConfig =
+            toml::from_str("toggle = \"Touch\"\nrows = [\"abc\", \"de\"]").unwrap();
+        let mut keyboard = OnScreenKeyboard::new(config.codes().unwrap());
+        keyboard.button(GamepadControl::Touch, KeyAction::Press);
+        keyboard
+    }
+
+    /// Presses and lets go of the D-Pad, -1, 0 or 1 per axis.
+    fn push(keyboard: &mut OnScreenKeyboard, (x, y): (i32, i32), now: Instant) {
+        keyboard.axis(GamepadControl::DPadX, x as u8 as i32, now);
+        keyboard.axis(GamepadControl::DPadY, y as u8 as i32, now);
+        keyboard.axis(GamepadControl::DPadX, 0, now);
+        keyboard.axis(GamepadControl::DPadY, 0, now);
+    }
+
+    #[test]
+    fn navigate_and_type() {
+        let mut keyboard = keyboard();
+        let now = Instant::now();
+        push(&mut keyboard, (-1, 0), now);
+        assert_eq!(keyboard.view().unwrap().cursor, (0, 2));
+        push(&mut keyboard, (0, 1), now);
+        // Clamped onto the shorter row
+        assert_eq!(keyboard.view().unwrap().cursor, (1, 1));
+
+        keyboard.button(GamepadControl::Triangle, KeyAction::Press);
+        assert_eq!(
+            keyboard.button(GamepadControl::Cross, KeyAction::Press),
+            Some(KeyboardOutput::Text("E".to_string()))
+        );
+        // Shift only lasts for one character
+        assert_eq!(
+            keyboard.button(GamepadControl::Cross, KeyAction::Press),
+            Some(KeyboardOutput::Text("e".to_string()))
+        );
+        assert_eq!(
+            keyboard.button(GamepadControl::Circle, KeyAction::Press),
+            Some(KeyboardOutput::Text(" ".to_string()))
+        );
+        assert_eq!(
+            keyboard.button(GamepadControl::Square, KeyAction::Press),
+            Some(KeyboardOutput::Key(0x08))
+        );
+    }
+
+    #[test]
+    fn closed_keyboard_leaves_buttons_alone() {
+        let mut keyboard = keyboard();
+        assert!(keyboard.consumes(GamepadControl::Cross));
+        keyboard.button(GamepadControl::Touch, KeyAction::Press);
+        assert!(!keyboard.consumes(GamepadControl::Cross));
+        assert!(keyboard.consumes(GamepadControl::Touch));
+        assert!(!keyboard.watches(GamepadControl::DPadX));
+        assert_eq!(keyboard.view(), None);
+    }
+
+    #[test]
+    fn cursor_stays_inside_smaller_unshifted_rows() {
+        let config: KeyboardConfig =
+            toml::from_str("toggle = \"Touch\"\nrows = [\"abc\"]\nshift_rows = [\"ABC\", \"DEF\"]")
+                .unwrap();
+        let mut keyboard = OnScreenKeyboard::new(config.codes().unwrap());
+        keyboard.button(GamepadControl::Touch, KeyAction::Press);
+        let now = Instant::now();
+
+        keyboard.button(GamepadControl::Triangle, KeyAction::Press);
+        push(&mut keyboard, (0, 1), now);
+        assert_eq!(
+            keyboard.button(GamepadControl::Cross, KeyAction::Press),
+            Some(KeyboardOutput::Text("D".to_string()))
+        );
+        assert_eq!(keyboard.view().unwrap().cursor, (0, 0));
+        push(&mut keyboard, (1, 0), now);
+        assert_eq!(keyboard.view().unwrap().cursor, (0, 1));
+    }
+
+    #[test]
+    fn held_direction_repeats() {
+        let mut keyboard = keyboard();
+        let start = Instant::now();
+        keyboard.axis(GamepadControl::DPadX, 1, start);
+        let moves = (0..=100)
+            .filter(|step| keyboard.tick(start + Duration::from_millis(step * 5)))
+            .count();
+        // The press moved once, then the repeat starts at 400 ms and the
+        // next would only come at 520
+        assert_eq!(moves, 1);
+        assert_eq!(keyboard.view().unwrap().cursor, (0, 2));
+    }
+}
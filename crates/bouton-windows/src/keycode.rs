@@ -147,11 +147,17 @@ pub enum KeyCode {
     ScrollLock,
 
     // Shift/Ctrl/Alt variants
+    #[serde(alias = "LSHIFT")]
     LShift,
+    #[serde(alias = "RSHIFT")]
     RShift,
+    #[serde(alias = "LCTRL")]
     LControl,
+    #[serde(alias = "RCTRL")]
     RControl,
+    #[serde(alias = "LALT")]
     LAlt,
+    #[serde(alias = "RALT")]
     RAlt,
 
     // Browser keys
@@ -509,3 +515,91 @@ impl KeyCode {
         }
     }
 }
+
+/// Whether a raw virtual key code is Shift, Ctrl, Alt or Win, either side.
+pub fn is_modifier(code: u32) -> bool {
+    matches!(code, 0x10..=0x12 | 0x5B | 0x5C | 0xA0..=0xA5)
+}
+
+/// Display name for a raw virtual key code, used in logs and the TUI.
+pub fn code_to_name(code: u32) -> String {
+    match code {
+        0x01 => "LButton".to_string(),
+        0x02 => "RButton".to_string(),
+        0x04 => "MButton".to_string(),
+        0x05 => "XButton1".to_string(),
+        0x06 => "XButton2".to_string(),
+        0x08 => "Backspace".to_string(),
+        0x09 => "Tab".to_string(),
+        0x0C => "Clear".to_string(),
+        0x0D => "Enter".to_string(),
+        0x10 => "Shift".to_string(),
+        0x11 => "Ctrl".to_string(),
+        0x12 => "Alt".to_string(),
+        0x13 => "Pause".to_string(),
+        0x14 => "CapsLock".to_string(),
+        0x1B => "Esc".to_string(),
+        0x20 => "Space".to_string(),
+        0x21 => "PageUp".to_string(),
+        0x22 => "PageDown".to_string(),
+        0x23 => "End".to_string(),
+        0x24 => "Home".to_string(),
+        0x25 => "Left".to_string(),
+        0x26 => "Up".to_string(),
+        0x27 => "Right".to_string(),
+        0x28 => "Down".to_string(),
+        0x2C => "PrintScreen".to_string(),
+        0x2D => "Insert".to_string(),
+        0x2E => "Delete".to_string(),
+        0x30..=0x39 => format!("{}", code - 0x30),
+        0x41..=0x5A => format!("{}", (code as u8 as char)),
+        0x60..=0x69 => format!("Numpad{}", code - 0x60),
+        0x70..=0x87 => format!("F{}", code - 0x70 + 1),
+        0x90 => "NumLock".to_string(),
+        0x91 => "ScrollLock".to_string(),
+        0xA0 => "LShift".to_string(),
+        0xA1 => "RShift".to_string(),
+        0xA2 => "LCtrl".to_string(),
+        0xA3 => "RCtrl".to_string(),
+        0xA4 => "LAlt".to_string(),
+        0xA5 => "RAlt".to_string(),
+        0xAD => "VolumeMute".to_string(),
+        0xAE => "VolumeDown".to_string(),
+        0xAF => "VolumeUp".to_string(),
+        0xB0 => "MediaNextTrack".to_string(),
+        0xB1 => "MediaPrevTrack".to_string(),
+        0xB2 => "MediaStop".to_string(),
+        0xB3 => "MediaPlayPause".to_string(),
+        0xBA => ";".to_string(),
+        0xBB => "=".to_string(),
+        0xBC => ",".to_string(),
+        0xBD => "-".to_string(),
+        0xBE => ".".to_string(),
+        0xBF => "/".to_string(),
+        0xC0 => "`".to_string(),
+        0xDB => "[".to_string(),
+        0xDC => "\\".to_string(),
+        0xDD => "]".to_string(),
+        0xDE => "'".to_string(),
+        _ => format!("Unknown(0x{:02X})", code),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modifier_codes_have_their_key_names() {
+        for key in [
+            KeyCode::LShift,
+            KeyCode::RShift,
+            KeyCode::LControl,
+            KeyCode::RControl,
+            KeyCode::LAlt,
+            KeyCode::RAlt,
+        ] {
+            assert_eq!(code_to_name(key.code()), key.name());
+        }
+    }
+}
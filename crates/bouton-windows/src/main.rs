@@ -1,7 +1,26 @@
+mod analog;
+mod chords;
 mod config;
+mod directions;
+mod filters;
+mod flick;
+mod gestures;
+mod gyro;
 mod key_injector;
+mod keyboard;
+mod morse;
 mod keycode;
+mod motion;
+mod motion_inputs;
+mod output;
+mod pwm;
+mod scanning;
+mod smoothing;
+mod socd;
 mod socket_server;
+#[cfg(test)]
+mod testing;
+mod trackpad;
 mod ui;
 
 use config::Config;
@@ -10,7 +29,7 @@ use std::env;
 use std::path::PathBuf;
 use std::collections::HashMap;
 use bouton_core::control::GamepadControl;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
 use crossterm::{
     event::{self, Event, KeyCode},
     execute,
@@ -52,12 +71,133 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     };
 
+    let bindings = bindings(&config);
+
+    let addr = format!("{}:{}", config.server.listen_addr, config.server.listen_port);
+    let addr: std::net::SocketAddr = addr.parse()?;
+    
+    // Create UI event channel
+    let (ui_tx, mut ui_rx) = mpsc::unbounded_channel::<UIEvent>();
+    
+    let server = SocketServer::bind(addr, bindings, ui_tx).await?;
+    
+    // Setup terminal for TUI
+    enable_raw_mode()?;
+    let mut stdout = std::io::stdout();
+    execute!(stdout, EnterAlternateScreen)?;
+    let backend = CrosstermBackend::new(stdout);
+    let mut terminal = Terminal::new(backend)?;
+    
+    // Initialize UI state
+    let mut ui_state = ui::KeyInjectionState::new();
+    
+    // Spawn server task
+    let (shutdown_tx, shutdown_rx) = oneshot::channel();
+    let server_handle = tokio::spawn(server.run(shutdown_rx));
+    
+    // Main TUI loop
+    loop {
+        // Check for keyboard input (non-blocking)
+        if event::poll(std::time::Duration::from_millis(100))?
+            && let Event::Key(key) = event::read()?
+            && (key.code == KeyCode::Char('q') || key.code == KeyCode::Esc)
+        {
+            break;
+        }
+        
+        // Process any pending UI events from the server
+         while let Ok(ui_event) = ui_rx.try_recv() {
+             match ui_event {
+                 UIEvent::ClientConnected(addr) => {
+                     ui_state.log_client_connected(addr);
+                 }
+                 UIEvent::KeyPressed(key_name, key_code) => {
+                     ui_state.log_key_injection(key_name, "pressed".to_string(), key_code);
+                 }
+                 UIEvent::KeyReleased(key_name, key_code) => {
+                     ui_state.log_key_injection(key_name, "released".to_string(), key_code);
+                 }
+                 UIEvent::KeyLatched(key_name, key_code) => {
+                     ui_state.log_latched(key_name, key_code);
+                 }
+                 UIEvent::KeyUnlatched(key_name, key_code) => {
+                     ui_state.log_unlatched(key_name, key_code);
+                 }
+                 UIEvent::Typed(text) => {
+                     ui_state.add_log(format!("Typed {:?}", text));
+                 }
+                 UIEvent::Mode(mode, on) => {
+                     ui_state.add_log(format!("{}: {}", mode, if on { "on" } else { "off" }));
+                 }
+                 UIEvent::Keyboard(view) => {
+                     ui_state.keyboard = view;
+                 }
+                 UIEvent::Morse(view) => {
+                     ui_state.morse = Some(view);
+                 }
+                 UIEvent::ScanItems(labels, columns) => {
+                     ui_state.set_scan_items(labels, columns);
+                 }
+                 UIEvent::ScanHighlight(highlight) => {
+                     ui_state.scan_highlight = highlight;
+                 }
+                 UIEvent::ModifierSticky(key_name, key_code, sticky) => {
+                     ui_state.set_sticky(key_name, key_code, sticky);
+                 }
+                 UIEvent::KeyHolders(key_name, key_code, holders) => {
+                     ui_state.set_holders(key_name, key_code, holders);
+                 }
+                 UIEvent::MouseButton(button, action) => {
+                     let action = match action {
+                         bouton_core::KeyAction::Press => "pressed",
+                         bouton_core::KeyAction::Release => "released",
+                     };
+                     ui_state.add_log(format!("Mouse {:?}: {}", button, action));
+                 }
+                 UIEvent::RadialSector(stick, selected, sectors) => {
+                     ui_state.set_radial(stick, selected, sectors);
+                 }
+                 UIEvent::Unbound(control) => {
+                     ui_state.log_unbound(control);
+                 }
+                 UIEvent::Error(msg) => {
+                     ui_state.add_log(format!("✗ {}", msg));
+                 }
+             }
+         }
+        
+        // Render the UI
+        terminal.draw(|f| {
+            ui::draw(f, &ui_state);
+        })?;
+        
+        // Check if server task has finished (shouldn't happen unless error)
+        if server_handle.is_finished() {
+            break;
+        }
+    }
+    
+    // Let the server release any held or latched keys before exiting
+    let _ = shutdown_tx.send(());
+    let _ = server_handle.await;
+
+    // Cleanup terminal
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+    
+    Ok(())
+}
+
+/// Resolves the config into the bindings the server maps with, skipping
+/// entries that don't make sense with a message.
+fn bindings(config: &Config) -> config::Bindings {
     // Build button code to key code mapping
-    let button_map: HashMap<GamepadControl, u32> = config
+    let button_map: HashMap<GamepadControl, config::ButtonCodeConfig> = config
         .keys
         .buttons
         .iter()
-        .filter_map(|(button_name, key_code_enum)| {
+        .filter_map(|(button_name, button_config)| {
             let control = match button_name.as_str() {
                 "Square" => Some(GamepadControl::Square),
                 "Cross" => Some(GamepadControl::Cross),
@@ -76,7 +216,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 "Aux2" => Some(GamepadControl::Aux2),
                 _ => None,
             };
-            control.map(|c| (c, key_code_enum.code()))
+            control.map(|c| (
+                c,
+                config::ButtonCodeConfig {
+                    action: button_config.action(),
+                    toggle: button_config.toggle(),
+                    repeat: button_config.repeat(),
+                }
+            ))
         })
         .collect();
 
@@ -91,15 +238,29 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             _ => None,
         };
         
+        let pwm = match stick_config.pwm_codes() {
+            Ok(pwm) => pwm,
+            Err(e) => {
+                eprintln!("Joystick {}: {}, skipping", stick_name, e);
+                continue;
+            }
+        };
+
         if let Some(control) = control {
             joystick_map.insert(
                 control,
                 config::JoystickCodeConfig {
-                    deadzone: stick_config.deadzone.unwrap_or(20),
+                    pipeline: stick_config.pipeline(),
                     up: stick_config.up.code(),
                     down: stick_config.down.code(),
                     left: stick_config.left.code(),
                     right: stick_config.right.code(),
+                    toggle: stick_config.toggle,
+                    eight_way: stick_config.eight_way.codes(),
+                    run: stick_config.run_codes(),
+                    pwm,
+                    repeat: stick_config.repeat.as_ref().map(config::RepeatConfig::repeat),
+                    min_hold: config::millis(stick_config.min_hold_ms),
                 },
             );
         }
@@ -118,12 +279,29 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 "R2" => Some(GamepadControl::R2),
                 _ => None,
             };
+
+            let stages = trigger_config.stages();
+            if stages.is_empty() {
+                eprintln!("Trigger {} has no key or stages, skipping", trigger_name);
+                return None;
+            }
+            let pwm = match trigger_config.pwm_codes() {
+                Ok(pwm) => pwm,
+                Err(e) => {
+                    eprintln!("Trigger {}: {}, skipping", trigger_name, e);
+                    return None;
+                }
+            };
             
             control.map(|c| (
                 c,
                 config::TriggerCodeConfig {
-                    key: trigger_config.key.code(),
-                    deadzone: trigger_config.deadzone.unwrap_or(127),
+                    stages,
+                    pipeline: trigger_config.pipeline(),
+                    toggle: trigger_config.toggle,
+                    pwm,
+                    repeat: trigger_config.repeat.as_ref().map(config::RepeatConfig::repeat),
+                    min_hold: config::millis(trigger_config.min_hold_ms),
                 }
             ))
         })
@@ -131,6 +309,150 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     println!("Mapped {} triggers from config", trigger_map.len());
 
+    // Build radial menu configs
+    let mut radial_map: HashMap<GamepadControl, config::RadialCodeConfig> = HashMap::new();
+    for (stick_name, radial_config) in config.keys.radial.iter() {
+        let control = match stick_name.as_str() {
+            "LeftStick" => Some(GamepadControl::LeftStickX),
+            "RightStick" => Some(GamepadControl::RightStickX),
+            _ => None,
+        };
+
+        if !config::RADIAL_SECTORS.contains(&radial_config.sectors.len()) {
+            eprintln!(
+                "Radial menu {} needs 4 to 12 sectors, skipping",
+                stick_name
+            );
+            continue;
+        }
+
+        if let Some(control) = control {
+            radial_map.insert(
+                control,
+                config::RadialCodeConfig {
+                    pipeline: radial_config.pipeline(),
+                    sectors: radial_config.sectors.iter().map(|key| key.code()).collect(),
+                    fire: radial_config.fire,
+                    confirm: radial_config.confirm,
+                },
+            );
+        }
+    }
+
+    if !radial_map.is_empty() {
+        println!("Mapped {} radial menus from config", radial_map.len());
+    }
+
+    // Build flick stick configs
+    let mut flick_map: HashMap<GamepadControl, config::FlickStickCodeConfig> = HashMap::new();
+    for (stick_name, flick_config) in config.keys.flick_stick.iter() {
+        let control = match stick_name.as_str() {
+            "LeftStick" => Some(GamepadControl::LeftStickX),
+            "RightStick" => Some(GamepadControl::RightStickX),
+            _ => None,
+        };
+
+        if let Some(control) = control {
+            flick_map.insert(control, flick_config.codes());
+        }
+    }
+
+    if !flick_map.is_empty() {
+        println!("Mapped {} flick sticks from config", flick_map.len());
+    }
+
+    // Build motion-input patterns
+    let mut motion_inputs = Vec::new();
+    for motion_input in &config.motion_inputs {
+        match motion_input.codes() {
+            Ok(codes) => motion_inputs.push(codes),
+            Err(e) => eprintln!(
+                "Motion input {} on {}: {}, skipping",
+                motion_input.pattern, motion_input.source, e
+            ),
+        }
+    }
+
+    if !motion_inputs.is_empty() {
+        println!("Watching for {} motion inputs", motion_inputs.len());
+    }
+
+    // Build axis smoothing, sticks covering both of their axes
+    let mut smoothing_map: HashMap<GamepadControl, smoothing::Smoothing> = HashMap::new();
+    for (name, smoothing_config) in config.smoothing.iter() {
+        let controls: &[GamepadControl] = match name.as_str() {
+            "LeftStick" => &[GamepadControl::LeftStickX, GamepadControl::LeftStickY],
+            "RightStick" => &[GamepadControl::RightStickX, GamepadControl::RightStickY],
+            "L2" => &[GamepadControl::L2],
+            "R2" => &[GamepadControl::R2],
+            _ => {
+                eprintln!("Smoothing for unknown stick or trigger {}, skipping", name);
+                continue;
+            }
+        };
+        for &control in controls {
+            smoothing_map.insert(control, smoothing_config.smoothing());
+        }
+    }
+
+    if !smoothing_map.is_empty() {
+        println!("Smoothing {} axes", smoothing_map.len());
+    }
+
+    // Build button filters
+    let filters = config.filters.codes().unwrap_or_else(|e| {
+        eprintln!("Filters: {}, skipping", e);
+        config::FiltersCodeConfig::default()
+    });
+
+    // Build Morse entry
+    let morse = config.morse.as_ref().and_then(|morse| match morse.codes() {
+        Ok(codes) => Some(codes),
+        Err(e) => {
+            eprintln!("Morse: {}, skipping", e);
+            None
+        }
+    });
+
+    if let Some(morse) = &morse {
+        println!("Morse entry on {}", morse.button);
+    }
+
+    // Build the on-screen keyboard
+    let keyboard = config.keyboard.as_ref().and_then(|keyboard| match keyboard.codes() {
+        Ok(codes) => Some(codes),
+        Err(e) => {
+            eprintln!("Keyboard: {}, skipping", e);
+            None
+        }
+    });
+
+    // Build chord entry
+    let chords = config.chords.as_ref().and_then(|chords| match chords.codes() {
+        Ok(codes) => Some(codes),
+        Err(e) => {
+            eprintln!("Chords: {}, skipping", e);
+            None
+        }
+    });
+
+    if let Some(chords) = &chords {
+        println!("Chord entry with {} chords", chords.table.len());
+    }
+
+    // Build switch scanning
+    let scanning = config.scanning.as_ref().and_then(|scanning| match scanning.codes() {
+        Ok(codes) => Some(codes),
+        Err(e) => {
+            eprintln!("Scanning: {}, skipping", e);
+            None
+        }
+    });
+
+    if let Some(scanning) = &scanning {
+        println!("Scanning {} items", scanning.items.len());
+    }
+
     // Build D-Pad config
     let dpad_config: Option<config::DPadCodeConfig> = config
         .keys
@@ -141,80 +463,61 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             down: dpad.down.code(),
             left: dpad.left.code(),
             right: dpad.right.code(),
+            toggle: dpad.toggle,
+            eight_way: dpad.eight_way.codes(),
+            repeat: dpad.repeat.as_ref().map(config::RepeatConfig::repeat),
         });
 
     if dpad_config.is_some() {
         println!("Mapped D-Pad from config");
     }
 
-    let addr = format!("{}:{}", config.server.listen_addr, config.server.listen_port);
-    let addr: std::net::SocketAddr = addr.parse()?;
-    
-    // Create UI event channel
-    let (ui_tx, mut ui_rx) = mpsc::unbounded_channel::<UIEvent>();
-    
-    let server = SocketServer::bind(addr, button_map, joystick_map, trigger_map, dpad_config, ui_tx).await?;
-    
-    // Setup terminal for TUI
-    enable_raw_mode()?;
-    let mut stdout = std::io::stdout();
-    execute!(stdout, EnterAlternateScreen)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
-    
-    // Initialize UI state
-    let mut ui_state = ui::KeyInjectionState::new();
-    
-    // Spawn server task
-    let server_handle = tokio::spawn(server.run());
-    
-    // Main TUI loop
-    loop {
-        // Check for keyboard input (non-blocking)
-        if event::poll(std::time::Duration::from_millis(100))? {
-            if let Event::Key(key) = event::read()? {
-                if key.code == KeyCode::Char('q') || key.code == KeyCode::Esc {
-                    break;
-                }
-            }
-        }
-        
-        // Process any pending UI events from the server
-         while let Ok(ui_event) = ui_rx.try_recv() {
-             match ui_event {
-                 UIEvent::ClientConnected(addr) => {
-                     ui_state.log_client_connected(addr);
-                 }
-                 UIEvent::KeyPressed(key_name, key_code) => {
-                     ui_state.log_key_injection(key_name, "pressed".to_string(), key_code);
-                 }
-                 UIEvent::KeyReleased(key_name, key_code) => {
-                     ui_state.log_key_injection(key_name, "released".to_string(), key_code);
-                 }
-                 UIEvent::Unbound(control) => {
-                     ui_state.log_unbound(control);
-                 }
-                 UIEvent::Error(msg) => {
-                     ui_state.add_log(format!("✗ {}", msg));
-                 }
-             }
-         }
-        
-        // Render the UI
-        terminal.draw(|f| {
-            ui::draw(f, &ui_state);
-        })?;
-        
-        // Check if server task has finished (shouldn't happen unless error)
-        if server_handle.is_finished() {
-            break;
+    let bindings = config::Bindings {
+        buttons: button_map,
+        joysticks: joystick_map,
+        triggers: trigger_map,
+        dpad: dpad_config,
+        radial: radial_map,
+        flick_sticks: flick_map,
+        touchpad: config.keys.touchpad.as_ref().map(config::TouchpadConfig::codes),
+        gyro: config.keys.gyro.as_ref().map(config::GyroConfig::codes),
+        motion: config.keys.motion.as_ref().map(config::MotionConfig::codes),
+        socd: config.socd.iter().map(config::SocdConfig::pair).collect(),
+        motion_inputs,
+        filters,
+        smoothing: smoothing_map,
+        sticky_modifiers: config.keys.sticky_modifiers,
+        scanning,
+        keyboard,
+        chords,
+        morse,
+    };
+
+    if bindings.sticky_modifiers {
+        println!("Sticky modifiers on");
+    }
+
+    if !bindings.socd.is_empty() {
+        println!("Cleaning {} SOCD key pairs", bindings.socd.len());
+    }
+
+    match bindings.touchpad.as_ref().map(|touchpad| touchpad.mode) {
+        Some(config::TouchpadMode::Trackpad) => println!("Using the touchpad as a trackpad"),
+        Some(config::TouchpadMode::Gestures) => println!("Using touchpad gestures"),
+        None => {}
+    }
+    if let Some(gyro) = &bindings.gyro {
+        match gyro.button {
+            Some(button) => println!("Using gyro aiming ({:?} on {})", gyro.button_mode, button),
+            None => println!("Using gyro aiming"),
         }
     }
-    
-    // Cleanup terminal
-    disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
-    terminal.show_cursor()?;
-    
-    Ok(())
+    if bindings.motion.is_some() {
+        println!("Using motion gestures");
+    }
+    if let Some(keyboard) = &bindings.keyboard {
+        println!("On-screen keyboard on {}", keyboard.toggle);
+    }
+
+    bindings
 }
@@ -0,0 +1,326 @@
+use crate::config::MorseCodeConfig;
+use bouton_core::KeyAction;
+use bouton_core::control::GamepadControl;
+use std::time::{Duration, Instant};
+
+/// How many decoded characters the TUI shows
+const SHOWN_TEXT: usize = 32;
+/// How far one press moves the adaptive timing towards the user's speed
+const ADAPT_RATE: f32 = 0.3;
+
+/// International Morse code, with the error prosign as backspace and AA as
+/// a new line.
+const MORSE_CODE: [(&str, &str); 56] = [
+    (".-", "a"),
+    ("-...", "b"),
+    ("-.-.", "c"),
+    ("-..", "d"),
+    (".", "e"),
+    ("..-.", "f"),
+    ("--.", "g"),
+    ("....", "h"),
+    ("..", "i"),
+    (".---", "j"),
+    ("-.-", "k"),
+    (".-..", "l"),
+    ("--", "m"),
+    ("-.", "n"),
+    ("---", "o"),
+    (".--.", "p"),
+    ("--.-", "q"),
+    (".-.", "r"),
+    ("...", "s"),
+    ("-", "t"),
+    ("..-", "u"),
+    ("...-", "v"),
+    (".--", "w"),
+    ("-..-", "x"),
+    ("-.--", "y"),
+    ("--..", "z"),
+    ("-----", "0"),
+    (".----", "1"),
+    ("..---", "2"),
+    ("...--", "3"),
+    ("....-", "4"),
+    (".....", "5"),
+    ("-....", "6"),
+    ("--...", "7"),
+    ("---..", "8"),
+    ("----.", "9"),
+    (".-.-.-", "."),
+    ("--..--", ","),
+    ("..--..", "?"),
+    (".----.", "'"),
+    ("-.-.--", "!"),
+    ("-..-.", "/"),
+    ("-.--.", "("),
+    ("-.--.-", ")"),
+    (".-...", "&"),
+    ("---...", ":"),
+    ("-.-.-.", ";"),
+    ("-...-", "="),
+    (".-.-.", "+"),
+    ("-....-", "-"),
+    ("..--.-", "_"),
+    (".-..-.", "\""),
+    ("...-..-", "$"),
+    (".--.-.", "@"),
+    ("........", "\u{8}"),
+    (".-.-", "\n"),
+];
+
+/// What a finished letter or word gap types.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MorseOutput {
+    Text(String),
+    /// A pattern that isn't in the code
+    Unknown(String),
+}
+
+/// What the TUI shows of Morse entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MorseView {
+    /// Dots and dashes of the letter being entered
+    pub pattern: String,
+    /// The last characters typed
+    pub text: String,
+    /// Current dash threshold, which moves with adaptive timing
+    pub dash: Duration,
+}
+
+/// Morse entry on one button. A press shorter than the dash threshold is a
+/// dot, a longer one a dash. Letting the button rest for the letter gap
+/// ends the letter, and resting for the word gap after that types a space.
+/// With adaptive timing every press moves the threshold and both gaps
+/// towards the user's own speed.
+pub struct MorseDecoder {
+    config: MorseCodeConfig,
+    pressed_at: Option<Instant>,
+    released_at: Option<Instant>,
+    pattern: String,
+    /// A letter has been typed since the last space
+    in_word: bool,
+    text: String,
+    /// Speed relative to the configured timing, above 1 when slower
+    scale: f32,
+}
+
+impl MorseDecoder {
+    pub fn new(config: MorseCodeConfig) -> Self {
+        Self {
+            config,
+            pressed_at: None,
+            released_at: None,
+            pattern: String::new(),
+            in_word: false,
+            text: String::new(),
+            scale: 1.0,
+        }
+    }
+
+    pub fn view(&self) -> MorseView {
+        MorseView {
+            pattern: self.pattern.clone(),
+            text: self.text.clone(),
+            dash: self.scaled(self.config.dash),
+        }
+    }
+
+    pub fn watches(&self, control: GamepadControl) -> bool {
+        control == self.config.button
+    }
+
+    /// Takes a press or release of the Morse button. Returns true when the
+    /// pattern changed.
+    pub fn button(&mut self, action: KeyAction, now: Instant) -> bool {
+        match action {
+            KeyAction::Press => {
+                self.pressed_at = Some(now);
+                false
+            }
+            KeyAction::Release => {
+                let Some(pressed_at) = self.pressed_at.take() else {
+                    return false;
+                };
+                let held = now.duration_since(pressed_at);
+                let dash = held >= self.scaled(self.config.dash);
+                self.pattern.push(if dash { '-' } else { '.' });
+                self.released_at = Some(now);
+                if self.config.adaptive {
+                    self.adapt(held, dash);
+                }
+                true
+            }
+        }
+    }
+
+    /// Ends the letter or the word once the button has rested long enough.
+    pub fn tick(&mut self, now: Instant) -> Option<MorseOutput> {
+        if self.pressed_at.is_some() {
+            return None;
+        }
+        let rest = now.duration_since(self.released_at?);
+
+        if !self.pattern.is_empty() {
+            if rest < self.scaled(self.config.letter_gap) {
+                return None;
+            }
+            let pattern = std::mem::take(&mut self.pattern);
+            let Some(&(_, text)) = MORSE_CODE.iter().find(|&&(code, _)| code == pattern) else {
+                return Some(MorseOutput::Unknown(pattern));
+            };
+            // No space after a line break or a backspace
+            self.in_word = text.chars().all(|c| !c.is_whitespace() && !c.is_control());
+            self.show(text);
+            return Some(MorseOutput::Text(text.to_string()));
+        }
+
+        if self.in_word && rest >= self.scaled(self.config.word_gap) {
+            self.in_word = false;
+            self.show(" ");
+            return Some(MorseOutput::Text(" ".to_string()));
+        }
+        None
+    }
+
+    fn scaled(&self, duration: Duration) -> Duration {
+        duration.mul_f32(self.scale)
+    }
+
+    /// Moves the scale towards the one this press suggests: a dot is
+    /// nominally half the dash threshold and a dash one and a half times.
+    fn adapt(&mut self, held: Duration, dash: bool) {
+        let threshold = self.config.dash.as_secs_f32();
+        if threshold <= 0.0 {
+            return;
+        }
+        let nominal = if dash {
+            threshold * 1.5
+        } else {
+            threshold * 0.5
+        };
+        let scale = held.as_secs_f32() / nominal;
+        self.scale = (self.scale + ADAPT_RATE * (scale - self.scale)).clamp(0.5, 3.0);
+    }
+
+    fn show(&mut self, text: &str) {
+        for character in text.chars() {
+            match character {
+                '\u{8}' => {
+                    self.text.pop();
+                }
+                '\n' => self.text.push('⏎'),
+                _ => self.text.push(character),
+            }
+        }
+        let extra = self.text.chars().count().saturating_sub(SHOWN_TEXT);
+        self.text = self.text.chars().skip(extra).collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::MorseConfig;
+    use crate::testing::{self, Step};
+
+    fn decoder(options: &str) -> MorseDecoder {
+        let config: MorseConfig =
+            toml::from_str(&format!("button = \"Cross\"\n{}", options)).unwrap();
+        MorseDecoder::new(config.codes().unwrap())
+    }
+
+    /// Plays `(milliseconds, action)` button events and returns everything
+    /// typed.
+    fn play(decoder: &mut MorseDecoder, trace: &[(u64, KeyAction)], end: u64) -> Vec<MorseOutput> {
+        let mut typed = Vec::new();
+        testing::play(trace, end, |_, now, step| match step {
+            Step::Event(action) => {
+                decoder.button(action, now);
+            }
+            Step::Tick => typed.extend(decoder.tick(now)),
+        });
+        typed
+    }
+
+    /// Presses for each `(start, held)` in milliseconds.
+    fn presses(presses: &[(u64, u64)]) -> Vec<(u64, KeyAction)> {
+        presses
+            .iter()
+            .flat_map(|&(at, held)| [(at, KeyAction::Press), (at + held, KeyAction::Release)])
+            .collect()
+    }
+
+    fn text(typed: &[MorseOutput]) -> String {
+        typed
+            .iter()
+            .map(|output| match output {
+                MorseOutput::Text(text) => text.clone(),
+                MorseOutput::Unknown(pattern) => format!("[{}]", pattern),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn pauses_end_letters_and_words() {
+        let mut morse = decoder("");
+        // "hi" with a letter gap between, a word gap, then "g"
+        let trace = presses(&[
+            (0, 50),
+            (150, 50),
+            (300, 50),
+            (450, 50),
+            (1300, 50),
+            (1450, 50),
+            (4000, 400),
+            (4600, 400),
+            (5200, 50),
+        ]);
+        let typed = play(&mut morse, &trace, 8000);
+        assert_eq!(text(&typed), "hi g ");
+        assert_eq!(morse.view().text, "hi g ");
+
+        let mut unknown = decoder("");
+        let typed = play(
+            &mut unknown,
+            &presses(&[
+                (0, 400),
+                (600, 400),
+                (1200, 400),
+                (1800, 400),
+                (2400, 400),
+                (3000, 400),
+            ]),
+            4500,
+        );
+        assert_eq!(typed, vec![MorseOutput::Unknown("------".to_string())]);
+    }
+
+    #[test]
+    fn dash_threshold_is_adjustable() {
+        let mut slow = decoder("dash_ms = 500\nletter_gap_ms = 1500");
+        let typed = play(&mut slow, &presses(&[(0, 400), (600, 400)]), 2500);
+        assert_eq!(typed, vec![MorseOutput::Text("i".to_string())]);
+    }
+
+    #[test]
+    fn adaptive_timing_follows_a_slower_user() {
+        // "a" six times with 400 ms dots and 1200 ms dashes, so slow that
+        // every press is a dash at the default threshold
+        let slow_a: Vec<(u64, u64)> = (0..6)
+            .flat_map(|letter| {
+                let start = letter * 4800;
+                [(start, 400), (start + 800, 1200)]
+            })
+            .collect();
+
+        let mut fixed = decoder("");
+        let typed = text(&play(&mut fixed, &presses(&slow_a), 30000));
+        assert!(typed.starts_with("m m "), "{:?}", typed);
+
+        let mut adaptive = decoder("adaptive = true");
+        let typed = text(&play(&mut adaptive, &presses(&slow_a), 30000));
+        assert!(typed.ends_with("aaa"), "{:?}", typed);
+        assert!(adaptive.view().dash > Duration::from_millis(300));
+    }
+}
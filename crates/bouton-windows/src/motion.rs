@@ -0,0 +1,233 @@
+use crate::config::MotionCodeConfig;
+use crate::directions::wrap_degrees;
+use bouton_core::control::GamepadControl;
+use bouton_core::{ControlMotion, KeyAction};
+use std::time::Instant;
+
+/// Names of the twist directions, in the order of `MotionCodeConfig::twists`
+const TWISTS: [&str; 4] = ["left", "right", "up", "down"];
+
+/// Keys produced by one motion sample.
+#[derive(Debug, Default, PartialEq)]
+pub struct MotionKeys {
+    /// Tilt keys that should be held right now
+    pub tilt: Vec<u32>,
+    /// Keys to tap for shakes and twists, with a description of the gesture
+    pub taps: Vec<(String, u32)>,
+}
+
+/// Recognizes motion gestures. Tilt is measured from the direction of
+/// gravity in the accelerometer, relative to a calibrated neutral
+/// orientation, and holds a key while it passes `tilt_angle`. A shake is a
+/// jolt of acceleration beyond gravity and a twist a fast rotation; both tap
+/// a key and then wait out a cooldown.
+pub struct MotionGestures {
+    config: MotionCodeConfig,
+    /// Neutral `(roll, pitch)` in degrees, set from the next sample when
+    /// `None`
+    neutral: Option<(f32, f32)>,
+    tilted: [bool; 4],
+    last_shake: Option<Instant>,
+    last_twist: Option<Instant>,
+}
+
+impl MotionGestures {
+    pub fn new(config: MotionCodeConfig) -> Self {
+        Self {
+            config,
+            neutral: None,
+            tilted: [false; 4],
+            last_shake: None,
+            last_twist: None,
+        }
+    }
+
+    /// Tracks the calibrate button. Returns whether `control` is that button.
+    pub fn button(&mut self, control: GamepadControl, action: KeyAction) -> bool {
+        if self.config.calibrate != Some(control) {
+            return false;
+        }
+        if action == KeyAction::Press {
+            self.neutral = None;
+        }
+        true
+    }
+
+    pub fn motion(&mut self, motion: ControlMotion, now: Instant) -> MotionKeys {
+        let [x, y, z] = motion
+            .accel
+            .map(|value| value as f32 / self.config.accel_resolution);
+        let mut keys = MotionKeys::default();
+
+        // With the gamepad lying flat gravity reads along +y; tilting the
+        // right side down swings it towards -x and the front down towards +z
+        let orientation = ((-x).atan2(y.hypot(z)).to_degrees(), z.atan2(y).to_degrees());
+        let (neutral_roll, neutral_pitch) = *self.neutral.get_or_insert(orientation);
+        let roll = wrap_degrees(orientation.0 - neutral_roll);
+        let pitch = wrap_degrees(orientation.1 - neutral_pitch);
+
+        for (index, angle) in [-roll, roll, pitch, -pitch].into_iter().enumerate() {
+            let threshold = if self.tilted[index] {
+                self.config.tilt_release
+            } else {
+                self.config.tilt_angle
+            };
+            self.tilted[index] = angle > threshold;
+            if self.tilted[index]
+                && let Some(key) = self.config.tilts[index]
+            {
+                keys.tilt.push(key);
+            }
+        }
+
+        if let Some(key) = self.config.shake {
+            let jolt = (x.hypot(y).hypot(z) - 1.0).abs();
+            if jolt > self.config.shake_threshold
+                && cooled_down(self.last_shake, self.config.shake_cooldown, now)
+            {
+                self.last_shake = Some(now);
+                keys.taps.push(("Shake".to_string(), key));
+            }
+        }
+
+        // Positive yaw turns left and positive pitch tips the front up
+        let [pitch_rate, yaw_rate, _] = motion
+            .gyro
+            .map(|rate| rate as f32 / self.config.gyro_resolution);
+        let twist = if yaw_rate.abs() >= pitch_rate.abs() {
+            if yaw_rate > 0.0 { 0 } else { 1 }
+        } else if pitch_rate > 0.0 {
+            2
+        } else {
+            3
+        };
+        if yaw_rate.hypot(pitch_rate) > self.config.twist_speed
+            && cooled_down(self.last_twist, self.config.twist_cooldown, now)
+        {
+            // Even unbound directions start the cooldown, so the swing back
+            // after a twist doesn't fire the opposite one
+            self.last_twist = Some(now);
+            if let Some(key) = self.config.twists[twist] {
+                keys.taps.push((format!("Twist {}", TWISTS[twist]), key));
+            }
+        }
+
+        keys
+    }
+}
+
+fn cooled_down(last: Option<Instant>, cooldown: std::time::Duration, now: Instant) -> bool {
+    last.is_none_or(|last| now.duration_since(last) >= cooldown)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::MotionConfig;
+    use crate::keycode::KeyCode;
+    use std::time::Duration;
+
+    fn gestures() -> MotionGestures {
+        let config: MotionConfig = toml::from_str(
+            "calibrate = \"Select\"\n\
+             shake = { key = \"R\" }\n\
+             [tilt]\nleft = \"Q\"\nright = \"E\"\nforward = \"W\"\n\
+             [twist]\nleft = \"1\"\nright = \"2\"",
+        )
+        .unwrap();
+        MotionGestures::new(config.codes())
+    }
+
+    /// A still sample with the gamepad rolled right by `roll` degrees and
+    /// its front tipped down by `pitch` degrees.
+    fn tilted(roll: f32, pitch: f32) -> ControlMotion {
+        let (roll, pitch) = (roll.to_radians(), pitch.to_radians());
+        let gravity = [
+            -roll.sin(),
+            roll.cos() * pitch.cos(),
+            roll.cos() * pitch.sin(),
+        ];
+        ControlMotion {
+            gyro: [0, 0, 0],
+            accel: gravity.map(|g| (g * 8192.0) as i32),
+        }
+    }
+
+    #[test]
+    fn tilt_holds_keys_relative_to_neutral() {
+        let mut motion = gestures();
+        let now = Instant::now();
+        // Held at a slant, which becomes neutral
+        assert!(motion.motion(tilted(0.0, -40.0), now).tilt.is_empty());
+
+        let keys = motion.motion(tilted(35.0, -40.0), now);
+        assert_eq!(keys.tilt, vec![KeyCode::E.code()]);
+        // Within the hysteresis the key stays held
+        assert_eq!(
+            motion.motion(tilted(27.0, -40.0), now).tilt,
+            vec![KeyCode::E.code()]
+        );
+        assert!(motion.motion(tilted(20.0, -40.0), now).tilt.is_empty());
+
+        assert_eq!(
+            motion.motion(tilted(0.0, -5.0), now).tilt,
+            vec![KeyCode::W.code()]
+        );
+
+        // Recalibrating makes the current orientation neutral
+        motion.button(GamepadControl::Select, KeyAction::Press);
+        assert!(motion.motion(tilted(0.0, -5.0), now).tilt.is_empty());
+        assert_eq!(
+            motion.motion(tilted(-40.0, -5.0), now).tilt,
+            vec![KeyCode::Q.code()]
+        );
+    }
+
+    #[test]
+    fn shakes_and_twists_tap_with_cooldown() {
+        let mut motion = gestures();
+        let start = Instant::now();
+        motion.motion(tilted(0.0, 0.0), start);
+
+        let jolt = ControlMotion {
+            gyro: [0, 0, 0],
+            accel: [0, 8192 * 3, 0],
+        };
+        let keys = motion.motion(jolt, start);
+        assert_eq!(keys.taps, vec![("Shake".to_string(), KeyCode::R.code())]);
+        assert!(
+            motion
+                .motion(jolt, start + Duration::from_millis(100))
+                .taps
+                .is_empty()
+        );
+        assert_eq!(
+            motion
+                .motion(jolt, start + Duration::from_millis(600))
+                .taps
+                .len(),
+            1
+        );
+
+        let twist_right = ControlMotion {
+            gyro: [0, -500 * 1024, 0],
+            ..tilted(0.0, 0.0)
+        };
+        let keys = motion.motion(twist_right, start);
+        assert_eq!(
+            keys.taps,
+            vec![("Twist right".to_string(), KeyCode::Key2.code())]
+        );
+        // The swing back is inside the cooldown
+        let swing_back = ControlMotion {
+            gyro: [0, 500 * 1024, 0],
+            ..tilted(0.0, 0.0)
+        };
+        assert!(
+            motion
+                .motion(swing_back, start + Duration::from_millis(100))
+                .taps
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,365 @@
+use crate::config::MotionInputCodeConfig;
+use crate::directions::Direction8;
+use bouton_core::control::GamepadControl;
+use std::collections::VecDeque;
+use std::fmt;
+use std::time::Instant;
+
+/// Directions kept per recognizer, far more than any pattern needs
+const MAX_HISTORY: usize = 32;
+
+/// Numpad neutral
+const NEUTRAL: u8 = 5;
+
+/// A motion in numpad notation: 8 is up, 6 forward (right), 2 down and
+/// 4 back, with diagonals in the corners and 5 for neutral.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MotionPattern {
+    /// Directions entered in order, like `236`. With `charge` the first one
+    /// has to be held for the charge time first, written `[4]6`.
+    Sequence { charge: bool, directions: Vec<u8> },
+    /// All four cardinal directions within the window, written `360`
+    Rotation,
+}
+
+impl MotionPattern {
+    pub fn parse(notation: &str) -> Result<Self, String> {
+        if notation == "360" {
+            return Ok(MotionPattern::Rotation);
+        }
+
+        let (charge, rest) = match notation.strip_prefix('[') {
+            Some(rest) => {
+                let (held, rest) = rest
+                    .split_once(']')
+                    .ok_or_else(|| format!("unclosed charge in {}", notation))?;
+                if held.len() != 1 {
+                    return Err(format!("charge takes one direction in {}", notation));
+                }
+                (true, format!("{}{}", held, rest))
+            }
+            None => (false, notation.to_string()),
+        };
+
+        let directions = rest
+            .chars()
+            .map(|c| match c.to_digit(10) {
+                Some(digit @ 1..=9) => Ok(digit as u8),
+                _ => Err(format!("{:?} is not a numpad direction in {}", c, notation)),
+            })
+            .collect::<Result<Vec<u8>, String>>()?;
+        if directions.len() < 2 {
+            return Err(format!("{} needs at least two directions", notation));
+        }
+
+        Ok(MotionPattern::Sequence { charge, directions })
+    }
+
+    /// Rough measure of how specific the pattern is, so longer motions win
+    /// over the shorter ones they contain.
+    pub fn len(&self) -> usize {
+        match self {
+            MotionPattern::Sequence { directions, .. } => directions.len(),
+            MotionPattern::Rotation => 4,
+        }
+    }
+}
+
+impl fmt::Display for MotionPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MotionPattern::Sequence { charge, directions } => {
+                for (index, direction) in directions.iter().enumerate() {
+                    if *charge && index == 0 {
+                        write!(f, "[{}]", direction)?;
+                    } else {
+                        write!(f, "{}", direction)?;
+                    }
+                }
+                Ok(())
+            }
+            MotionPattern::Rotation => write!(f, "360"),
+        }
+    }
+}
+
+/// Watches the directions a stick or the D-Pad passes through and reports
+/// when they complete a pattern.
+///
+/// Matching is lenient: other directions may come between the ones in the
+/// pattern, so a slightly rough quarter circle still counts, and a charge
+/// in a cardinal direction also accepts the diagonals next to it.
+pub struct MotionInputRecognizer {
+    config: MotionInputCodeConfig,
+    position: (i32, i32),
+    /// Directions entered and when, oldest first
+    history: VecDeque<(u8, Instant)>,
+}
+
+impl MotionInputRecognizer {
+    pub fn new(config: MotionInputCodeConfig) -> Self {
+        let center = config.pipeline.center();
+        let position = match config.source {
+            GamepadControl::DPadX => (0, 0),
+            _ => (center, center),
+        };
+        Self {
+            config,
+            position,
+            history: VecDeque::new(),
+        }
+    }
+
+    pub fn config(&self) -> &MotionInputCodeConfig {
+        &self.config
+    }
+
+    /// Whether `control` is one of the axes this recognizer watches.
+    pub fn watches(&self, control: GamepadControl) -> bool {
+        let source = match control {
+            GamepadControl::LeftStickX | GamepadControl::LeftStickY => GamepadControl::LeftStickX,
+            GamepadControl::RightStickX | GamepadControl::RightStickY => {
+                GamepadControl::RightStickX
+            }
+            GamepadControl::DPadX | GamepadControl::DPadY => GamepadControl::DPadX,
+            _ => return false,
+        };
+        source == self.config.source
+    }
+
+    /// Feeds one axis value. Returns true when the pattern was just
+    /// completed.
+    pub fn axis(&mut self, control: GamepadControl, value: i32, now: Instant) -> bool {
+        if !self.watches(control) {
+            return false;
+        }
+
+        let is_x_axis = matches!(
+            control,
+            GamepadControl::LeftStickX | GamepadControl::RightStickX | GamepadControl::DPadX
+        );
+        // D-Pad axes arrive as -1, 0 or 1 wrapped into a byte
+        let value = match self.config.source {
+            GamepadControl::DPadX => value as u8 as i8 as i32,
+            _ => value,
+        };
+        if is_x_axis {
+            self.position.0 = value;
+        } else {
+            self.position.1 = value;
+        }
+
+        let direction = self.direction();
+        if self.history.back().map(|&(last, _)| last) == Some(direction) {
+            return false;
+        }
+        self.history.push_back((direction, now));
+        if self.history.len() > MAX_HISTORY {
+            self.history.pop_front();
+        }
+
+        self.matches(now)
+    }
+
+    /// Forgets the directions seen so far, after this or another pattern on
+    /// the same source fired.
+    pub fn clear(&mut self) {
+        self.history.clear();
+    }
+
+    fn direction(&self) -> u8 {
+        let (x, y) = self.position;
+        let direction = match self.config.source {
+            GamepadControl::DPadX => Direction8::from_dpad(x as i8, y as i8),
+            _ => {
+                let (out_x, out_y) = self.config.pipeline.process(x, y);
+                (out_x != 0.0 || out_y != 0.0).then(|| Direction8::from_vector(out_x, out_y, 45.0))
+            }
+        };
+        direction.map_or(NEUTRAL, Direction8::numpad)
+    }
+
+    fn matches(&self, now: Instant) -> bool {
+        let window_start = now.checked_sub(self.config.window);
+        let in_window =
+            |time: Instant| window_start.is_none_or(|window_start| time >= window_start);
+
+        match &self.config.pattern {
+            MotionPattern::Rotation => {
+                let mut seen = [false; 4];
+                for &(direction, time) in &self.history {
+                    if in_window(time)
+                        && let Some(index) = [8, 6, 2, 4].iter().position(|&d| d == direction)
+                    {
+                        seen[index] = true;
+                    }
+                }
+                seen.iter().all(|&seen| seen)
+            }
+            MotionPattern::Sequence { charge, directions } => {
+                let entries: Vec<(u8, Instant)> = self.history.iter().copied().collect();
+                let Some((&last, wanted)) = directions.split_last() else {
+                    return false;
+                };
+                if entries.last().map(|&(direction, _)| direction) != Some(last) {
+                    return false;
+                }
+
+                // Walk back through the history finding the earlier
+                // directions in reverse order
+                let mut index = entries.len() - 1;
+                for (position, &direction) in wanted.iter().enumerate().rev() {
+                    let is_charge = *charge && position == 0;
+                    loop {
+                        if index == 0 {
+                            return false;
+                        }
+                        index -= 1;
+                        let (entered, time) = entries[index];
+                        // Only the end of a charge has to be in the window
+                        let ended = entries[index + 1].1;
+                        if !in_window(if is_charge { ended } else { time }) {
+                            return false;
+                        }
+                        if is_charge && charges(direction, entered) {
+                            let mut start = index;
+                            while start > 0 && charges(direction, entries[start - 1].0) {
+                                start -= 1;
+                            }
+                            if ended.duration_since(entries[start].1) >= self.config.charge {
+                                break;
+                            }
+                        } else if !is_charge && entered == direction {
+                            break;
+                        }
+                    }
+                }
+                true
+            }
+        }
+    }
+}
+
+/// Whether holding `actual` charges `wanted`: the same direction, or for a
+/// cardinal direction either diagonal next to it.
+fn charges(wanted: u8, actual: u8) -> bool {
+    let neighbours: &[u8] = match wanted {
+        4 => &[1, 4, 7],
+        6 => &[3, 6, 9],
+        2 => &[1, 2, 3],
+        8 => &[7, 8, 9],
+        _ => return wanted == actual,
+    };
+    neighbours.contains(&actual)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::MotionInputConfig;
+    use crate::testing::{self, Step};
+    use std::time::Duration;
+
+    fn recognizer(source: &str, pattern: &str) -> MotionInputRecognizer {
+        let config: MotionInputConfig = toml::from_str(&format!(
+            "source = \"{}\"\npattern = \"{}\"\nkey = \"U\"",
+            source, pattern
+        ))
+        .unwrap();
+        MotionInputRecognizer::new(config.codes().unwrap())
+    }
+
+    /// Plays `(milliseconds, numpad direction)` D-Pad inputs and returns the
+    /// times at which the pattern completed.
+    fn play(recognizer: &mut MotionInputRecognizer, trace: &[(u64, u8)]) -> Vec<u64> {
+        let end = trace.last().map_or(0, |&(ms, _)| ms);
+        let mut fired = Vec::new();
+        testing::play(trace, end, |ms, now, step| {
+            let Step::Event(direction) = step else {
+                return;
+            };
+            let x = [-1, 0, 1][(direction as usize - 1) % 3];
+            let y = [1, 0, -1][(direction as usize - 1) / 3];
+            let completed = [(GamepadControl::DPadX, x), (GamepadControl::DPadY, y)]
+                .into_iter()
+                .fold(false, |completed, (control, value)| {
+                    recognizer.axis(control, value as u8 as i32, now) || completed
+                });
+            if completed {
+                fired.push(ms);
+                recognizer.clear();
+            }
+        });
+        fired
+    }
+
+    #[test]
+    fn parses_numpad_notation() {
+        assert_eq!(
+            MotionPattern::parse("236"),
+            Ok(MotionPattern::Sequence {
+                charge: false,
+                directions: vec![2, 3, 6],
+            })
+        );
+        assert_eq!(
+            MotionPattern::parse("[4]6"),
+            Ok(MotionPattern::Sequence {
+                charge: true,
+                directions: vec![4, 6],
+            })
+        );
+        assert_eq!(MotionPattern::parse("360"), Ok(MotionPattern::Rotation));
+        assert_eq!(MotionPattern::parse("[2]8").unwrap().to_string(), "[2]8");
+        assert!(MotionPattern::parse("206").is_err());
+        assert!(MotionPattern::parse("[4").is_err());
+        assert!(MotionPattern::parse("6").is_err());
+    }
+
+    #[test]
+    fn quarter_circle_within_the_window() {
+        let qcf = || recognizer("DPad", "236");
+        assert_eq!(
+            play(&mut qcf(), &[(0, 2), (50, 3), (100, 6), (200, 5)]),
+            vec![100]
+        );
+        // A rough motion with an extra direction still counts
+        assert_eq!(
+            play(&mut qcf(), &[(0, 2), (30, 1), (60, 3), (90, 6)]),
+            vec![90]
+        );
+        // Too slow
+        assert!(play(&mut qcf(), &[(0, 2), (400, 3), (700, 6)]).is_empty());
+        // Wrong order
+        assert!(play(&mut qcf(), &[(0, 6), (50, 3), (100, 2)]).is_empty());
+    }
+
+    #[test]
+    fn charge_needs_holding_back() {
+        let charge = || recognizer("DPad", "[4]6");
+        assert!(play(&mut charge(), &[(0, 4), (100, 6)]).is_empty());
+        // Down-back charges back too
+        assert_eq!(
+            play(&mut charge(), &[(0, 4), (300, 1), (600, 6)]),
+            vec![600]
+        );
+    }
+
+    #[test]
+    fn full_rotation_on_a_stick() {
+        let mut spin = recognizer("LeftStick", "360");
+        let start = Instant::now();
+        let positions = [(128, 0), (255, 128), (128, 255), (0, 128)];
+        let fired: Vec<usize> = positions
+            .iter()
+            .enumerate()
+            .filter(|&(step, &(x, y))| {
+                let now = start + Duration::from_millis(step as u64 * 50);
+                spin.axis(GamepadControl::LeftStickX, x, now)
+                    | spin.axis(GamepadControl::LeftStickY, y, now)
+            })
+            .map(|(step, _)| step)
+            .collect();
+        assert_eq!(fired, vec![3]);
+    }
+}
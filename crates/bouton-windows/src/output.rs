@@ -0,0 +1,591 @@
+use crate::key_injector::{InputBackend, KeyInjector, MouseButton, MouseEvent};
+use crate::keycode::{code_to_name, is_modifier};
+use crate::socd::{SocdPair, SocdResolver};
+use crate::socket_server::UIEvent;
+use bouton_core::KeyAction;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+/// Typematic repeat timing for a holder's keys.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Repeat {
+    pub delay: Duration,
+    pub interval: Duration,
+}
+
+/// Holder name for modifiers held down by sticky modifiers
+const STICKY_HOLDER: &str = "Sticky";
+
+/// A sticky modifier that is down without its binding being held.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StickyState {
+    /// Released again once a key or mouse button pressed after it is let go
+    Latched,
+    /// Stays down until the modifier is pressed again
+    Locked,
+}
+
+/// Final stage between the mapping handlers and the input backend.
+///
+/// Handlers report binding-level presses and releases here, each on behalf of
+/// a named holder (a button, a stick, a trigger...). A key goes down when its
+/// first holder presses it and only comes back up once the last holder lets
+/// go, so two controls sharing a key don't release it early. After that come
+/// SOCD cleaning and injection, with every injected key remembered so it can
+/// be force-released when the server shuts down. Mouse output skips all of
+/// that and goes straight to the backend.
+///
+/// `SendInput` never auto-repeats, so the key a holder with a `Repeat` pressed
+/// last is sent down again on `tick`, the way a keyboard repeats the last key
+/// held. Modifiers never repeat.
+///
+/// With sticky modifiers on, pressing a Shift, Ctrl, Alt or Win binding
+/// latches that modifier until the next other key or mouse button has been
+/// pressed and let go. Keys already down when it latched don't count. Pressing
+/// it again while latched locks it, and a third press lets it go.
+pub struct KeyOutput<B: InputBackend = KeyInjector> {
+    backend: B,
+    holders: HashMap<u32, BTreeSet<String>>,
+    latched: HashSet<(String, u32)>,
+    held: HashSet<u32>,
+    repeats: HashMap<String, Repeat>,
+    /// The non-modifier key each holder pressed last, while it is down
+    last_pressed: HashMap<String, u32>,
+    /// When each repeating key is next sent down again
+    repeat_at: HashMap<u32, Instant>,
+    sticky_modifiers: bool,
+    sticky: HashMap<u32, StickyState>,
+    /// Keys and mouse buttons that went down while a modifier was latched
+    pressed_while_latched: HashSet<u32>,
+    clicked_while_latched: HashSet<MouseButton>,
+    socd: SocdResolver,
+    ui_tx: mpsc::UnboundedSender<UIEvent>,
+}
+
+impl<B: InputBackend> KeyOutput<B> {
+    pub fn new(
+        backend: B,
+        socd_pairs: Vec<SocdPair>,
+        ui_tx: mpsc::UnboundedSender<UIEvent>,
+    ) -> Self {
+        Self {
+            backend,
+            holders: HashMap::new(),
+            latched: HashSet::new(),
+            held: HashSet::new(),
+            repeats: HashMap::new(),
+            last_pressed: HashMap::new(),
+            repeat_at: HashMap::new(),
+            sticky_modifiers: false,
+            sticky: HashMap::new(),
+            pressed_while_latched: HashSet::new(),
+            clicked_while_latched: HashSet::new(),
+            socd: SocdResolver::new(socd_pairs),
+            ui_tx,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    pub fn send(&mut self, holder: &str, key_code: u32, action: KeyAction, toggle: bool) {
+        if !toggle && self.sticky_modifiers && is_modifier(key_code) {
+            // The binding's release doesn't matter, only its presses
+            if action == KeyAction::Press {
+                self.press_sticky(key_code);
+            }
+            return;
+        }
+
+        if !toggle {
+            match action {
+                KeyAction::Press => self.hold(holder, key_code),
+                KeyAction::Release => self.unhold(holder, key_code),
+            }
+            return;
+        }
+
+        // Toggle bindings only react to presses: the first press latches the
+        // key down, the next one releases it.
+        if action == KeyAction::Release {
+            return;
+        }
+
+        let key_name = code_to_name(key_code);
+        if self.latched.remove(&(holder.to_string(), key_code)) {
+            self.unhold(holder, key_code);
+            let _ = self.ui_tx.send(UIEvent::KeyUnlatched(key_name, key_code));
+        } else {
+            self.latched.insert((holder.to_string(), key_code));
+            self.hold(holder, key_code);
+            let _ = self.ui_tx.send(UIEvent::KeyLatched(key_name, key_code));
+        }
+    }
+
+    /// Makes keys held by `holder` auto-repeat.
+    pub fn set_repeat(&mut self, holder: &str, repeat: Repeat) {
+        self.repeats.insert(holder.to_string(), repeat);
+    }
+
+    pub fn set_sticky_modifiers(&mut self, sticky_modifiers: bool) {
+        self.sticky_modifiers = sticky_modifiers;
+    }
+
+    /// Sends repeated key-downs for held keys whose repeat is due. The
+    /// delay counts from the first tick after the key went down.
+    pub fn tick(&mut self, now: Instant) {
+        let held: Vec<u32> = self.held.iter().copied().collect();
+        for key_code in held {
+            let repeat = self.holders.get(&key_code).and_then(|holders| {
+                holders
+                    .iter()
+                    .filter(|&holder| self.last_pressed.get(holder) == Some(&key_code))
+                    .find_map(|holder| self.repeats.get(holder).copied())
+            });
+            let Some(repeat) = repeat else {
+                self.repeat_at.remove(&key_code);
+                continue;
+            };
+
+            let next = *self.repeat_at.entry(key_code).or_insert(now + repeat.delay);
+            if now < next {
+                continue;
+            }
+            // Skip repeats missed while stalled instead of bursting them
+            let mut following = next + repeat.interval;
+            if following <= now {
+                following = now + repeat.interval;
+            }
+            self.repeat_at.insert(key_code, following);
+
+            if let Err(e) = self.backend.send_key(key_code, KeyAction::Press) {
+                let _ = self.ui_tx.send(UIEvent::Error(format!(
+                    "Failed to repeat {}: {}",
+                    code_to_name(key_code),
+                    e
+                )));
+            }
+        }
+    }
+
+    pub fn send_mouse(&mut self, event: MouseEvent) {
+        match self.backend.send_mouse(event) {
+            Err(e) => {
+                let _ = self
+                    .ui_tx
+                    .send(UIEvent::Error(format!("Failed to inject {:?}: {}", event, e)));
+            }
+            Ok(_) => {
+                if let MouseEvent::Button(button, action) = event {
+                    let _ = self.ui_tx.send(UIEvent::MouseButton(button, action));
+                    match action {
+                        KeyAction::Press if self.is_latched() => {
+                            self.clicked_while_latched.insert(button);
+                        }
+                        KeyAction::Press => {}
+                        KeyAction::Release => {
+                            if self.clicked_while_latched.remove(&button) {
+                                self.release_latched();
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Types text, which also lets go of latched sticky modifiers like a
+    /// key would.
+    pub fn send_text(&mut self, text: &str) {
+        match self.backend.send_text(text) {
+            Err(e) => {
+                let _ = self
+                    .ui_tx
+                    .send(UIEvent::Error(format!("Failed to type {:?}: {}", text, e)));
+            }
+            Ok(_) => {
+                let _ = self.ui_tx.send(UIEvent::Typed(text.to_string()));
+            }
+        }
+        self.release_latched();
+    }
+
+    /// Releases every key still held down, latched or not.
+    pub fn release_all(&mut self) {
+        for (_, key_code) in self.latched.drain() {
+            let _ = self
+                .ui_tx
+                .send(UIEvent::KeyUnlatched(code_to_name(key_code), key_code));
+        }
+        for (key_code, _) in self.sticky.drain() {
+            let _ = self.ui_tx.send(UIEvent::ModifierSticky(
+                code_to_name(key_code),
+                key_code,
+                None,
+            ));
+        }
+        for (key_code, _) in self.holders.drain() {
+            let _ = self.ui_tx.send(UIEvent::KeyHolders(
+                code_to_name(key_code),
+                key_code,
+                Vec::new(),
+            ));
+        }
+        self.pressed_while_latched.clear();
+        self.clicked_while_latched.clear();
+        self.socd.clear();
+        self.last_pressed.clear();
+        self.repeat_at.clear();
+
+        let held: Vec<u32> = self.held.iter().copied().collect();
+        for key_code in held {
+            self.inject(key_code, KeyAction::Release);
+        }
+    }
+
+    fn hold(&mut self, holder: &str, key_code: u32) {
+        let holders = self.holders.entry(key_code).or_default();
+        let first = holders.is_empty();
+        if !holders.insert(holder.to_string()) {
+            return;
+        }
+        self.report_holders(key_code);
+        if !is_modifier(key_code) {
+            self.last_pressed.insert(holder.to_string(), key_code);
+        }
+
+        if first {
+            if !is_modifier(key_code) && self.is_latched() {
+                self.pressed_while_latched.insert(key_code);
+            }
+            self.request(key_code, KeyAction::Press);
+        }
+    }
+
+    fn unhold(&mut self, holder: &str, key_code: u32) {
+        let Some(holders) = self.holders.get_mut(&key_code) else {
+            return;
+        };
+        if !holders.remove(holder) {
+            return;
+        }
+        if self.last_pressed.get(holder) == Some(&key_code) {
+            self.last_pressed.remove(holder);
+        }
+        let last = holders.is_empty();
+        if last {
+            self.holders.remove(&key_code);
+        }
+        self.report_holders(key_code);
+
+        if last {
+            self.request(key_code, KeyAction::Release);
+            if self.pressed_while_latched.remove(&key_code) {
+                self.release_latched();
+            }
+        }
+    }
+
+    /// Latches, locks or lets go of a sticky modifier as its binding is
+    /// pressed.
+    fn press_sticky(&mut self, key_code: u32) {
+        let state = match self.sticky.get(&key_code) {
+            None => Some(StickyState::Latched),
+            Some(StickyState::Latched) => Some(StickyState::Locked),
+            Some(StickyState::Locked) => None,
+        };
+        match state {
+            Some(state) => {
+                self.sticky.insert(key_code, state);
+                self.hold(STICKY_HOLDER, key_code);
+            }
+            None => {
+                self.sticky.remove(&key_code);
+                self.unhold(STICKY_HOLDER, key_code);
+            }
+        }
+        let _ = self.ui_tx.send(UIEvent::ModifierSticky(
+            code_to_name(key_code),
+            key_code,
+            state,
+        ));
+    }
+
+    fn is_latched(&self) -> bool {
+        self.sticky.values().any(|&state| state == StickyState::Latched)
+    }
+
+    /// Lets go of latched modifiers once the key they applied to is up.
+    fn release_latched(&mut self) {
+        self.pressed_while_latched.clear();
+        self.clicked_while_latched.clear();
+        let latched: Vec<u32> = self
+            .sticky
+            .iter()
+            .filter(|&(_, &state)| state == StickyState::Latched)
+            .map(|(&key_code, _)| key_code)
+            .collect();
+        for key_code in latched {
+            self.sticky.remove(&key_code);
+            self.unhold(STICKY_HOLDER, key_code);
+            let _ = self.ui_tx.send(UIEvent::ModifierSticky(
+                code_to_name(key_code),
+                key_code,
+                None,
+            ));
+        }
+    }
+
+    fn report_holders(&self, key_code: u32) {
+        let holders = self
+            .holders
+            .get(&key_code)
+            .map(|holders| holders.iter().cloned().collect())
+            .unwrap_or_default();
+        let _ = self
+            .ui_tx
+            .send(UIEvent::KeyHolders(code_to_name(key_code), key_code, holders));
+    }
+
+    /// Passes a key-level change through SOCD cleaning.
+    fn request(&mut self, key_code: u32, action: KeyAction) {
+        for (key_code, action) in self.socd.resolve(key_code, action) {
+            self.inject(key_code, action);
+        }
+    }
+
+    fn inject(&mut self, key_code: u32, action: KeyAction) {
+        let key_name = code_to_name(key_code);
+        match self.backend.send_key(key_code, action) {
+            Err(e) => {
+                let _ = self.ui_tx.send(UIEvent::Error(format!(
+                    "Failed to inject {}: {}",
+                    key_name, e
+                )));
+            }
+            Ok(_) => {
+                let ui_event = match action {
+                    KeyAction::Press => {
+                        self.held.insert(key_code);
+                        UIEvent::KeyPressed(key_name, key_code)
+                    }
+                    KeyAction::Release => {
+                        self.held.remove(&key_code);
+                        self.repeat_at.remove(&key_code);
+                        UIEvent::KeyReleased(key_name, key_code)
+                    }
+                };
+                let _ = self.ui_tx.send(ui_event);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Recorder;
+
+    const W: u32 = 0x57;
+
+    fn output() -> KeyOutput<Recorder> {
+        let (ui_tx, _ui_rx) = mpsc::unbounded_channel();
+        KeyOutput::new(Recorder::default(), Vec::new(), ui_tx)
+    }
+
+    #[test]
+    fn shared_key_released_by_last_holder() {
+        let mut output = output();
+        output.send("Triangle", W, KeyAction::Press, false);
+        output.send("Left Stick", W, KeyAction::Press, false);
+        output.send("Triangle", W, KeyAction::Release, false);
+        assert_eq!(output.backend.keys, vec![(W, KeyAction::Press)]);
+
+        output.send("Left Stick", W, KeyAction::Release, false);
+        assert_eq!(
+            output.backend.keys,
+            vec![(W, KeyAction::Press), (W, KeyAction::Release)]
+        );
+    }
+
+    #[test]
+    fn latched_holder_keeps_key_down() {
+        let mut output = output();
+        output.send("L1", W, KeyAction::Press, true);
+        output.send("Triangle", W, KeyAction::Press, false);
+        output.send("Triangle", W, KeyAction::Release, false);
+        assert_eq!(output.backend.keys, vec![(W, KeyAction::Press)]);
+
+        output.send("L1", W, KeyAction::Press, true);
+        assert_eq!(output.backend.keys.last(), Some(&(W, KeyAction::Release)));
+    }
+
+    #[test]
+    fn held_key_repeats_after_delay() {
+        let mut output = output();
+        output.set_repeat(
+            "D-Pad",
+            Repeat {
+                delay: Duration::from_millis(500),
+                interval: Duration::from_millis(50),
+            },
+        );
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+
+        output.send("D-Pad", W, KeyAction::Press, false);
+        output.send("Triangle", 0x41, KeyAction::Press, false);
+        for ms in (0..=600).step_by(5) {
+            output.tick(at(ms));
+        }
+        // The first press, then repeats at 500, 550 and 600; Triangle's key
+        // doesn't repeat
+        let presses = |output: &KeyOutput<Recorder>, key| {
+            output
+                .backend
+                .keys
+                .iter()
+                .filter(|&&event| event == (key, KeyAction::Press))
+                .count()
+        };
+        assert_eq!(presses(&output, W), 4);
+        assert_eq!(presses(&output, 0x41), 1);
+
+        output.send("D-Pad", W, KeyAction::Release, false);
+        output.tick(at(1000));
+        assert_eq!(output.backend.keys.last(), Some(&(W, KeyAction::Release)));
+    }
+
+    #[test]
+    fn only_the_last_key_pressed_repeats() {
+        const D: u32 = 0x44;
+        const SHIFT: u32 = 0xA0;
+        let mut output = output();
+        output.set_repeat(
+            "Left Stick",
+            Repeat {
+                delay: Duration::from_millis(500),
+                interval: Duration::from_millis(50),
+            },
+        );
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+
+        // A diagonal with the run key on top
+        output.send("Left Stick", W, KeyAction::Press, false);
+        output.send("Left Stick", D, KeyAction::Press, false);
+        output.send("Left Stick", SHIFT, KeyAction::Press, false);
+        for ms in (0..=600).step_by(5) {
+            output.tick(at(ms));
+        }
+        let presses = |output: &KeyOutput<Recorder>, key| {
+            output
+                .backend
+                .keys
+                .iter()
+                .filter(|&&event| event == (key, KeyAction::Press))
+                .count()
+        };
+        assert_eq!(presses(&output, W), 1);
+        assert_eq!(presses(&output, D), 4);
+        assert_eq!(presses(&output, SHIFT), 1);
+
+        // Letting go of it stops the repeat, like on a keyboard
+        output.send("Left Stick", D, KeyAction::Release, false);
+        for ms in (605..=1200).step_by(5) {
+            output.tick(at(ms));
+        }
+        assert_eq!(presses(&output, W), 1);
+    }
+
+    #[test]
+    fn sticky_modifier_applies_to_the_next_key() {
+        const SHIFT: u32 = 0xA0;
+        let mut output = output();
+        output.set_sticky_modifiers(true);
+        output.send("L1", SHIFT, KeyAction::Press, false);
+        output.send("L1", SHIFT, KeyAction::Release, false);
+        output.send("Triangle", W, KeyAction::Press, false);
+        output.send("Triangle", W, KeyAction::Release, false);
+        assert_eq!(
+            output.backend.keys,
+            vec![
+                (SHIFT, KeyAction::Press),
+                (W, KeyAction::Press),
+                (W, KeyAction::Release),
+                (SHIFT, KeyAction::Release),
+            ]
+        );
+
+        // Pressed twice it locks and stays down over several keys
+        output.backend.keys.clear();
+        for _ in 0..2 {
+            output.send("L1", SHIFT, KeyAction::Press, false);
+            output.send("L1", SHIFT, KeyAction::Release, false);
+        }
+        for _ in 0..2 {
+            output.send("Triangle", W, KeyAction::Press, false);
+            output.send("Triangle", W, KeyAction::Release, false);
+        }
+        assert!(!output.backend.keys.contains(&(SHIFT, KeyAction::Release)));
+
+        // A third press lets go of it
+        output.send("L1", SHIFT, KeyAction::Press, false);
+        assert_eq!(output.backend.keys.last(), Some(&(SHIFT, KeyAction::Release)));
+    }
+
+    #[test]
+    fn sticky_modifier_waits_for_a_key_pressed_after_it() {
+        const SHIFT: u32 = 0xA0;
+        const A: u32 = 0x41;
+        let mut output = output();
+        output.set_sticky_modifiers(true);
+        output.send("Left Stick", W, KeyAction::Press, false);
+        output.send("L1", SHIFT, KeyAction::Press, false);
+        output.send("L1", SHIFT, KeyAction::Release, false);
+        output.send("Left Stick", W, KeyAction::Release, false);
+        output.send_mouse(MouseEvent::Button(MouseButton::Left, KeyAction::Release));
+        assert!(!output.backend.keys.contains(&(SHIFT, KeyAction::Release)));
+
+        output.send("Triangle", A, KeyAction::Press, false);
+        output.send("Triangle", A, KeyAction::Release, false);
+        assert_eq!(output.backend.keys.last(), Some(&(SHIFT, KeyAction::Release)));
+
+        // The same goes for a click
+        output.send("L1", SHIFT, KeyAction::Press, false);
+        output.send_mouse(MouseEvent::Button(MouseButton::Left, KeyAction::Press));
+        output.send_mouse(MouseEvent::Button(MouseButton::Left, KeyAction::Release));
+        assert_eq!(output.backend.keys.last(), Some(&(SHIFT, KeyAction::Release)));
+    }
+
+    #[test]
+    fn text_goes_to_the_backend_and_ends_sticky_modifiers() {
+        const SHIFT: u32 = 0xA0;
+        let mut output = output();
+        output.set_sticky_modifiers(true);
+        output.send("L1", SHIFT, KeyAction::Press, false);
+        output.send("L1", SHIFT, KeyAction::Release, false);
+        output.send_text("me@example.com ");
+        output.send_text("👍");
+        assert_eq!(output.backend.text, "me@example.com 👍");
+        assert_eq!(
+            output.backend.keys,
+            vec![(SHIFT, KeyAction::Press), (SHIFT, KeyAction::Release)]
+        );
+    }
+
+    #[test]
+    fn release_all_clears_holders() {
+        let mut output = output();
+        output.send("Triangle", W, KeyAction::Press, false);
+        output.release_all();
+        assert_eq!(output.backend.keys.last(), Some(&(W, KeyAction::Release)));
+
+        // A stale release from the old holder must not inject anything
+        output.send("Triangle", W, KeyAction::Release, false);
+        assert_eq!(output.backend.keys.len(), 2);
+    }
+}
@@ -0,0 +1,67 @@
+use std::time::Duration;
+
+/// Pulses a key on and off so partial deflection gives partial speed in
+/// games that only know "key down".
+///
+/// Each `period` the key is held for `duty` of the time. Pulses and gaps are
+/// never shorter than `min_pulse`, since games polling once a frame would
+/// miss them: a shorter pulse is stretched and a shorter gap is closed up.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pwm {
+    pub period: Duration,
+    pub min_pulse: Duration,
+}
+
+impl Pwm {
+    /// Whether a key pulsed at `duty` (0 to 1) is down `elapsed` after its
+    /// pulse train started.
+    pub fn is_on(&self, duty: f32, elapsed: Duration) -> bool {
+        if duty <= 0.0 || self.period.is_zero() {
+            return false;
+        }
+
+        let period = self.period.as_secs_f32();
+        let min_pulse = self.min_pulse.as_secs_f32().min(period);
+        let mut on_time = duty.min(1.0) * period;
+        if on_time < min_pulse {
+            on_time = min_pulse;
+        }
+        if period - on_time < min_pulse {
+            return true;
+        }
+
+        elapsed.as_secs_f32() % period < on_time
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pwm() -> Pwm {
+        Pwm {
+            period: Duration::from_millis(100),
+            min_pulse: Duration::from_millis(20),
+        }
+    }
+
+    fn on_count(pwm: Pwm, duty: f32) -> usize {
+        (0..100)
+            .filter(|&ms| pwm.is_on(duty, Duration::from_millis(ms)))
+            .count()
+    }
+
+    #[test]
+    fn duty_cycle_sets_time_held() {
+        assert_eq!(on_count(pwm(), 0.0), 0);
+        assert_eq!(on_count(pwm(), 0.5), 50);
+        assert!(pwm().is_on(0.5, Duration::from_millis(110)));
+        assert!(!pwm().is_on(0.5, Duration::from_millis(160)));
+    }
+
+    #[test]
+    fn short_pulses_and_gaps_are_stretched() {
+        assert_eq!(on_count(pwm(), 0.05), 20);
+        assert_eq!(on_count(pwm(), 0.9), 100);
+    }
+}
@@ -0,0 +1,313 @@
+use crate::config::{ScanCodeConfig, ScanMode};
+use bouton_core::KeyAction;
+use bouton_core::control::GamepadControl;
+use std::ops::Range;
+use std::time::Instant;
+
+/// Where the scanning highlight is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Highlight {
+    /// A whole row, when the items are arranged in columns
+    Row(usize),
+    /// One item, by its index in the item list
+    Item(usize),
+}
+
+/// Switch-access scanning for users with one or two switches.
+///
+/// In auto mode the first press of select starts the scan and the highlight
+/// moves every dwell time; it stops after `loops` passes without a
+/// selection. In step mode the step button moves the highlight instead.
+/// With columns, select on a row moves into that row's items, and passing
+/// its last item goes back to the rows. Firing an item ends the scan.
+pub struct Scanner {
+    config: ScanCodeConfig,
+    highlight: Option<Highlight>,
+    /// When auto scanning next moves the highlight
+    next_move: Option<Instant>,
+    /// Full passes through the rows, or the items without columns
+    passes: usize,
+    /// When select went down, until it has been held for the accept time
+    select_down: Option<Instant>,
+}
+
+impl Scanner {
+    pub fn new(config: ScanCodeConfig) -> Self {
+        Self {
+            config,
+            highlight: None,
+            next_move: None,
+            passes: 0,
+            select_down: None,
+        }
+    }
+
+    pub fn config(&self) -> &ScanCodeConfig {
+        &self.config
+    }
+
+    pub fn highlight(&self) -> Option<Highlight> {
+        self.highlight
+    }
+
+    /// Whether `control` is the select or step switch.
+    pub fn watches(&self, control: GamepadControl) -> bool {
+        control == self.config.select || Some(control) == self.config.step
+    }
+
+    /// Takes a select or step switch change. Returns the index of the item
+    /// to fire, if one was selected.
+    pub fn button(
+        &mut self,
+        control: GamepadControl,
+        action: KeyAction,
+        now: Instant,
+    ) -> Option<usize> {
+        if control == self.config.select {
+            match action {
+                KeyAction::Press if self.config.accept.is_zero() => return self.select(now),
+                KeyAction::Press => self.select_down = Some(now),
+                KeyAction::Release => self.select_down = None,
+            }
+        } else if Some(control) == self.config.step && action == KeyAction::Press {
+            match self.highlight {
+                None => self.start(now),
+                Some(_) => self.advance(now),
+            }
+        }
+        None
+    }
+
+    /// Moves the auto-scan highlight once its dwell is up, and accepts a
+    /// select held long enough. Returns the item to fire, if any.
+    pub fn tick(&mut self, now: Instant) -> Option<usize> {
+        if let Some(next_move) = self.next_move
+            && now >= next_move
+        {
+            self.advance(now);
+        }
+
+        let down = self.select_down?;
+        if now.duration_since(down) < self.config.accept {
+            return None;
+        }
+        self.select_down = None;
+        self.select(now)
+    }
+
+    fn select(&mut self, now: Instant) -> Option<usize> {
+        match self.highlight {
+            None => {
+                // Step mode starts with the step switch
+                if self.config.mode == ScanMode::Auto {
+                    self.start(now);
+                }
+                None
+            }
+            Some(Highlight::Row(row)) => {
+                self.highlight = Some(Highlight::Item(self.row_items(row).start));
+                self.schedule(now);
+                None
+            }
+            Some(Highlight::Item(item)) => {
+                self.highlight = None;
+                self.next_move = None;
+                Some(item)
+            }
+        }
+    }
+
+    fn start(&mut self, now: Instant) {
+        self.highlight = Some(match self.config.columns {
+            Some(_) => Highlight::Row(0),
+            None => Highlight::Item(0),
+        });
+        self.passes = 0;
+        self.schedule(now);
+    }
+
+    fn advance(&mut self, now: Instant) {
+        let next = match self.highlight {
+            None => return,
+            Some(Highlight::Row(row)) => {
+                if row + 1 < self.rows() {
+                    Highlight::Row(row + 1)
+                } else {
+                    self.passes += 1;
+                    Highlight::Row(0)
+                }
+            }
+            Some(Highlight::Item(item)) => match self.config.columns {
+                Some(columns) if item + 1 >= self.row_items(item / columns).end => {
+                    // Past the end of the row: back to choosing a row
+                    Highlight::Row(item / columns)
+                }
+                Some(_) => Highlight::Item(item + 1),
+                None if item + 1 < self.config.items.len() => Highlight::Item(item + 1),
+                None => {
+                    self.passes += 1;
+                    Highlight::Item(0)
+                }
+            },
+        };
+
+        if self.config.mode == ScanMode::Auto && self.passes >= self.config.loops {
+            self.highlight = None;
+            self.next_move = None;
+            return;
+        }
+        self.highlight = Some(next);
+        self.schedule(now);
+    }
+
+    /// Sets when auto scanning moves on, with the longer first dwell at the
+    /// start of a pass.
+    fn schedule(&mut self, now: Instant) {
+        if self.config.mode != ScanMode::Auto {
+            return;
+        }
+        let first = match self.highlight {
+            Some(Highlight::Row(row)) => row == 0,
+            Some(Highlight::Item(item)) => match self.config.columns {
+                Some(columns) => item % columns == 0,
+                None => item == 0,
+            },
+            None => false,
+        };
+        let dwell = if first {
+            self.config.first_dwell
+        } else {
+            self.config.dwell
+        };
+        self.next_move = Some(now + dwell);
+    }
+
+    fn rows(&self) -> usize {
+        let columns = self.config.columns.unwrap_or(self.config.items.len());
+        self.config.items.len().div_ceil(columns)
+    }
+
+    fn row_items(&self, row: usize) -> Range<usize> {
+        let columns = self.config.columns.unwrap_or(self.config.items.len());
+        row * columns..((row + 1) * columns).min(self.config.items.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ScanningConfig;
+    use crate::testing::{self, Step};
+
+    fn scanner(options: &str) -> Scanner {
+        let config: ScanningConfig = toml::from_str(&format!(
+            "select = \"Cross\"\nstep = \"Circle\"\ndwell_ms = 100\n{}\n\
+             items = [{{ key = \"A\" }}, {{ key = \"B\" }}, {{ key = \"C\" }}, \
+             {{ key = \"D\" }}, {{ key = \"E\" }}]",
+            options
+        ))
+        .unwrap();
+        Scanner::new(config.codes().unwrap())
+    }
+
+    /// Plays `(milliseconds, button, action)` switch events and returns when
+    /// each item fired.
+    fn play(
+        scanner: &mut Scanner,
+        trace: &[(u64, GamepadControl, KeyAction)],
+        end: u64,
+    ) -> Vec<(u64, usize)> {
+        let trace: Vec<_> = trace
+            .iter()
+            .map(|&(ms, control, action)| (ms, (control, action)))
+            .collect();
+        let mut fired = Vec::new();
+        testing::play(&trace, end, |ms, now, step| {
+            let item = match step {
+                Step::Event((control, action)) => scanner.button(control, action, now),
+                Step::Tick => scanner.tick(now),
+            };
+            fired.extend(item.map(|item| (ms, item)));
+        });
+        fired
+    }
+
+    use GamepadControl::{Circle, Cross};
+    use KeyAction::{Press, Release};
+
+    #[test]
+    fn auto_scan_fires_the_highlighted_item() {
+        let mut auto = scanner("");
+        // Started at 0, on C from 200 to 300
+        assert_eq!(
+            play(&mut auto, &[(0, Cross, Press), (250, Cross, Press)], 400),
+            vec![(250, 2)]
+        );
+        assert_eq!(auto.highlight(), None);
+
+        // Stops after three passes without a selection
+        let mut auto = scanner("");
+        play(&mut auto, &[(0, Cross, Press)], 1495);
+        assert!(auto.highlight().is_some());
+        let mut auto = scanner("");
+        play(&mut auto, &[(0, Cross, Press)], 1500);
+        assert_eq!(auto.highlight(), None);
+    }
+
+    #[test]
+    fn step_scan_with_two_switches() {
+        let mut step = scanner("mode = \"step\"");
+        assert_eq!(
+            play(
+                &mut step,
+                &[
+                    (0, Cross, Press),
+                    (10, Circle, Press),
+                    (20, Circle, Press),
+                    (500, Cross, Press),
+                ],
+                600
+            ),
+            vec![(500, 1)]
+        );
+    }
+
+    #[test]
+    fn row_column_scanning() {
+        let mut grid = scanner("columns = 2");
+        // Rows [A B] [C D] [E]: second row at 100, into it at 150, D at 250
+        assert_eq!(
+            play(
+                &mut grid,
+                &[(0, Cross, Press), (150, Cross, Press), (260, Cross, Press)],
+                300
+            ),
+            vec![(260, 3)]
+        );
+
+        // Passing the end of a row goes back to the rows
+        let mut grid = scanner("columns = 2");
+        play(&mut grid, &[(0, Cross, Press), (50, Cross, Press)], 300);
+        assert_eq!(grid.highlight(), Some(Highlight::Row(0)));
+    }
+
+    #[test]
+    fn select_has_to_be_held_for_the_accept_time() {
+        let mut held = scanner("accept_ms = 50");
+        assert!(
+            play(
+                &mut held,
+                &[
+                    (0, Cross, Press),
+                    (60, Cross, Release),
+                    (120, Cross, Press),
+                    (130, Cross, Release),
+                ],
+                190
+            )
+            .is_empty()
+        );
+        // The scan started at 50 and has been on B since 150
+        assert_eq!(held.highlight(), Some(Highlight::Item(1)));
+    }
+}
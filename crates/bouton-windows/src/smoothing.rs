@@ -0,0 +1,217 @@
+use std::collections::VecDeque;
+use std::f32::consts::PI;
+use std::time::{Duration, Instant};
+
+/// Smoothing applied to the raw values of one axis before any mapping.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Smoothing {
+    /// Exponential moving average: the output closes 63% of the gap to the
+    /// raw value every `time_constant`
+    Ema { time_constant: Duration },
+    /// One Euro filter: heavy smoothing while the axis is held still, less
+    /// the faster it moves so deliberate motions don't lag. Cutoffs are in
+    /// Hz and `beta` is per raw unit per second of speed.
+    OneEuro {
+        min_cutoff: f32,
+        beta: f32,
+        derivative_cutoff: f32,
+    },
+    /// Median of the last `samples` values, dropping short spikes
+    Median { samples: usize },
+}
+
+/// Smooths one axis. The raw value is sampled on every event, and for EMA
+/// and One Euro again on every tick, so the output settles even when the
+/// device stops sending because the axis is still. The median only counts
+/// events: a repeated tick sample would outvote a spike's neighbours.
+pub struct AxisSmoother {
+    smoothing: Smoothing,
+    raw: i32,
+    /// Raw value at the previous sample
+    previous_raw: i32,
+    value: f32,
+    /// Smoothed speed of the raw value, for the One Euro filter
+    speed: f32,
+    last_sample: Option<Instant>,
+    history: VecDeque<i32>,
+}
+
+impl AxisSmoother {
+    pub fn new(smoothing: Smoothing) -> Self {
+        Self {
+            smoothing,
+            raw: 0,
+            previous_raw: 0,
+            value: 0.0,
+            speed: 0.0,
+            last_sample: None,
+            history: VecDeque::new(),
+        }
+    }
+
+    /// Takes a new raw value and returns the smoothed one.
+    pub fn axis(&mut self, raw: i32, now: Instant) -> i32 {
+        self.raw = raw;
+        self.sample(now);
+        self.value()
+    }
+
+    /// Samples the last raw value again, returning the smoothed value if
+    /// it changed.
+    pub fn tick(&mut self, now: Instant) -> Option<i32> {
+        if let Smoothing::Median { .. } = self.smoothing {
+            return None;
+        }
+        let last_sample = self.last_sample?;
+        if now <= last_sample {
+            return None;
+        }
+        let previous = self.value();
+        self.sample(now);
+        let value = self.value();
+        (value != previous).then_some(value)
+    }
+
+    pub fn raw(&self) -> i32 {
+        self.raw
+    }
+
+    pub fn value(&self) -> i32 {
+        self.value.round() as i32
+    }
+
+    fn sample(&mut self, now: Instant) {
+        let raw = self.raw as f32;
+        let moved = (self.raw - std::mem::replace(&mut self.previous_raw, self.raw)) as f32;
+        let Some(last_sample) = self.last_sample.replace(now) else {
+            // Nothing to smooth against yet
+            self.value = raw;
+            self.history.push_back(self.raw);
+            return;
+        };
+        let dt = now.duration_since(last_sample).as_secs_f32();
+
+        match self.smoothing {
+            Smoothing::Ema { time_constant } => {
+                let alpha = if time_constant.is_zero() {
+                    1.0
+                } else {
+                    1.0 - (-dt / time_constant.as_secs_f32()).exp()
+                };
+                self.value += alpha * (raw - self.value);
+            }
+            Smoothing::OneEuro {
+                min_cutoff,
+                beta,
+                derivative_cutoff,
+            } => {
+                if dt > 0.0 {
+                    let speed = moved / dt;
+                    self.speed += low_pass_alpha(derivative_cutoff, dt) * (speed - self.speed);
+                    let cutoff = min_cutoff + beta * self.speed.abs();
+                    self.value += low_pass_alpha(cutoff, dt) * (raw - self.value);
+                }
+            }
+            Smoothing::Median { samples } => {
+                self.history.push_back(self.raw);
+                while self.history.len() > samples.max(1) {
+                    self.history.pop_front();
+                }
+                let mut sorted: Vec<i32> = self.history.iter().copied().collect();
+                sorted.sort_unstable();
+                self.value = sorted[(sorted.len() - 1) / 2] as f32;
+            }
+        }
+    }
+}
+
+/// Smoothing factor of a first-order low-pass filter with the given cutoff
+/// frequency, for a sample `dt` seconds after the last.
+fn low_pass_alpha(cutoff: f32, dt: f32) -> f32 {
+    let tau = 1.0 / (2.0 * PI * cutoff.max(f32::EPSILON));
+    1.0 / (1.0 + tau / dt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, Step};
+
+    /// Feeds `(milliseconds, raw)` events and returns the smoothed value
+    /// after each millisecond listed in `at`.
+    fn play(smoothing: Smoothing, trace: &[(u64, i32)], at: &[u64]) -> Vec<i32> {
+        let mut smoother = AxisSmoother::new(smoothing);
+        let end = at.iter().copied().max().unwrap_or(0);
+        let mut values = Vec::new();
+        testing::play(trace, end, |ms, now, step| match step {
+            Step::Event(raw) => {
+                smoother.axis(raw, now);
+            }
+            Step::Tick => {
+                smoother.tick(now);
+                if at.contains(&ms) {
+                    values.push(smoother.value());
+                }
+            }
+        });
+        values
+    }
+
+    #[test]
+    fn ema_settles_on_a_held_value() {
+        let ema = Smoothing::Ema {
+            time_constant: Duration::from_millis(50),
+        };
+        let values = play(ema, &[(0, 0), (10, 200)], &[10, 60, 500]);
+        assert!(values[0] < 100, "{:?}", values);
+        // One time constant later the rest of the gap is 63% closed
+        let expected = values[0] as f32 + (200 - values[0]) as f32 * (1.0 - (-1.0f32).exp());
+        assert!((values[1] as f32 - expected).abs() <= 2.0, "{:?}", values);
+        assert_eq!(values[2], 200);
+    }
+
+    #[test]
+    fn one_euro_follows_fast_moves_and_steadies_tremor() {
+        let one_euro = Smoothing::OneEuro {
+            min_cutoff: 1.0,
+            beta: 0.01,
+            derivative_cutoff: 1.0,
+        };
+        // A 10 Hz tremor of 10 units around the centre barely shows
+        let tremor: Vec<(u64, i32)> = (0..40)
+            .map(|step| (step * 50, if step % 2 == 0 { 138 } else { 118 }))
+            .collect();
+        let values = play(one_euro, &tremor, &[1000, 1025, 1950, 1975]);
+        assert!(
+            values.iter().all(|&value| (value - 128).abs() <= 4),
+            "{:?}",
+            values
+        );
+
+        // A full push is tracked closely, then settles exactly
+        let values = play(one_euro, &[(0, 128), (100, 255)], &[200, 2000]);
+        assert!(values[0] > 200, "{:?}", values);
+        assert_eq!(values[1], 255);
+    }
+
+    #[test]
+    fn median_drops_spikes() {
+        let median = Smoothing::Median { samples: 5 };
+        // Ticks are not samples: a single new value doesn't take over
+        // however long it holds, but a few events of it do
+        let values = play(
+            median,
+            &[
+                (0, 128),
+                (20, 128),
+                (25, 255),
+                (30, 128),
+                (60, 200),
+                (200, 200),
+                (210, 200),
+            ],
+            &[25, 30, 190, 210],
+        );
+        assert_eq!(values, vec![128, 128, 128, 200]);
+    }
+}
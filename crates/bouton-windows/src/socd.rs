@@ -0,0 +1,175 @@
+use bouton_core::KeyAction;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// How to resolve two opposing keys requested at the same time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SocdMode {
+    /// The most recently pressed key wins.
+    #[default]
+    Last,
+    /// The key that was already held wins.
+    First,
+    /// Neither key is sent while both are requested.
+    Neutral,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SocdPair {
+    pub keys: [u32; 2],
+    pub mode: SocdMode,
+}
+
+/// Cleans simultaneous opposing directions out of the output key stream.
+///
+/// Takes the keys the bindings ask for and returns the key changes that
+/// should actually be injected. Keys that aren't part of any pair pass
+/// straight through.
+pub struct SocdResolver {
+    pairs: Vec<SocdPair>,
+    /// Most recently pressed key of each pair, indexed like `pairs`
+    last_pressed: Vec<Option<u32>>,
+    requested: HashSet<u32>,
+    active: HashSet<u32>,
+}
+
+impl SocdResolver {
+    pub fn new(pairs: Vec<SocdPair>) -> Self {
+        Self {
+            last_pressed: vec![None; pairs.len()],
+            pairs,
+            requested: HashSet::new(),
+            active: HashSet::new(),
+        }
+    }
+
+    /// Records a requested key change and returns the injections it causes,
+    /// releases first.
+    pub fn resolve(&mut self, key_code: u32, action: KeyAction) -> Vec<(u32, KeyAction)> {
+        match action {
+            KeyAction::Press => {
+                self.requested.insert(key_code);
+                for (pair, last) in self.pairs.iter().zip(self.last_pressed.iter_mut()) {
+                    if pair.keys.contains(&key_code) {
+                        *last = Some(key_code);
+                    }
+                }
+            }
+            KeyAction::Release => {
+                self.requested.remove(&key_code);
+            }
+        }
+
+        // Only the changed key and its opponents can change state
+        let mut affected = vec![key_code];
+        for pair in self.pairs.iter().filter(|pair| pair.keys.contains(&key_code)) {
+            affected.extend(pair.keys.iter().filter(|&&key| key != key_code));
+        }
+
+        let mut releases = Vec::new();
+        let mut presses = Vec::new();
+        for key in affected {
+            let should_be_active = self.should_be_active(key);
+            let is_active = self.active.contains(&key);
+            if should_be_active && !is_active {
+                self.active.insert(key);
+                presses.push((key, KeyAction::Press));
+            } else if !should_be_active && is_active {
+                self.active.remove(&key);
+                releases.push((key, KeyAction::Release));
+            }
+        }
+
+        releases.extend(presses);
+        releases
+    }
+
+    /// Forgets all requested keys, e.g. after everything was force-released.
+    pub fn clear(&mut self) {
+        self.requested.clear();
+        self.active.clear();
+        self.last_pressed.fill(None);
+    }
+
+    fn should_be_active(&self, key: u32) -> bool {
+        if !self.requested.contains(&key) {
+            return false;
+        }
+
+        self.pairs
+            .iter()
+            .zip(&self.last_pressed)
+            .filter(|(pair, _)| pair.keys.contains(&key))
+            .all(|(pair, last)| {
+                let opponent = if pair.keys[0] == key {
+                    pair.keys[1]
+                } else {
+                    pair.keys[0]
+                };
+                if !self.requested.contains(&opponent) {
+                    return true;
+                }
+                match pair.mode {
+                    SocdMode::Last => *last == Some(key),
+                    SocdMode::First => *last != Some(key),
+                    SocdMode::Neutral => false,
+                }
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEFT: u32 = 0x25;
+    const RIGHT: u32 = 0x27;
+
+    fn resolver(mode: SocdMode) -> SocdResolver {
+        SocdResolver::new(vec![SocdPair {
+            keys: [LEFT, RIGHT],
+            mode,
+        }])
+    }
+
+    #[test]
+    fn last_input_wins_and_restores_on_release() {
+        let mut socd = resolver(SocdMode::Last);
+        assert_eq!(socd.resolve(LEFT, KeyAction::Press), vec![(LEFT, KeyAction::Press)]);
+        assert_eq!(
+            socd.resolve(RIGHT, KeyAction::Press),
+            vec![(LEFT, KeyAction::Release), (RIGHT, KeyAction::Press)]
+        );
+        assert_eq!(
+            socd.resolve(RIGHT, KeyAction::Release),
+            vec![(RIGHT, KeyAction::Release), (LEFT, KeyAction::Press)]
+        );
+    }
+
+    #[test]
+    fn first_input_keeps_the_held_key() {
+        let mut socd = resolver(SocdMode::First);
+        socd.resolve(LEFT, KeyAction::Press);
+        assert!(socd.resolve(RIGHT, KeyAction::Press).is_empty());
+        assert_eq!(
+            socd.resolve(LEFT, KeyAction::Release),
+            vec![(LEFT, KeyAction::Release), (RIGHT, KeyAction::Press)]
+        );
+    }
+
+    #[test]
+    fn neutral_releases_both() {
+        let mut socd = resolver(SocdMode::Neutral);
+        socd.resolve(LEFT, KeyAction::Press);
+        assert_eq!(socd.resolve(RIGHT, KeyAction::Press), vec![(LEFT, KeyAction::Release)]);
+        assert_eq!(socd.resolve(LEFT, KeyAction::Release), vec![(RIGHT, KeyAction::Press)]);
+    }
+
+    #[test]
+    fn unpaired_keys_pass_through() {
+        let mut socd = resolver(SocdMode::Neutral);
+        assert_eq!(socd.resolve(0x41, KeyAction::Press), vec![(0x41, KeyAction::Press)]);
+        assert_eq!(socd.resolve(0x41, KeyAction::Release), vec![(0x41, KeyAction::Release)]);
+    }
+}
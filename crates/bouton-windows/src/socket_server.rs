@@ -1,612 +1,1367 @@
-use crate::config::{DPadCodeConfig, JoystickCodeConfig, TriggerCodeConfig};
-use crate::key_injector::KeyInjector;
-use bouton_core::{ControlEvent, KeyAction, control::GamepadControl};
+use crate::analog::{stage_keys, stage_level};
+use crate::config::{Bindings, ButtonAction, RadialCodeConfig, RadialFire, TouchpadMode};
+use crate::directions::{Direction8, radial_sector};
+use crate::filters::InputFilters;
+use crate::chords::{Chord, ChordRecognizer};
+use crate::keyboard::{KeyboardOutput, KeyboardView, OnScreenKeyboard};
+use crate::morse::{MorseDecoder, MorseOutput, MorseView};
+use crate::scanning::{Highlight, Scanner};
+use crate::smoothing::AxisSmoother;
+use crate::flick::FlickStick;
+use crate::gestures::GestureRecognizer;
+use crate::gyro::GyroMouse;
+use crate::motion::MotionGestures;
+use crate::motion_inputs::MotionInputRecognizer;
+use crate::key_injector::{InputBackend, KeyInjector, MouseButton};
+use crate::keycode::{code_to_name, is_modifier};
+use crate::output::{KeyOutput, StickyState};
+use crate::pwm::Pwm;
+use crate::trackpad::Trackpad;
+use bouton_core::{ControlEvent, KeyAction, ServerEvent, control::GamepadControl};
 use std::collections::HashMap;
 use std::net::SocketAddr;
-use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::net::UdpSocket;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
 
 #[derive(Debug, Clone)]
 pub enum UIEvent {
     ClientConnected(String),
     KeyPressed(String, u32),
     KeyReleased(String, u32),
+    KeyLatched(String, u32),
+    KeyUnlatched(String, u32),
+    /// Text typed as Unicode characters
+    Typed(String),
+    /// A text-entry mode switched on or off
+    Mode(String, bool),
+    /// On-screen keyboard, `None` once it is closed
+    Keyboard(Option<KeyboardView>),
+    /// Morse letter being entered and the text typed so far
+    Morse(MorseView),
+    /// Labels of the switch-scanning items and the number of columns
+    ScanItems(Vec<String>, Option<usize>),
+    /// Where the scanning highlight is, `None` while scanning is idle
+    ScanHighlight(Option<Highlight>),
+    /// Sticky modifier latched or locked, `None` once it is let go
+    ModifierSticky(String, u32, Option<StickyState>),
+    /// Controls currently holding a key down; empty once it is released
+    KeyHolders(String, u32, Vec<String>),
+    MouseButton(MouseButton, KeyAction),
+    /// Radial-menu stick, its selected sector and key if any, and the sector count
+    RadialSector(String, Option<(usize, String)>, usize),
     Unbound(String),
     Error(String),
 }
 
+/// How often time-based behaviour (minimum hold times, PWM and the like) is
+/// re-evaluated when no new events arrive. Also the resolution of PWM pulses.
+pub(crate) const TICK_INTERVAL: Duration = Duration::from_millis(5);
+
 pub struct SocketServer {
     socket: UdpSocket,
-    button_map: Arc<HashMap<GamepadControl, u32>>,
-    joystick_map: Arc<HashMap<GamepadControl, JoystickCodeConfig>>,
-    trigger_map: Arc<HashMap<GamepadControl, TriggerCodeConfig>>,
-    dpad_config: Arc<Option<DPadCodeConfig>>,
+    bindings: Bindings,
     ui_tx: mpsc::UnboundedSender<UIEvent>,
 }
 
 impl SocketServer {
     pub async fn bind(
         addr: SocketAddr,
-        button_map: HashMap<GamepadControl, u32>,
-        joystick_map: HashMap<GamepadControl, JoystickCodeConfig>,
-        trigger_map: HashMap<GamepadControl, TriggerCodeConfig>,
-        dpad_config: Option<DPadCodeConfig>,
+        bindings: Bindings,
         ui_tx: mpsc::UnboundedSender<UIEvent>,
     ) -> std::io::Result<Self> {
         let socket = UdpSocket::bind(addr).await?;
         Ok(Self {
             socket,
-            button_map: Arc::new(button_map),
-            joystick_map: Arc::new(joystick_map),
-            trigger_map: Arc::new(trigger_map),
-            dpad_config: Arc::new(dpad_config),
+            bindings,
             ui_tx,
         })
     }
 
-    pub async fn run(self) -> std::io::Result<()> {
+    pub async fn run(self, mut shutdown: oneshot::Receiver<()>) -> std::io::Result<()> {
         let mut buf = [0u8; 4096];
-        let button_map = Arc::new(self.button_map);
-        let joystick_map = Arc::new(self.joystick_map);
-        let trigger_map = Arc::new(self.trigger_map);
-        let dpad_config = Arc::new(self.dpad_config);
         let ui_tx = self.ui_tx;
 
         // Track state across all datagrams
-        let mut joystick_states: HashMap<GamepadControl, (u8, u8)> = HashMap::new();
-        let mut joystick_pressed: HashMap<GamepadControl, (Option<u32>, Option<u32>)> =
-            HashMap::new();
-        let mut trigger_states: HashMap<GamepadControl, bool> = HashMap::new();
-        let mut dpad_state: Option<(u8, u8)> = None;
-        let mut dpad_pressed: Option<u32> = None;
+        let mut mapper = Mapper::new(self.bindings, KeyInjector, ui_tx.clone());
         let mut connected_client: Option<std::net::SocketAddr> = None;
+        let mut tick = tokio::time::interval(TICK_INTERVAL);
+        tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
 
         loop {
-            match self.socket.recv_from(&mut buf).await {
-                Ok((n, addr)) => {
-                    // Update when a new client connects or reconnects
-                    if connected_client != Some(addr) {
-                        connected_client = Some(addr);
-                        let _ = ui_tx.send(UIEvent::ClientConnected(addr.to_string()));
-                    }
+            tokio::select! {
+                _ = tick.tick() => {
+                    mapper.tick(Instant::now());
+                }
+                _ = &mut shutdown => {
+                    // Never leave keys stuck down on the Windows side
+                    mapper.output.release_all();
+                    return Ok(());
+                }
+                received = self.socket.recv_from(&mut buf) => match received {
+                    Ok((n, addr)) => {
+                        // Update when a new client connects or reconnects
+                        if connected_client != Some(addr) {
+                            connected_client = Some(addr);
+                            let _ = ui_tx.send(UIEvent::ClientConnected(addr.to_string()));
+                        }
 
-                    if let Ok(event) = bincode::deserialize::<ControlEvent>(&buf[..n]) {
-                        handle_event(
-                            event,
-                            &button_map,
-                            &joystick_map,
-                            &trigger_map,
-                            &dpad_config,
-                            &mut joystick_states,
-                            &mut joystick_pressed,
-                            &mut trigger_states,
-                            &mut dpad_state,
-                            &mut dpad_pressed,
-                            ui_tx.clone(),
-                        )
-                        .await;
+                        if let Ok(event) = bincode::deserialize::<ControlEvent>(&buf[..n]) {
+                            mapper.handle_event(event, Instant::now());
+                        }
+                    }
+                    Err(e) => {
+                        let _ = ui_tx.send(UIEvent::Error(format!("Socket recv error: {}", e)));
                     }
                 }
-                Err(e) => {
-                    let _ = ui_tx.send(UIEvent::Error(format!("Socket recv error: {}", e)));
+            }
+
+            // Let the client show state only the server knows
+            for event in mapper.feedback.drain(..) {
+                if let Some(addr) = connected_client
+                    && let Ok(bytes) = bincode::serialize(&event)
+                {
+                    let _ = self.socket.send_to(&bytes, addr).await;
                 }
             }
         }
     }
 }
 
-async fn handle_event(
-    event: ControlEvent,
-    button_map: &Arc<HashMap<GamepadControl, u32>>,
-    joystick_map: &Arc<HashMap<GamepadControl, JoystickCodeConfig>>,
-    trigger_map: &Arc<HashMap<GamepadControl, TriggerCodeConfig>>,
-    dpad_config: &Arc<Option<DPadCodeConfig>>,
-    joystick_states: &mut HashMap<GamepadControl, (u8, u8)>,
-    joystick_pressed: &mut HashMap<GamepadControl, (Option<u32>, Option<u32>)>,
-    trigger_states: &mut HashMap<GamepadControl, bool>,
-    dpad_state: &mut Option<(u8, u8)>,
-    dpad_pressed: &mut Option<u32>,
+/// A key pressed at `down_at` and released at `up_at`, so that a macro's keys
+/// stay down long enough for a game polling once a frame to see them.
+struct TimedTap {
+    holder: String,
+    key: u32,
+    down_at: Instant,
+    up_at: Instant,
+    down: bool,
+}
+
+/// Maps incoming control events onto key output, keeping per-control state
+/// between datagrams.
+struct Mapper<B: InputBackend = KeyInjector> {
+    bindings: Bindings,
+    joystick_states: HashMap<GamepadControl, (i32, i32)>,
+    joystick_pressed: HashMap<GamepadControl, Vec<u32>>,
+    /// Keys each stick points at, including the ones PWM has in an off phase
+    joystick_engaged: HashMap<GamepadControl, Vec<u32>>,
+    joystick_directions: HashMap<GamepadControl, Direction8>,
+    /// Sticks past their run radius
+    joystick_running: HashMap<GamepadControl, bool>,
+    trigger_values: HashMap<GamepadControl, i32>,
+    /// Number of trigger stages currently reached
+    trigger_states: HashMap<GamepadControl, usize>,
+    trigger_pressed: HashMap<GamepadControl, Vec<u32>>,
+    /// When each pulsing stick or trigger started its PWM cycle
+    pwm_started: HashMap<GamepadControl, Instant>,
+    /// When each stick or trigger last changed its keys, for minimum hold times
+    changed_at: HashMap<GamepadControl, Instant>,
+    dpad_state: Option<(u8, u8)>,
+    dpad_pressed: Vec<u32>,
+    /// Sector each radial-menu stick currently points into
+    radial_sectors: HashMap<GamepadControl, usize>,
+    flick_sticks: HashMap<GamepadControl, FlickStick>,
+    trackpad: Option<Trackpad>,
+    gestures: Option<GestureRecognizer>,
+    gyro: Option<GyroMouse>,
+    motion_gestures: Option<MotionGestures>,
+    /// Keys held by tilting the gamepad
+    tilt_pressed: Vec<u32>,
+    /// Longest patterns first, so they win over the shorter ones they contain
+    motion_inputs: Vec<MotionInputRecognizer>,
+    /// Keys motion inputs are tapping, in the order they go down
+    timed_taps: Vec<TimedTap>,
+    filters: InputFilters,
+    smoothers: HashMap<GamepadControl, AxisSmoother>,
+    scanner: Option<Scanner>,
+    keyboard: Option<OnScreenKeyboard>,
+    chords: Option<ChordRecognizer>,
+    morse: Option<MorseDecoder>,
+    /// Highlight last shown in the TUI
+    scan_highlight: Option<Highlight>,
+    /// Events waiting to be sent back to the client
+    feedback: Vec<ServerEvent>,
+    output: KeyOutput<B>,
     ui_tx: mpsc::UnboundedSender<UIEvent>,
-) {
-    match event {
-        ControlEvent::Button(button_event) => {
-            if let Some(&key_code) = button_map.get(&button_event.control) {
-                let key_name = code_to_name(key_code);
+}
 
-                match KeyInjector::inject(key_code, button_event.action) {
-                    Err(e) => {
-                        let _ = ui_tx.send(UIEvent::Error(format!(
-                            "Failed to inject {}: {}",
-                            key_name, e
-                        )));
-                    }
-                    Ok(_) => {
-                        let ui_event = match button_event.action {
-                            KeyAction::Press => UIEvent::KeyPressed(key_name.clone(), key_code),
-                            KeyAction::Release => UIEvent::KeyReleased(key_name.clone(), key_code),
-                        };
-                        let _ = ui_tx.send(ui_event);
-                    }
-                }
-            } else {
-                // Button is unbound
-                let action_str = match button_event.action {
-                    KeyAction::Press => "pressed",
-                    KeyAction::Release => "released",
-                };
-                let _ = ui_tx.send(UIEvent::Unbound(format!(
-                    "{} ({})",
-                    button_event.control, action_str
-                )));
+impl<B: InputBackend> Mapper<B> {
+    fn new(bindings: Bindings, backend: B, ui_tx: mpsc::UnboundedSender<UIEvent>) -> Self {
+        let mut output = KeyOutput::new(backend, bindings.socd.clone(), ui_tx.clone());
+        // Repeats are looked up by the holder names the handlers use
+        for (control, button) in &bindings.buttons {
+            if let Some(repeat) = button.repeat {
+                output.set_repeat(&control.to_string(), repeat);
+            }
+        }
+        for (&stick_key, stick) in &bindings.joysticks {
+            if let Some(repeat) = stick.repeat {
+                output.set_repeat(stick_name(stick_key), repeat);
+            }
+        }
+        for (control, trigger) in &bindings.triggers {
+            if let Some(repeat) = trigger.repeat {
+                output.set_repeat(&control.to_string(), repeat);
+            }
+        }
+        if let Some(repeat) = bindings.dpad.and_then(|dpad| dpad.repeat) {
+            output.set_repeat("D-Pad", repeat);
+        }
+        output.set_sticky_modifiers(bindings.sticky_modifiers);
+        let touchpad = bindings.touchpad.clone();
+        let (trackpad, gestures) = match touchpad {
+            Some(config) if config.mode == TouchpadMode::Gestures => {
+                (None, Some(GestureRecognizer::new(config)))
             }
+            Some(config) => (Some(Trackpad::new(config)), None),
+            None => (None, None),
+        };
+        let filters = InputFilters::new(bindings.filters.clone());
+        let scanner = bindings.scanning.clone().map(Scanner::new);
+        let keyboard = bindings.keyboard.clone().map(OnScreenKeyboard::new);
+        let chords = bindings.chords.clone().map(ChordRecognizer::new);
+        let morse = bindings.morse.clone().map(MorseDecoder::new);
+        if let Some(morse) = &morse {
+            let _ = ui_tx.send(UIEvent::Morse(morse.view()));
+        }
+        if let Some(scanning) = &bindings.scanning {
+            let labels = scanning.items.iter().map(|item| item.label.clone()).collect();
+            let _ = ui_tx.send(UIEvent::ScanItems(labels, scanning.columns));
+        }
+        let smoothers = bindings
+            .smoothing
+            .iter()
+            .map(|(&control, &smoothing)| (control, AxisSmoother::new(smoothing)))
+            .collect();
+        let flick_sticks = bindings
+            .flick_sticks
+            .iter()
+            .map(|(&stick_key, config)| (stick_key, FlickStick::new(config.clone())))
+            .collect();
+        let gyro = bindings.gyro.clone().map(GyroMouse::new);
+        let motion_gestures = bindings.motion.clone().map(MotionGestures::new);
+        let mut motion_inputs: Vec<MotionInputRecognizer> = bindings
+            .motion_inputs
+            .iter()
+            .cloned()
+            .map(MotionInputRecognizer::new)
+            .collect();
+        motion_inputs.sort_by_key(|recognizer| std::cmp::Reverse(recognizer.config().pattern.len()));
+        Self {
+            bindings,
+            joystick_states: HashMap::new(),
+            joystick_pressed: HashMap::new(),
+            joystick_engaged: HashMap::new(),
+            joystick_directions: HashMap::new(),
+            joystick_running: HashMap::new(),
+            trigger_values: HashMap::new(),
+            trigger_states: HashMap::new(),
+            trigger_pressed: HashMap::new(),
+            pwm_started: HashMap::new(),
+            changed_at: HashMap::new(),
+            dpad_state: None,
+            dpad_pressed: Vec::new(),
+            radial_sectors: HashMap::new(),
+            flick_sticks,
+            trackpad,
+            gestures,
+            gyro,
+            motion_gestures,
+            tilt_pressed: Vec::new(),
+            motion_inputs,
+            timed_taps: Vec::new(),
+            filters,
+            smoothers,
+            scanner,
+            keyboard,
+            chords,
+            morse,
+            scan_highlight: None,
+            feedback: Vec::new(),
+            output,
+            ui_tx,
         }
-        ControlEvent::Axis(axis_event) => {
-            let control = axis_event.control;
+    }
 
-            match control {
-                GamepadControl::LeftStickX | GamepadControl::LeftStickY => {
-                    if let Some(joystick_config) = joystick_map.get(&GamepadControl::LeftStickX) {
-                        handle_joystick_axis(
-                            control,
-                            axis_event.value as u8,
-                            joystick_config,
-                            joystick_states,
-                            joystick_pressed,
-                            ui_tx.clone(),
-                        )
-                        .await;
-                    } else {
-                        let _ = ui_tx.send(UIEvent::Unbound(format!(
-                            "{}: {}",
-                            control, axis_event.value
-                        )));
-                    }
+    fn handle_event(&mut self, event: ControlEvent, now: Instant) {
+        match event {
+            ControlEvent::Button(button_event) => {
+                // Slow, bounce and minimum-hold filters decide whether and
+                // when the change counts
+                if let Some(action) = self.filters.button(button_event.control, button_event.action, now) {
+                    self.handle_button(button_event.control, action, now);
                 }
-                GamepadControl::RightStickX | GamepadControl::RightStickY => {
-                    if let Some(joystick_config) = joystick_map.get(&GamepadControl::RightStickX) {
-                        handle_joystick_axis(
+            }
+            ControlEvent::Axis(axis_event) => {
+                let control = axis_event.control;
+                let value = match self.smoothers.get_mut(&control) {
+                    Some(smoother) => {
+                        let value = smoother.axis(axis_event.value, now);
+                        self.feedback.push(ServerEvent::AxisSmoothed {
                             control,
-                            axis_event.value as u8,
-                            joystick_config,
-                            joystick_states,
-                            joystick_pressed,
-                            ui_tx.clone(),
-                        )
-                        .await;
-                    } else {
-                        let _ = ui_tx.send(UIEvent::Unbound(format!(
-                            "{}: {}",
-                            control, axis_event.value
-                        )));
+                            raw: axis_event.value,
+                            value,
+                        });
+                        value
                     }
-                }
-                GamepadControl::L2 | GamepadControl::R2 => {
-                    if let Some(trigger_config) = trigger_map.get(&control) {
-                        handle_trigger_axis(
-                            control,
-                            axis_event.value as u8,
-                            trigger_config,
-                            trigger_states,
-                            ui_tx.clone(),
-                        )
-                        .await;
-                    } else {
-                        let _ = ui_tx.send(UIEvent::Unbound(format!(
-                            "{}: {}",
-                            control, axis_event.value
-                        )));
+                    None => axis_event.value,
+                };
+                self.handle_axis(control, value, now);
+            }
+            ControlEvent::Touch(touch) => {
+                if let Some(trackpad) = self.trackpad.as_mut() {
+                    for mouse_event in trackpad.touch(touch, now) {
+                        self.output.send_mouse(mouse_event);
                     }
+                } else if let Some(gestures) = self.gestures.as_mut() {
+                    if let Some(gesture) = gestures.touch(touch, now) {
+                        let key = self
+                            .bindings
+                            .touchpad
+                            .as_ref()
+                            .and_then(|config| gesture.key(&config.gestures));
+                        match key {
+                            Some(key) => self.tap("Touchpad", key),
+                            None => {
+                                let _ = self
+                                    .ui_tx
+                                    .send(UIEvent::Unbound(format!("Touchpad {}", gesture)));
+                            }
+                        }
+                    }
+                } else if touch.position.is_none() {
+                    // Once per touch rather than for every movement
+                    let _ = self
+                        .ui_tx
+                        .send(UIEvent::Unbound(format!("Touchpad finger {}", touch.slot)));
                 }
-                GamepadControl::DPadX | GamepadControl::DPadY => {
-                    if let Some(dpad) = dpad_config.as_ref() {
-                        handle_dpad_axis(
-                            control,
-                            axis_event.value as u8,
-                            dpad,
-                            dpad_state,
-                            dpad_pressed,
-                            ui_tx.clone(),
-                        )
-                        .await;
-                    } else {
-                        let _ = ui_tx.send(UIEvent::Unbound(format!(
-                            "{}: {}",
-                            control, axis_event.value
-                        )));
+            }
+            ControlEvent::Motion(motion) => {
+                // Motion arrives hundreds of times a second, so it isn't
+                // reported as unbound
+                if let Some(mouse_event) = self.gyro.as_mut().and_then(|gyro| gyro.motion(motion, now)) {
+                    self.output.send_mouse(mouse_event);
+                }
+                if let Some(gestures) = self.motion_gestures.as_mut() {
+                    let keys = gestures.motion(motion, now);
+                    let old_keys = std::mem::replace(&mut self.tilt_pressed, keys.tilt.clone());
+                    self.switch_keys("Tilt", &old_keys, &keys.tilt, false);
+                    for (gesture, key) in keys.taps {
+                        self.tap(&gesture, key);
                     }
                 }
-                _ => {}
             }
         }
     }
-}
 
-fn compute_adaptive_deadzone(axis_diff: i16, perpendicular_diff: i16, base_deadzone: i16) -> bool {
-    let max_range = 128i16;
-    let max_dynamic_deadzone = 50i16;
+    fn handle_axis(&mut self, control: GamepadControl, value: i32, now: Instant) {
+        // The open on-screen keyboard takes its navigation axes
+        if let Some(keyboard) = self.keyboard.as_mut()
+            && keyboard.watches(control)
+        {
+            if keyboard.axis(control, value, now) {
+                self.report_keyboard();
+            }
+            return;
+        }
+
+        let handled = match control {
+            GamepadControl::LeftStickX
+            | GamepadControl::LeftStickY
+            | GamepadControl::RightStickX
+            | GamepadControl::RightStickY => self.handle_joystick_axis(control, value, now),
+            GamepadControl::L2 | GamepadControl::R2 => {
+                self.handle_trigger_axis(control, value, now)
+            }
+            GamepadControl::DPadX | GamepadControl::DPadY => {
+                self.handle_dpad_axis(control, value as u8)
+            }
+            _ => true,
+        };
+        let recognized = self.handle_motion_inputs(control, value, now);
 
-    let ratio = perpendicular_diff as f32 / max_range as f32;
-    let dynamic_deadzone = (max_dynamic_deadzone as f32 * ratio).ceil() as i16;
+        if !handled && !recognized {
+            let _ = self
+                .ui_tx
+                .send(UIEvent::Unbound(format!("{}: {}", control, value)));
+        }
+    }
 
-    // Use whichever is larger: configurable base or dynamic
-    let effective_deadzone = base_deadzone.max(dynamic_deadzone);
-    axis_diff < effective_deadzone
-}
+    fn handle_button(&mut self, control: GamepadControl, action: KeyAction, now: Instant) {
+        // The gyro and calibrate buttons can also be bound to keys
+        let gyro_button = self
+            .gyro
+            .as_mut()
+            .is_some_and(|gyro| gyro.button(control, action));
+        let calibrate_button = self
+            .motion_gestures
+            .as_mut()
+            .is_some_and(|gestures| gestures.button(control, action));
 
-async fn handle_joystick_axis(
-    control: GamepadControl,
-    value: u8,
-    config: &JoystickCodeConfig,
-    states: &mut HashMap<GamepadControl, (u8, u8)>,
-    pressed: &mut HashMap<GamepadControl, (Option<u32>, Option<u32>)>,
-    ui_tx: mpsc::UnboundedSender<UIEvent>,
-) {
-    // Determine the paired axis and use the X axis control as the key
-    let (is_x_axis, stick_key) = match control {
-        GamepadControl::LeftStickX => (true, GamepadControl::LeftStickX),
-        GamepadControl::LeftStickY => (false, GamepadControl::LeftStickX),
-        GamepadControl::RightStickX => (true, GamepadControl::RightStickX),
-        GamepadControl::RightStickY => (false, GamepadControl::RightStickX),
-        _ => return,
-    };
-
-    // Get current state, defaulting to center (127, 127)
-    let (mut x, mut y) = states.get(&stick_key).copied().unwrap_or((127, 127));
-
-    if is_x_axis {
-        x = value;
-    } else {
-        y = value;
-    }
-
-    states.insert(stick_key, (x, y));
-
-    // Compute distances from center with adaptive deadzone
-    let center = 127i16;
-    let x_diff = (x as i16 - center).abs();
-    let y_diff = (y as i16 - center).abs();
-    let base_deadzone = config.deadzone as i16;
-
-    // Apply adaptive deadzone: each axis's deadzone scales based on the other axis's deviation
-    let x_in_deadzone = compute_adaptive_deadzone(x_diff, y_diff, base_deadzone);
-    let y_in_deadzone = compute_adaptive_deadzone(y_diff, x_diff, base_deadzone);
-
-    let (mut x_key_pressed, mut y_key_pressed) =
-        pressed.get(&stick_key).copied().unwrap_or((None, None));
-
-    // Handle X axis
-    let new_x_key = if x_in_deadzone {
-        None
-    } else if x > 127 {
-        Some(config.right)
-    } else {
-        Some(config.left)
-    };
-
-    if new_x_key != x_key_pressed {
-        if let Some(old_key) = x_key_pressed {
-            let key_name = code_to_name(old_key);
-            match KeyInjector::inject(old_key, KeyAction::Release) {
-                Err(e) => {
-                    let _ = ui_tx.send(UIEvent::Error(format!(
-                        "Failed to release {}: {}",
-                        key_name, e
-                    )));
+        if self.handle_keyboard_button(control, action) {
+            // Consumed by the on-screen keyboard
+        } else if self.handle_chord_button(control, action) {
+            // Consumed by chord entry
+        } else if self.handle_morse_button(control, action, now) {
+            // Consumed as the Morse button
+        } else if self.handle_scanning(control, action, now) {
+            // Consumed as a scanning switch
+        } else if self.handle_radial_confirm(control, action) {
+            // Consumed as a radial-menu confirm button
+        } else if let Some(button) = self.bindings.buttons.get(&control) {
+            match &button.action {
+                ButtonAction::Key(key) => {
+                    self.output
+                        .send(&control.to_string(), *key, action, button.toggle)
                 }
-                Ok(_) => {
-                    let _ = ui_tx.send(UIEvent::KeyReleased(key_name.clone(), old_key));
+                ButtonAction::Text(text) if action == KeyAction::Press => {
+                    self.output.send_text(text)
                 }
+                ButtonAction::Text(_) => {}
             }
+        } else if !gyro_button && !calibrate_button {
+            // Button is unbound
+            let action_str = match action {
+                KeyAction::Press => "pressed",
+                KeyAction::Release => "released",
+            };
+            let _ = self
+                .ui_tx
+                .send(UIEvent::Unbound(format!("{} ({})", control, action_str)));
         }
-        if let Some(new_key) = new_x_key {
-            let key_name = code_to_name(new_key);
-            match KeyInjector::inject(new_key, KeyAction::Press) {
-                Err(e) => {
-                    let _ = ui_tx.send(UIEvent::Error(format!(
-                        "Failed to inject {}: {}",
-                        key_name, e
-                    )));
-                }
-                Ok(_) => {
-                    let _ = ui_tx.send(UIEvent::KeyPressed(key_name.clone(), new_key));
-                }
+    }
+
+    /// Re-evaluates sticks and triggers with their last values, so a change
+    /// held back by a minimum hold time still happens once it has expired
+    /// and PWM keys pulse while the control is held still. Also releases
+    /// buttons held back by the input filters, lets smoothed axes settle,
+    /// plays timed taps and drives key repeat.
+    fn tick(&mut self, now: Instant) {
+        for (control, action) in self.filters.tick(now) {
+            self.handle_button(control, action, now);
+        }
+
+        self.play_timed_taps(now);
+
+        if self.keyboard.as_mut().is_some_and(|keyboard| keyboard.tick(now)) {
+            self.report_keyboard();
+        }
+
+        if let Some(output) = self.morse.as_mut().and_then(|morse| morse.tick(now)) {
+            self.type_morse(output);
+        }
+
+        if let Some(item) = self.scanner.as_mut().and_then(|scanner| scanner.tick(now)) {
+            self.fire_scan_item(item);
+        }
+        self.report_scan();
+
+        // Smoothed axes keep settling while the raw value holds still
+        let settled: Vec<(GamepadControl, i32, i32)> = self
+            .smoothers
+            .iter_mut()
+            .filter_map(|(&control, smoother)| {
+                smoother
+                    .tick(now)
+                    .map(|value| (control, smoother.raw(), value))
+            })
+            .collect();
+        for (control, raw, value) in settled {
+            self.feedback.push(ServerEvent::AxisSmoothed { control, raw, value });
+            self.handle_axis(control, value, now);
+        }
+
+        let sticks: Vec<(GamepadControl, i32)> = self
+            .joystick_states
+            .iter()
+            .map(|(&stick_key, &(x, _))| (stick_key, x))
+            .collect();
+        for (stick_key, x) in sticks {
+            self.handle_joystick_axis(stick_key, x, now);
+        }
+
+        let triggers: Vec<(GamepadControl, i32)> = self
+            .trigger_values
+            .iter()
+            .map(|(&control, &value)| (control, value))
+            .collect();
+        for (control, value) in triggers {
+            self.handle_trigger_axis(control, value, now);
+        }
+
+        self.output.tick(now);
+    }
+
+    /// True once `control` has kept its current keys for at least `min_hold`.
+    fn hold_expired(&self, control: GamepadControl, min_hold: Duration, now: Instant) -> bool {
+        self.changed_at
+            .get(&control)
+            .is_none_or(|&changed_at| now.duration_since(changed_at) >= min_hold)
+    }
+
+    /// Releases keys in `old_keys` that aren't in `new_keys`, then presses the
+    /// newly added ones, so a key shared by both directions stays down.
+    fn switch_keys(&mut self, holder: &str, old_keys: &[u32], new_keys: &[u32], toggle: bool) {
+        for &old_key in old_keys.iter().filter(|key| !new_keys.contains(key)) {
+            self.output.send(holder, old_key, KeyAction::Release, toggle);
+        }
+        for &new_key in new_keys.iter().filter(|key| !old_keys.contains(key)) {
+            self.output.send(holder, new_key, KeyAction::Press, toggle);
+        }
+    }
+
+    /// Returns false when the stick has no binding.
+    fn handle_joystick_axis(&mut self, control: GamepadControl, value: i32, now: Instant) -> bool {
+        // Determine the paired axis and use the X axis control as the key
+        let (is_x_axis, stick_key) = match control {
+            GamepadControl::LeftStickX => (true, GamepadControl::LeftStickX),
+            GamepadControl::LeftStickY => (false, GamepadControl::LeftStickX),
+            GamepadControl::RightStickX => (true, GamepadControl::RightStickX),
+            GamepadControl::RightStickY => (false, GamepadControl::RightStickX),
+            _ => return true,
+        };
+
+        if let Some(radial) = self.bindings.radial.get(&stick_key).cloned() {
+            let (x, y) = self.update_stick(stick_key, is_x_axis, value, radial.pipeline.center());
+            self.handle_radial(stick_key, &radial, x, y);
+            return true;
+        }
+
+        if let Some(center) = self.flick_sticks.get(&stick_key).map(FlickStick::center) {
+            let (x, y) = self.update_stick(stick_key, is_x_axis, value, center);
+            if let Some(mouse_event) = self
+                .flick_sticks
+                .get_mut(&stick_key)
+                .and_then(|flick| flick.update([x, y], now))
+            {
+                self.output.send_mouse(mouse_event);
             }
+            return true;
         }
-        x_key_pressed = new_x_key;
+
+        let Some(config) = self.bindings.joysticks.get(&stick_key).cloned() else {
+            return false;
+        };
+
+        let (x, y) = self.update_stick(stick_key, is_x_axis, value, config.pipeline.center());
+
+        let old_keys = self
+            .joystick_pressed
+            .get(&stick_key)
+            .cloned()
+            .unwrap_or_default();
+        let old_engaged = self
+            .joystick_engaged
+            .get(&stick_key)
+            .cloned()
+            .unwrap_or_default();
+
+        // Axes that already hold a key release at the lower release deadzone,
+        // also while PWM has the key up between pulses
+        let holds = |key: u32| match &config.run {
+            Some(run) => run.holds(&old_engaged, key),
+            None => old_engaged.contains(&key),
+        };
+        let held = match config.eight_way {
+            Some(_) => [!old_engaged.is_empty(); 2],
+            None => [
+                holds(config.left) || holds(config.right),
+                holds(config.up) || holds(config.down),
+            ],
+        };
+
+        // Deadzones, curves and the rest of the analog pipeline
+        let (out_x, out_y) = config.pipeline.process_held([x, y], held);
+
+        let new_keys = match config.eight_way {
+            Some(eight_way) if out_x != 0.0 || out_y != 0.0 => {
+                // 8-way: one sector of the circle
+                let direction = Direction8::from_vector_held(
+                    out_x,
+                    out_y,
+                    eight_way.diagonal_angle,
+                    self.joystick_directions.get(&stick_key).copied(),
+                    eight_way.sector_hysteresis,
+                );
+                self.joystick_directions.insert(stick_key, direction);
+                direction.keys(
+                    config.up,
+                    config.down,
+                    config.left,
+                    config.right,
+                    &eight_way,
+                )
+            }
+            Some(_) => {
+                self.joystick_directions.remove(&stick_key);
+                Vec::new()
+            }
+            None => {
+                // Each axis picks its own key independently
+                let x_key = if out_x > 0.0 {
+                    Some(config.right)
+                } else if out_x < 0.0 {
+                    Some(config.left)
+                } else {
+                    None
+                };
+                let y_key = if out_y > 0.0 {
+                    Some(config.down)
+                } else if out_y < 0.0 {
+                    Some(config.up)
+                } else {
+                    None
+                };
+
+                x_key.into_iter().chain(y_key).collect()
+            }
+        };
+
+        // Run stage: past the run radius, with its own hysteresis
+        let was_running = self.joystick_running.get(&stick_key).copied().unwrap_or(false);
+        let running = config.run.as_ref().is_some_and(|run| {
+            let radius = if was_running {
+                run.release_radius
+            } else {
+                run.radius
+            };
+            !new_keys.is_empty() && config.pipeline.deflection([x, y]) > radius
+        });
+        let engaged = match &config.run {
+            Some(run) if running => run.keys(&new_keys),
+            _ => new_keys,
+        };
+        let new_keys = match config.pwm {
+            // Running is full speed, so only walking pulses
+            Some(pwm) if !running => {
+                let eight_way = config.eight_way.is_some();
+                let horizontal = [config.left, config.right];
+                self.pulse(stick_key, pwm, engaged.clone(), now, |key| {
+                    if eight_way {
+                        out_x.hypot(out_y)
+                    } else if horizontal.contains(&key) {
+                        out_x.abs()
+                    } else {
+                        out_y.abs()
+                    }
+                })
+            }
+            _ => engaged.clone(),
+        };
+
+        if new_keys != old_keys && self.hold_expired(stick_key, config.min_hold, now) {
+            self.switch_keys(stick_name(stick_key), &old_keys, &new_keys, config.toggle);
+            self.joystick_pressed.insert(stick_key, new_keys);
+            self.joystick_engaged.insert(stick_key, engaged);
+            self.joystick_running.insert(stick_key, running);
+            self.changed_at.insert(stick_key, now);
+        } else if new_keys == old_keys {
+            // A run stage or PWM phase that changes no keys still moves the
+            // hysteresis
+            self.joystick_engaged.insert(stick_key, engaged);
+            self.joystick_running.insert(stick_key, running);
+        }
+        true
     }
 
-    // Handle Y axis
-    let new_y_key = if y_in_deadzone {
-        None
-    } else if y > 127 {
-        Some(config.down)
-    } else {
-        Some(config.up)
-    };
+    /// Stores a new value for one axis of a stick and returns the stick's
+    /// position, with the other axis defaulting to `center`.
+    fn update_stick(
+        &mut self,
+        stick_key: GamepadControl,
+        is_x_axis: bool,
+        value: i32,
+        center: i32,
+    ) -> (i32, i32) {
+        let (mut x, mut y) = self
+            .joystick_states
+            .get(&stick_key)
+            .copied()
+            .unwrap_or((center, center));
 
-    if new_y_key != y_key_pressed {
-        if let Some(old_key) = y_key_pressed {
-            let key_name = code_to_name(old_key);
-            match KeyInjector::inject(old_key, KeyAction::Release) {
-                Err(e) => {
-                    let _ = ui_tx.send(UIEvent::Error(format!(
-                        "Failed to release {}: {}",
-                        key_name, e
-                    )));
+        if is_x_axis {
+            x = value;
+        } else {
+            y = value;
+        }
+
+        self.joystick_states.insert(stick_key, (x, y));
+        (x, y)
+    }
+
+    /// Selects the sector a radial-menu stick points into, firing the last
+    /// one when the stick returns to the centre in `return` mode.
+    fn handle_radial(&mut self, stick_key: GamepadControl, radial: &RadialCodeConfig, x: i32, y: i32) {
+        let selected = self.radial_sectors.get(&stick_key).copied();
+        let (out_x, out_y) = radial
+            .pipeline
+            .process_held([x, y], [selected.is_some(); 2]);
+        let sector = (out_x != 0.0 || out_y != 0.0)
+            .then(|| radial_sector(out_x, out_y, radial.sectors.len()));
+
+        if sector == selected {
+            return;
+        }
+
+        if sector.is_none()
+            && radial.fire == RadialFire::Return
+            && let Some(selected) = selected
+        {
+            self.tap(stick_name(stick_key), radial.sectors[selected]);
+        }
+
+        match sector {
+            Some(sector) => self.radial_sectors.insert(stick_key, sector),
+            None => self.radial_sectors.remove(&stick_key),
+        };
+        self.report_radial(stick_key, radial, sector);
+    }
+
+    /// Fires the selected sector of every radial menu confirmed by `control`.
+    /// Returns false when `control` isn't a confirm button.
+    fn handle_radial_confirm(&mut self, control: GamepadControl, action: KeyAction) -> bool {
+        let menus: Vec<(GamepadControl, Option<u32>)> = self
+            .bindings
+            .radial
+            .iter()
+            .filter(|(_, radial)| {
+                radial.fire == RadialFire::Confirm && radial.confirm == Some(control)
+            })
+            .map(|(&stick_key, radial)| {
+                let key = self
+                    .radial_sectors
+                    .get(&stick_key)
+                    .map(|&sector| radial.sectors[sector]);
+                (stick_key, key)
+            })
+            .collect();
+
+        if action == KeyAction::Press {
+            for &(stick_key, key) in &menus {
+                if let Some(key) = key {
+                    self.tap(stick_name(stick_key), key);
                 }
-                Ok(_) => {
-                    let _ = ui_tx.send(UIEvent::KeyReleased(key_name.clone(), old_key));
+            }
+        }
+        !menus.is_empty()
+    }
+
+    fn report_radial(&mut self, stick_key: GamepadControl, radial: &RadialCodeConfig, sector: Option<usize>) {
+        let key_name = sector.map(|sector| code_to_name(radial.sectors[sector]));
+        let _ = self.ui_tx.send(UIEvent::RadialSector(
+            stick_name(stick_key).to_string(),
+            sector.zip(key_name.clone()),
+            radial.sectors.len(),
+        ));
+        self.feedback.push(ServerEvent::RadialSector {
+            control: stick_key,
+            sector: sector.map(|sector| sector as u8),
+            sectors: radial.sectors.len() as u8,
+            key: key_name,
+        });
+    }
+
+    /// Feeds an axis to the motion-input recognizers and taps the keys of
+    /// the longest pattern it completes, one after another, each held for
+    /// the pattern's hold time. Returns whether any recognizer watches the
+    /// axis.
+    fn handle_motion_inputs(&mut self, control: GamepadControl, value: i32, now: Instant) -> bool {
+        let mut watched = false;
+        let mut completed = None;
+        for (index, recognizer) in self.motion_inputs.iter_mut().enumerate() {
+            watched |= recognizer.watches(control);
+            if recognizer.axis(control, value, now) && completed.is_none() {
+                completed = Some(index);
+            }
+        }
+
+        if let Some(index) = completed {
+            let config = self.motion_inputs[index].config().clone();
+            for recognizer in &mut self.motion_inputs {
+                if recognizer.watches(control) {
+                    recognizer.clear();
                 }
             }
+            let holder = format!("Motion {}", config.pattern);
+            for (index, key) in config.keys.into_iter().enumerate() {
+                let down_at = now + config.hold * index as u32;
+                self.timed_taps.push(TimedTap {
+                    holder: holder.clone(),
+                    key,
+                    down_at,
+                    up_at: down_at + config.hold,
+                    down: false,
+                });
+            }
+            self.play_timed_taps(now);
+        }
+        watched
+    }
+
+    /// Presses and releases the timed taps that are due.
+    fn play_timed_taps(&mut self, now: Instant) {
+        let mut taps = std::mem::take(&mut self.timed_taps);
+        taps.retain_mut(|tap| {
+            if !tap.down && now >= tap.down_at {
+                self.output.send(&tap.holder, tap.key, KeyAction::Press, false);
+                tap.down = true;
+            }
+            if tap.down && now >= tap.up_at {
+                self.output.send(&tap.holder, tap.key, KeyAction::Release, false);
+                return false;
+            }
+            true
+        });
+        self.timed_taps = taps;
+    }
+
+    /// Returns true when the on-screen keyboard takes `control`.
+    fn handle_keyboard_button(&mut self, control: GamepadControl, action: KeyAction) -> bool {
+        let Some(keyboard) = self.keyboard.as_mut() else {
+            return false;
+        };
+        if !keyboard.consumes(control) {
+            return false;
+        }
+        let was_open = keyboard.is_open();
+        let output = keyboard.button(control, action);
+        if keyboard.is_open() && !was_open {
+            let controls = keyboard.controls();
+            self.release_bindings(&controls);
         }
-        if let Some(new_key) = new_y_key {
-            let key_name = code_to_name(new_key);
-            match KeyInjector::inject(new_key, KeyAction::Press) {
-                Err(e) => {
-                    let _ = ui_tx.send(UIEvent::Error(format!(
-                        "Failed to inject {}: {}",
-                        key_name, e
-                    )));
+        match output {
+            Some(KeyboardOutput::Text(text)) => self.output.send_text(&text),
+            Some(KeyboardOutput::Key(key)) => self.tap("Keyboard", key),
+            None => {}
+        }
+        self.report_keyboard();
+        true
+    }
+
+    /// Lets go of the keys `controls` hold through their normal bindings
+    /// as a text-entry mode takes them over. Their releases and stick
+    /// movements no longer reach those bindings, so the keys would stay
+    /// down. Latched toggle keys stay latched.
+    fn release_bindings(&mut self, controls: &[GamepadControl]) {
+        for &control in controls {
+            match control {
+                GamepadControl::LeftStickX | GamepadControl::RightStickX => {
+                    let toggle = self
+                        .bindings
+                        .joysticks
+                        .get(&control)
+                        .is_some_and(|config| config.toggle);
+                    let old_keys = self.joystick_pressed.remove(&control).unwrap_or_default();
+                    self.switch_keys(stick_name(control), &old_keys, &[], toggle);
+                    // Forgotten rather than re-evaluated on every tick
+                    self.joystick_engaged.remove(&control);
+                    self.joystick_states.remove(&control);
+                    self.joystick_directions.remove(&control);
+                    self.joystick_running.remove(&control);
+                    self.pwm_started.remove(&control);
                 }
-                Ok(_) => {
-                    let _ = ui_tx.send(UIEvent::KeyPressed(key_name.clone(), new_key));
+                GamepadControl::DPadX => {
+                    let toggle = self.bindings.dpad.is_some_and(|dpad| dpad.toggle);
+                    let old_keys = std::mem::take(&mut self.dpad_pressed);
+                    self.switch_keys("D-Pad", &old_keys, &[], toggle);
+                    self.dpad_state = None;
+                }
+                _ => {
+                    if let Some(button) = self.bindings.buttons.get(&control)
+                        && let ButtonAction::Key(key) = button.action
+                        && !button.toggle
+                    {
+                        self.output
+                            .send(&control.to_string(), key, KeyAction::Release, false);
+                    }
                 }
             }
         }
-        y_key_pressed = new_y_key;
     }
 
-    pressed.insert(stick_key, (x_key_pressed, y_key_pressed));
-}
+    /// Returns true when chord entry takes `control`.
+    fn handle_chord_button(&mut self, control: GamepadControl, action: KeyAction) -> bool {
+        let Some(chords) = self.chords.as_mut() else {
+            return false;
+        };
+        if !chords.consumes(control) {
+            return false;
+        }
+        let was_active = chords.is_active();
+        let chord = chords.button(control, action);
+        let active = chords.is_active();
 
-async fn handle_trigger_axis(
-    control: GamepadControl,
-    value: u8,
-    config: &TriggerCodeConfig,
-    states: &mut HashMap<GamepadControl, bool>,
-    ui_tx: mpsc::UnboundedSender<UIEvent>,
-) {
-    let was_pressed = states.get(&control).copied().unwrap_or(false);
-    let is_pressed = value > config.deadzone;
-
-    // Only inject on state change
-    if is_pressed != was_pressed {
-        let action = if is_pressed {
-            KeyAction::Press
-        } else {
-            KeyAction::Release
+        if active && !was_active {
+            let buttons = chords.buttons().to_vec();
+            self.release_bindings(&buttons);
+        }
+        if active != was_active {
+            let _ = self.ui_tx.send(UIEvent::Mode("Chords".to_string(), active));
+        }
+        match chord {
+            Some(Chord {
+                text: Some(text), ..
+            }) => self.output.send_text(&text),
+            Some(Chord { name, text: None }) => {
+                let _ = self.ui_tx.send(UIEvent::Unbound(format!("Chord {}", name)));
+            }
+            None => {}
+        }
+        true
+    }
+
+    /// Returns true when `control` is the Morse button.
+    fn handle_morse_button(
+        &mut self,
+        control: GamepadControl,
+        action: KeyAction,
+        now: Instant,
+    ) -> bool {
+        let Some(morse) = self.morse.as_mut() else {
+            return false;
         };
+        if !morse.watches(control) {
+            return false;
+        }
+        if morse.button(action, now) {
+            let _ = self.ui_tx.send(UIEvent::Morse(morse.view()));
+        }
+        true
+    }
 
-        let key_name = code_to_name(config.key);
-        match KeyInjector::inject(config.key, action) {
-            Err(e) => {
-                let _ = ui_tx.send(UIEvent::Error(format!(
-                    "Failed to inject {}: {}",
-                    key_name, e
-                )));
-            }
-            Ok(_) => {
-                let ui_event = match action {
-                    KeyAction::Press => UIEvent::KeyPressed(key_name.clone(), config.key),
-                    KeyAction::Release => UIEvent::KeyReleased(key_name.clone(), config.key),
-                };
-                let _ = ui_tx.send(ui_event);
+    fn type_morse(&mut self, output: MorseOutput) {
+        match output {
+            MorseOutput::Text(text) => self.output.send_text(&text),
+            MorseOutput::Unknown(pattern) => {
+                let _ = self
+                    .ui_tx
+                    .send(UIEvent::Unbound(format!("Morse {}", pattern)));
             }
         }
+        if let Some(morse) = &self.morse {
+            let _ = self.ui_tx.send(UIEvent::Morse(morse.view()));
+        }
+    }
 
-        states.insert(control, is_pressed);
+    fn report_keyboard(&mut self) {
+        let view = self.keyboard.as_ref().and_then(OnScreenKeyboard::view);
+        let _ = self.ui_tx.send(UIEvent::Keyboard(view));
     }
-}
 
-async fn handle_dpad_axis(
-    control: GamepadControl,
-    value: u8,
-    config: &DPadCodeConfig,
-    state: &mut Option<(u8, u8)>,
-    pressed: &mut Option<u32>,
-    ui_tx: mpsc::UnboundedSender<UIEvent>,
-) {
-    // D-Pad tracking
-    let (is_x_axis, _other_control) = match control {
-        GamepadControl::DPadX => (true, GamepadControl::DPadY),
-        GamepadControl::DPadY => (false, GamepadControl::DPadX),
-        _ => return,
-    };
+    /// Returns true when `control` is a scanning switch.
+    fn handle_scanning(&mut self, control: GamepadControl, action: KeyAction, now: Instant) -> bool {
+        let Some(scanner) = self.scanner.as_mut() else {
+            return false;
+        };
+        if !scanner.watches(control) {
+            return false;
+        }
+        if let Some(item) = scanner.button(control, action, now) {
+            self.fire_scan_item(item);
+        }
+        self.report_scan();
+        true
+    }
 
-    let (mut x, mut y) = state.unwrap_or((0, 0));
+    /// Taps an item's keys in order. Modifiers stay down over the keys after
+    /// them and come up in reverse order at the end, so `["LCTRL", "C"]`
+    /// sends Ctrl+C.
+    fn fire_scan_item(&mut self, item: usize) {
+        let Some(keys) = self
+            .scanner
+            .as_ref()
+            .map(|scanner| scanner.config().items[item].keys.clone())
+        else {
+            return;
+        };
+        let mut modifiers = Vec::new();
+        for key in keys {
+            if is_modifier(key) {
+                self.output.send("Scan", key, KeyAction::Press, false);
+                modifiers.push(key);
+            } else {
+                self.tap("Scan", key);
+            }
+        }
+        for key in modifiers.into_iter().rev() {
+            self.output.send("Scan", key, KeyAction::Release, false);
+        }
+    }
 
-    if is_x_axis {
-        x = value;
-    } else {
-        y = value;
+    /// Shows the scanning highlight in the TUI when it moved.
+    fn report_scan(&mut self) {
+        let highlight = self.scanner.as_ref().and_then(Scanner::highlight);
+        if highlight != self.scan_highlight {
+            self.scan_highlight = highlight;
+            let _ = self.ui_tx.send(UIEvent::ScanHighlight(highlight));
+        }
     }
 
-    *state = Some((x, y));
+    /// Presses and immediately releases `key`.
+    fn tap(&mut self, holder: &str, key: u32) {
+        self.output.send(holder, key, KeyAction::Press, false);
+        self.output.send(holder, key, KeyAction::Release, false);
+    }
 
-    // D-Pad: determine direction and send key
-    let new_key = if x != 0 {
-        // Horizontal input
-        if x > 127 {
-            Some(config.left)
-        } else {
-            Some(config.right)
+    /// Returns false when the trigger has no binding.
+    fn handle_trigger_axis(&mut self, control: GamepadControl, value: i32, now: Instant) -> bool {
+        let Some(config) = self.bindings.triggers.get(&control).cloned() else {
+            return false;
+        };
+        self.trigger_values.insert(control, value);
+
+        // Number of stages reached, each releasing below its own threshold
+        let old_level = self.trigger_states.get(&control).copied().unwrap_or(0);
+        let pull = config.pipeline.pull(value);
+        let new_level = stage_level(&config.stages, pull, old_level);
+
+        let new_keys = stage_keys(&config.stages, new_level);
+        let new_keys = match config.pwm {
+            Some(pwm) => {
+                // Pulsing starts at the first stage, not the trigger deadzone
+                let duty = config.stages.first().map_or(0.0, |first| {
+                    let threshold = if old_level > 0 {
+                        first.release
+                    } else {
+                        first.threshold
+                    };
+                    config.pipeline.process_past(value, threshold)
+                });
+                self.pulse(control, pwm, new_keys, now, |_| duty)
+            }
+            None => new_keys,
+        };
+
+        // Only inject on change
+        let old_keys = self.trigger_pressed.get(&control).cloned().unwrap_or_default();
+        if (new_keys != old_keys || new_level != old_level)
+            && self.hold_expired(control, config.min_hold, now)
+        {
+            self.switch_keys(&control.to_string(), &old_keys, &new_keys, config.toggle);
+            self.trigger_states.insert(control, new_level);
+            self.trigger_pressed.insert(control, new_keys);
+            self.changed_at.insert(control, now);
         }
-    } else if y != 0 {
-        // Vertical input
-        if y > 127 {
-            Some(config.up)
+        true
+    }
+
+    /// Keeps the keys of `keys` that are in the on part of their PWM cycle,
+    /// each at the duty cycle `duty` gives it. The cycle restarts whenever
+    /// the control goes back to rest.
+    fn pulse(
+        &mut self,
+        control: GamepadControl,
+        pwm: Pwm,
+        keys: Vec<u32>,
+        now: Instant,
+        duty: impl Fn(u32) -> f32,
+    ) -> Vec<u32> {
+        if keys.is_empty() {
+            self.pwm_started.remove(&control);
+            return keys;
+        }
+
+        let started = *self.pwm_started.entry(control).or_insert(now);
+        let elapsed = now.duration_since(started);
+        keys.into_iter()
+            .filter(|&key| pwm.is_on(duty(key), elapsed))
+            .collect()
+    }
+
+    /// Returns false when the D-Pad has no binding.
+    fn handle_dpad_axis(&mut self, control: GamepadControl, value: u8) -> bool {
+        let Some(config) = self.bindings.dpad else {
+            return false;
+        };
+
+        // D-Pad tracking
+        let is_x_axis = match control {
+            GamepadControl::DPadX => true,
+            GamepadControl::DPadY => false,
+            _ => return true,
+        };
+
+        let (mut x, mut y) = self.dpad_state.unwrap_or((0, 0));
+
+        if is_x_axis {
+            x = value;
         } else {
-            Some(config.down)
-        }
-    } else {
-        // Neutral, release any pressed key
-        None
-    };
-
-    // Only inject if key state changed
-    if new_key != *pressed {
-        if let Some(old_key) = *pressed {
-            let key_name = code_to_name(old_key);
-            match KeyInjector::inject(old_key, KeyAction::Release) {
-                Err(e) => {
-                    let _ = ui_tx.send(UIEvent::Error(format!(
-                        "Failed to release {}: {}",
-                        key_name, e
-                    )));
-                }
-                Ok(_) => {
-                    let _ = ui_tx.send(UIEvent::KeyReleased(key_name.clone(), old_key));
-                }
-            }
+            y = value;
         }
-        if let Some(new_key_code) = new_key {
-            let key_name = code_to_name(new_key_code);
-            match KeyInjector::inject(new_key_code, KeyAction::Press) {
-                Err(e) => {
-                    let _ = ui_tx.send(UIEvent::Error(format!(
-                        "Failed to inject {}: {}",
-                        key_name, e
-                    )));
-                }
-                Ok(_) => {
-                    let _ = ui_tx.send(UIEvent::KeyPressed(key_name.clone(), new_key_code));
-                }
+
+        self.dpad_state = Some((x, y));
+
+        // D-Pad: determine direction and send key
+        let new_keys = if let Some(eight_way) = config.eight_way {
+            // 8-way: diagonals hold both keys or their dedicated key
+            Direction8::from_dpad(x as i8, y as i8)
+                .map(|direction| {
+                    direction.keys(config.up, config.down, config.left, config.right, &eight_way)
+                })
+                .unwrap_or_default()
+        } else if x != 0 {
+            // Horizontal input
+            if x > 127 {
+                vec![config.left]
+            } else {
+                vec![config.right]
+            }
+        } else if y != 0 {
+            // Vertical input
+            if y > 127 {
+                vec![config.up]
+            } else {
+                vec![config.down]
             }
+        } else {
+            // Neutral, release any pressed key
+            Vec::new()
+        };
+
+        // Only inject if key state changed
+        if new_keys != self.dpad_pressed {
+            let old_keys = std::mem::take(&mut self.dpad_pressed);
+            self.switch_keys("D-Pad", &old_keys, &new_keys, config.toggle);
+            self.dpad_pressed = new_keys;
         }
-        *pressed = new_key;
+        true
     }
 }
 
-fn code_to_name(code: u32) -> String {
-    match code {
-        0x01 => "LButton".to_string(),
-        0x02 => "RButton".to_string(),
-        0x04 => "MButton".to_string(),
-        0x05 => "XButton1".to_string(),
-        0x06 => "XButton2".to_string(),
-        0x08 => "Backspace".to_string(),
-        0x09 => "Tab".to_string(),
-        0x0C => "Clear".to_string(),
-        0x0D => "Enter".to_string(),
-        0x10 => "Shift".to_string(),
-        0x11 => "Ctrl".to_string(),
-        0x12 => "Alt".to_string(),
-        0x13 => "Pause".to_string(),
-        0x14 => "CapsLock".to_string(),
-        0x1B => "Esc".to_string(),
-        0x20 => "Space".to_string(),
-        0x21 => "PageUp".to_string(),
-        0x22 => "PageDown".to_string(),
-        0x23 => "End".to_string(),
-        0x24 => "Home".to_string(),
-        0x25 => "Left".to_string(),
-        0x26 => "Up".to_string(),
-        0x27 => "Right".to_string(),
-        0x28 => "Down".to_string(),
-        0x2C => "PrintScreen".to_string(),
-        0x2D => "Insert".to_string(),
-        0x2E => "Delete".to_string(),
-        0x30..=0x39 => format!("{}", code - 0x30),
-        0x41..=0x5A => format!("{}", (code as u8 as char)),
-        0x60..=0x69 => format!("Numpad{}", code - 0x60),
-        0x70..=0x87 => format!("F{}", code - 0x70 + 1),
-        0x90 => "NumLock".to_string(),
-        0x91 => "ScrollLock".to_string(),
-        0xA0 => "LShift".to_string(),
-        0xA1 => "RShift".to_string(),
-        0xA2 => "LAlt".to_string(),
-        0xA3 => "RAlt".to_string(),
-        0xAD => "VolumeMute".to_string(),
-        0xAE => "VolumeDown".to_string(),
-        0xAF => "VolumeUp".to_string(),
-        0xB0 => "MediaNextTrack".to_string(),
-        0xB1 => "MediaPrevTrack".to_string(),
-        0xB2 => "MediaStop".to_string(),
-        0xB3 => "MediaPlayPause".to_string(),
-        0xBA => ";".to_string(),
-        0xBB => "=".to_string(),
-        0xBC => ",".to_string(),
-        0xBD => "-".to_string(),
-        0xBE => ".".to_string(),
-        0xBF => "/".to_string(),
-        0xC0 => "`".to_string(),
-        0xDB => "[".to_string(),
-        0xDC => "\\".to_string(),
-        0xDD => "]".to_string(),
-        0xDE => "'".to_string(),
-        _ => format!("Unknown(0x{:02X})", code),
+/// Holder name for a stick, keyed by its X axis like the rest of the state.
+fn stick_name(stick_key: GamepadControl) -> &'static str {
+    match stick_key {
+        GamepadControl::RightStickX => "Right Stick",
+        _ => "Left Stick",
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::Config;
+    use crate::keycode::KeyCode;
+    use crate::testing::{Clock, Recorder, Step};
+    use GamepadControl::{Circle, Cross, DPadX, DPadY, L1, LeftStickY, R2, Select, Touch};
+    use KeyAction::{Press, Release};
+    use bouton_core::{ControlAxis, ControlButton};
 
-    #[test]
-    fn test_adaptive_deadzone_at_center() {
-        let base_deadzone = 20i16;
-        let center_diff = 0i16;
-        let perpendicular_diff = 0i16;
+    /// A mapper with a recording backend and its own clock.
+    struct Harness {
+        mapper: Mapper<Recorder>,
+        clock: Clock,
+    }
+
+    impl Harness {
+        /// Maps with a config that leaves out the `[server]` table.
+        fn new(config: &str) -> Self {
+            let config: Config = toml::from_str(&format!(
+                "[server]\nlisten_addr = \"127.0.0.1\"\nlisten_port = 0\n{}",
+                config
+            ))
+            .unwrap();
+            let (ui_tx, _ui_rx) = mpsc::unbounded_channel();
+            Self {
+                mapper: Mapper::new(crate::bindings(&config), Recorder::default(), ui_tx),
+                clock: Clock::default(),
+            }
+        }
+
+        /// Plays `(milliseconds, event)` control events up to `end`,
+        /// carrying on from the last call.
+        fn play(&mut self, trace: &[(u64, ControlEvent)], end: u64) {
+            let mapper = &mut self.mapper;
+            self.clock.play(trace, end, |_, now, step| match step {
+                Step::Event(event) => mapper.handle_event(event, now),
+                Step::Tick => mapper.tick(now),
+            });
+        }
 
-        let in_deadzone = compute_adaptive_deadzone(center_diff, perpendicular_diff, base_deadzone);
-        assert!(in_deadzone, "At center (0, 0), should be in deadzone");
+        /// Keys currently held down.
+        fn down(&self) -> Vec<u32> {
+            self.mapper.output.backend().down()
+        }
     }
 
+    fn button(control: GamepadControl, action: KeyAction) -> ControlEvent {
+        ControlEvent::Button(ControlButton { control, action })
+    }
+
+    fn axis(control: GamepadControl, value: i32) -> ControlEvent {
+        ControlEvent::Axis(ControlAxis { control, value })
+    }
+
+    const STICK: &str = "[keys.joysticks.LeftStick]\n\
+                         deadzone = 20\nup = \"W\"\ndown = \"S\"\nleft = \"A\"\nright = \"D\"\n";
+
     #[test]
-    fn test_adaptive_deadzone_just_outside_base() {
-        let base_deadzone = 20i16;
-        let axis_diff = 21i16;
-        let perpendicular_diff = 0i16;
-
-        let in_deadzone = compute_adaptive_deadzone(axis_diff, perpendicular_diff, base_deadzone);
-        assert!(
-            !in_deadzone,
-            "At 21 with base deadzone of 20 and perpendicular at center, should be outside deadzone"
+    fn buttons_reach_their_keys_through_the_filters() {
+        let mut harness =
+            Harness::new("[keys.buttons]\nCross = \"A\"\n[filters]\nCross = { min_hold_ms = 80 }");
+        harness.play(
+            &[(0, button(Cross, Press)), (10, button(Cross, Release))],
+            50,
         );
+        assert_eq!(harness.down(), vec![KeyCode::A.code()]);
+        harness.play(&[], 100);
+        assert!(harness.down().is_empty());
     }
 
     #[test]
-    fn test_adaptive_deadzone_scales_all_directions() {
-        let base_deadzone = 20i16;
-        let max_deflection = 128i16; // Maximum perpendicular deflection
-
-        // Dynamic deadzone at max perpendicular: ceil(85 * (128/128)) = 85
-        // Effective = max(20, 85) = 85
-        // So axis_diff of 84 should be inside (84 < 85)
-        let inside_dynamic = compute_adaptive_deadzone(84, max_deflection, base_deadzone);
-        assert!(inside_dynamic, "84 should be inside dynamic deadzone of 85");
-
-        // axis_diff of 85 should be outside (85 < 85 is false)
-        let outside_dynamic = compute_adaptive_deadzone(85, max_deflection, base_deadzone);
-        assert!(
-            !outside_dynamic,
-            "85 should be outside dynamic deadzone of 85"
+    fn opening_the_keyboard_lets_go_of_the_controls_it_takes() {
+        let mut harness = Harness::new(&format!(
+            "[keys.buttons]\nCross = \"J\"\n{}\
+             [keyboard]\ntoggle = \"Touch\"\nnavigate = \"LeftStick\"\nselect = \"Cross\"",
+            STICK
+        ));
+        harness.play(&[(0, button(Cross, Press)), (0, axis(LeftStickY, 0))], 20);
+        assert_eq!(harness.down(), vec![KeyCode::J.code(), KeyCode::W.code()]);
+
+        // Opening releases both, and ticks don't bring the stick's key back
+        harness.play(
+            &[(50, button(Touch, Press)), (60, button(Touch, Release))],
+            200,
         );
+        assert!(harness.down().is_empty());
 
-        // Test all four directions: forward, backward, left, right
-        for direction in &["forward", "backward", "left", "right"] {
-            let in_deadzone = compute_adaptive_deadzone(84, max_deflection, base_deadzone);
-            assert!(
-                in_deadzone,
-                "When pushing {}, deflection of 84 should be in dynamic deadzone",
-                direction
-            );
-        }
+        // Their releases go to the keyboard, and once it is closed the
+        // bindings work again
+        harness.play(
+            &[
+                (250, button(Cross, Release)),
+                (260, axis(LeftStickY, 127)),
+                (300, button(Touch, Press)),
+                (310, button(Touch, Release)),
+                (400, axis(LeftStickY, 0)),
+                (450, button(Cross, Press)),
+            ],
+            500,
+        );
+        assert_eq!(harness.down(), vec![KeyCode::W.code(), KeyCode::J.code()]);
+        assert!(harness.mapper.output.backend().text.is_empty());
     }
 
     #[test]
-    fn test_base_deadzone_at_center() {
-        let base_deadzone = 20i16;
+    fn switching_chords_on_lets_go_of_the_chord_buttons() {
+        let mut harness = Harness::new(
+            "[keys.buttons]\nCross = \"J\"\nL1 = \"K\"\n[chords]\ntoggle = \"Select\"",
+        );
+        harness.play(&[(0, button(Cross, Press)), (0, button(L1, Press))], 20);
+        harness.play(
+            &[(50, button(Select, Press)), (60, button(Select, Release))],
+            100,
+        );
+        assert!(harness.down().is_empty());
+
+        // The old presses' releases make no chord
+        harness.play(
+            &[(150, button(Cross, Release)), (160, button(L1, Release))],
+            200,
+        );
+        assert!(harness.mapper.output.backend().text.is_empty());
+        harness.play(
+            &[(250, button(Cross, Press)), (260, button(Cross, Release))],
+            300,
+        );
+        assert_eq!(harness.mapper.output.backend().text, "e");
+    }
 
-        // At center, dynamic deadzone = 0, so effective = max(20, 0) = 20
-        assert!(
-            compute_adaptive_deadzone(19, 0, base_deadzone),
-            "19 should be in deadzone"
+    #[test]
+    fn motion_inputs_hold_each_key_in_turn() {
+        const U: u32 = 0x55;
+        const I: u32 = 0x49;
+        let mut harness = Harness::new(
+            "[keys.buttons]\n[[motion_inputs]]\nsource = \"DPad\"\npattern = \"236\"\n\
+             keys = [\"U\", \"I\"]\nhold_ms = 40\n",
         );
-        assert!(
-            !compute_adaptive_deadzone(20, 0, base_deadzone),
-            "20 should be outside deadzone"
+        // Down, down-forward, forward
+        harness.play(
+            &[
+                (0, axis(DPadY, 1)),
+                (20, axis(DPadX, 1)),
+                (40, axis(DPadY, 0)),
+            ],
+            40,
         );
+        assert_eq!(harness.down(), vec![U]);
+        harness.play(&[], 75);
+        assert_eq!(harness.down(), vec![U]);
+        harness.play(&[], 80);
+        assert_eq!(harness.down(), vec![I]);
+        harness.play(&[], 120);
+        assert!(harness.down().is_empty());
+    }
+
+    #[test]
+    fn scan_macros_hold_their_modifiers() {
+        const LCTRL: u32 = 0xA2;
+        const LSHIFT: u32 = 0xA0;
+        const C: u32 = 0x43;
+        let mut harness = Harness::new(
+            "[keys.buttons]\n[scanning]\nmode = \"step\"\nselect = \"Cross\"\nstep = \"Circle\"\n\
+             items = [{ label = \"Copy\", keys = [\"LCTRL\", \"LSHIFT\", \"C\"] }]",
+        );
+        harness.play(
+            &[(0, button(Circle, Press)), (10, button(Cross, Press))],
+            20,
+        );
+        assert_eq!(
+            harness.mapper.output.backend().keys,
+            vec![
+                (LCTRL, Press),
+                (LSHIFT, Press),
+                (C, Press),
+                (C, Release),
+                (LSHIFT, Release),
+                (LCTRL, Release),
+            ]
+        );
+    }
+
+    #[test]
+    fn staged_triggers_pulse_from_their_first_stage() {
+        const A: u32 = 0x41;
+        let mut harness = Harness::new(
+            "[keys.buttons]\n[keys.triggers.R2]\nstages = [{ key = \"A\", threshold = 0.3 }]\n\
+             [keys.triggers.R2.pwm]\nperiod_ms = 100\nmin_pulse_ms = 20",
+        );
+        // About 0.39 of the pull, under the default single-key deadzone
+        harness.play(&[(0, axis(R2, 100))], 500);
+        let presses = harness.mapper.output.backend().keys.iter();
+        assert_eq!(presses.filter(|&&event| event == (A, Press)).count(), 5);
+    }
+
+    #[test]
+    fn pulsing_sticks_keep_their_release_deadzone() {
+        let mut harness = Harness::new(&format!(
+            "[keys.buttons]\n{}hysteresis = 10\n\
+             [keys.joysticks.LeftStick.analog]\ndeadzone_shape = \"axial\"\n\
+             [keys.joysticks.LeftStick.pwm]\nperiod_ms = 100\nmin_pulse_ms = 20",
+            STICK
+        ));
+        let w = KeyCode::W.code();
+        let pulses = |harness: &Harness| {
+            harness
+                .mapper
+                .output
+                .backend()
+                .keys
+                .iter()
+                .filter(|&&key| key == (w, Press))
+                .count()
+        };
+        // Pushed past the deadzone: one short pulse per period
+        harness.play(&[(0, axis(LeftStickY, 87))], 140);
+        assert_eq!(pulses(&harness), 2);
+
+        // Eased back inside the deadzone but not the release deadzone,
+        // during a gap between pulses: the stick still walks
+        harness.play(&[(150, axis(LeftStickY, 112))], 340);
+        assert_eq!(pulses(&harness), 4);
+
+        harness.play(&[(350, axis(LeftStickY, 127))], 500);
+        assert_eq!(pulses(&harness), 4);
+        assert!(harness.down().is_empty());
     }
 }
@@ -0,0 +1,102 @@
+//! Helpers shared by the unit tests.
+
+use crate::key_injector::{InputBackend, MouseEvent};
+use crate::socket_server::TICK_INTERVAL;
+use bouton_core::KeyAction;
+use std::time::{Duration, Instant};
+
+/// Backend that records everything sent instead of injecting it.
+#[derive(Debug, Default)]
+pub struct Recorder {
+    pub keys: Vec<(u32, KeyAction)>,
+    pub mouse: Vec<MouseEvent>,
+    pub text: String,
+}
+
+impl Recorder {
+    /// Keys pressed and not released again, in the order they went down.
+    pub fn down(&self) -> Vec<u32> {
+        let mut down = Vec::new();
+        for &(key, action) in &self.keys {
+            match action {
+                KeyAction::Press if !down.contains(&key) => down.push(key),
+                KeyAction::Press => {}
+                KeyAction::Release => down.retain(|&held| held != key),
+            }
+        }
+        down
+    }
+}
+
+impl InputBackend for Recorder {
+    fn send_key(&mut self, key_code: u32, action: KeyAction) -> Result<(), String> {
+        self.keys.push((key_code, action));
+        Ok(())
+    }
+
+    fn send_mouse(&mut self, event: MouseEvent) -> Result<(), String> {
+        self.mouse.push(event);
+        Ok(())
+    }
+
+    fn send_text(&mut self, text: &str) -> Result<(), String> {
+        self.text.push_str(text);
+        Ok(())
+    }
+}
+
+/// What happens at one point of a played trace.
+pub enum Step<E> {
+    Event(E),
+    /// The server's periodic tick, after the events due by then
+    Tick,
+}
+
+/// The server's tick clock, for traces played in several parts.
+pub struct Clock {
+    start: Instant,
+    /// Milliseconds of the next tick
+    next_tick: u64,
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+            next_tick: 0,
+        }
+    }
+}
+
+impl Clock {
+    /// Plays `(milliseconds, event)` pairs, in time order, ticking every
+    /// `TICK_INTERVAL` from where the last part stopped up to `end`
+    /// milliseconds. Each event is handed to `step` at the first tick at or
+    /// after its time, ahead of that tick, along with the milliseconds since
+    /// the start and the matching instant.
+    pub fn play<E: Clone>(
+        &mut self,
+        trace: &[(u64, E)],
+        end: u64,
+        mut step: impl FnMut(u64, Instant, Step<E>),
+    ) {
+        let interval = TICK_INTERVAL.as_millis() as usize;
+        let mut next = 0;
+        for ms in (self.next_tick..=end).step_by(interval) {
+            let now = self.start + Duration::from_millis(ms);
+            while let Some((at, event)) = trace.get(next)
+                && *at <= ms
+            {
+                step(ms, now, Step::Event(event.clone()));
+                next += 1;
+            }
+            step(ms, now, Step::Tick);
+            self.next_tick = ms + interval as u64;
+        }
+    }
+}
+
+/// Plays a whole trace on a fresh clock, see `Clock::play`.
+pub fn play<E: Clone>(trace: &[(u64, E)], end: u64, step: impl FnMut(u64, Instant, Step<E>)) {
+    Clock::default().play(trace, end, step)
+}
@@ -0,0 +1,322 @@
+use crate::config::TouchpadCodeConfig;
+use crate::key_injector::{MouseButton, MouseEvent};
+use bouton_core::{ControlTouch, KeyAction};
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+/// What the fingers currently on the pad are doing. Decided when they land
+/// and kept until the last one lifts, so lifting one finger of a scroll
+/// doesn't make the cursor jump.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Gesture {
+    Cursor,
+    Scroll,
+    /// One finger in the right edge strip scrolls vertically
+    EdgeVertical,
+    /// One finger in the bottom edge strip scrolls horizontally
+    EdgeHorizontal,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Finger {
+    start: (i32, i32),
+    last: (i32, i32),
+}
+
+/// Turns touchpad fingers into mouse output: one finger moves the cursor,
+/// two scroll, and a short touch that barely moved clicks (one finger for
+/// the left button, two for the right, three for the middle).
+pub struct Trackpad {
+    config: TouchpadCodeConfig,
+    fingers: BTreeMap<u8, Finger>,
+    gesture: Gesture,
+    touch_started: Option<Instant>,
+    most_fingers: usize,
+    moved: bool,
+    cursor_remainder: (f32, f32),
+    scroll_remainder: (f32, f32),
+}
+
+impl Trackpad {
+    pub fn new(config: TouchpadCodeConfig) -> Self {
+        Self {
+            config,
+            fingers: BTreeMap::new(),
+            gesture: Gesture::Cursor,
+            touch_started: None,
+            most_fingers: 0,
+            moved: false,
+            cursor_remainder: (0.0, 0.0),
+            scroll_remainder: (0.0, 0.0),
+        }
+    }
+
+    pub fn touch(&mut self, touch: ControlTouch, now: Instant) -> Vec<MouseEvent> {
+        let Some(position) = touch.position else {
+            return self.lift(touch.slot, now);
+        };
+
+        let Some(finger) = self.fingers.get_mut(&touch.slot) else {
+            self.land(touch.slot, position, now);
+            return Vec::new();
+        };
+
+        let delta = (position.0 - finger.last.0, position.1 - finger.last.1);
+        finger.last = position;
+        let travel =
+            ((position.0 - finger.start.0) as f32).hypot((position.1 - finger.start.1) as f32);
+        if travel > self.config.tap_distance {
+            self.moved = true;
+        }
+
+        let fingers = self.fingers.len() as f32;
+        let (dx, dy) = (delta.0 as f32, delta.1 as f32);
+        match self.gesture {
+            Gesture::Cursor => self.move_cursor(dx, dy),
+            // Each finger reports its own movement, so average them
+            Gesture::Scroll => self.scroll(dx / fingers, dy / fingers),
+            Gesture::EdgeVertical => self.scroll(0.0, dy),
+            Gesture::EdgeHorizontal => self.scroll(dx, 0.0),
+        }
+    }
+
+    fn land(&mut self, slot: u8, position: (i32, i32), now: Instant) {
+        if self.fingers.is_empty() {
+            self.touch_started = Some(now);
+            self.most_fingers = 0;
+            self.moved = false;
+            self.cursor_remainder = (0.0, 0.0);
+            self.scroll_remainder = (0.0, 0.0);
+            self.gesture = self.edge_gesture(position).unwrap_or(Gesture::Cursor);
+        }
+
+        self.fingers.insert(
+            slot,
+            Finger {
+                start: position,
+                last: position,
+            },
+        );
+        self.most_fingers = self.most_fingers.max(self.fingers.len());
+        if self.fingers.len() >= 2 {
+            self.gesture = Gesture::Scroll;
+        }
+    }
+
+    fn lift(&mut self, slot: u8, now: Instant) -> Vec<MouseEvent> {
+        if self.fingers.remove(&slot).is_none() || !self.fingers.is_empty() {
+            return Vec::new();
+        }
+
+        let is_tap = !self.moved
+            && self
+                .touch_started
+                .is_some_and(|started| now.duration_since(started) <= self.config.tap_time);
+        let button = match self.most_fingers {
+            1 => MouseButton::Left,
+            2 => MouseButton::Right,
+            _ => MouseButton::Middle,
+        };
+
+        if is_tap {
+            vec![
+                MouseEvent::Button(button, KeyAction::Press),
+                MouseEvent::Button(button, KeyAction::Release),
+            ]
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Edge-scroll gesture for a first finger landing in an edge strip.
+    fn edge_gesture(&self, (x, y): (i32, i32)) -> Option<Gesture> {
+        let fraction =
+            |value: i32, [min, max]: [i32; 2]| (value - min) as f32 / (max - min).max(1) as f32;
+
+        if self.config.edge_scroll_right > 0.0
+            && fraction(x, self.config.range_x) >= 1.0 - self.config.edge_scroll_right
+        {
+            Some(Gesture::EdgeVertical)
+        } else if self.config.edge_scroll_bottom > 0.0
+            && fraction(y, self.config.range_y) >= 1.0 - self.config.edge_scroll_bottom
+        {
+            Some(Gesture::EdgeHorizontal)
+        } else {
+            None
+        }
+    }
+
+    fn move_cursor(&mut self, dx: f32, dy: f32) -> Vec<MouseEvent> {
+        let x = dx * self.config.sensitivity + self.cursor_remainder.0;
+        let y = dy * self.config.sensitivity + self.cursor_remainder.1;
+        self.cursor_remainder = (x.fract(), y.fract());
+
+        let (dx, dy) = (x.trunc() as i32, y.trunc() as i32);
+        if dx == 0 && dy == 0 {
+            return Vec::new();
+        }
+        vec![MouseEvent::Move { dx, dy }]
+    }
+
+    fn scroll(&mut self, dx: f32, dy: f32) -> Vec<MouseEvent> {
+        // Fingers moving up scroll up, unless scrolling is natural
+        let direction = if self.config.natural_scroll {
+            1.0
+        } else {
+            -1.0
+        };
+        let step = self.config.scroll_step.max(1.0);
+        let x = dx / step + self.scroll_remainder.0;
+        let y = dy * direction / step + self.scroll_remainder.1;
+        self.scroll_remainder = (x.fract(), y.fract());
+
+        let mut events = Vec::new();
+        if y.trunc() != 0.0 {
+            events.push(MouseEvent::Wheel {
+                notches: y.trunc() as i32,
+                horizontal: false,
+            });
+        }
+        if x.trunc() != 0.0 {
+            events.push(MouseEvent::Wheel {
+                notches: x.trunc() as i32,
+                horizontal: true,
+            });
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::TouchpadConfig;
+    use crate::testing::{self, Step};
+
+    fn trackpad() -> Trackpad {
+        Trackpad::new(
+            TouchpadConfig {
+                edge_scroll_right: Some(0.1),
+                ..Default::default()
+            }
+            .codes(),
+        )
+    }
+
+    /// `(milliseconds, slot, position)` touches
+    type Trace = [(u64, u8, Option<(i32, i32)>)];
+
+    fn play(trackpad: &mut Trackpad, trace: &Trace) -> Vec<MouseEvent> {
+        let trace: Vec<_> = trace
+            .iter()
+            .map(|&(ms, slot, position)| (ms, ControlTouch { slot, position }))
+            .collect();
+        let end = trace.last().map_or(0, |&(ms, _)| ms);
+        let mut events = Vec::new();
+        testing::play(&trace, end, |_, now, step| {
+            if let Step::Event(touch) = step {
+                events.extend(trackpad.touch(touch, now));
+            }
+        });
+        events
+    }
+
+    #[test]
+    fn one_finger_moves_the_cursor() {
+        let events = play(
+            &mut trackpad(),
+            &[
+                (0, 0, Some((500, 500))),
+                (10, 0, Some((520, 490))),
+                (20, 0, Some((521, 490))),
+                (30, 0, Some((522, 490))),
+                (300, 0, None),
+            ],
+        );
+        assert_eq!(
+            events,
+            vec![
+                MouseEvent::Move { dx: 10, dy: -5 },
+                MouseEvent::Move { dx: 1, dy: 0 },
+            ]
+        );
+    }
+
+    #[test]
+    fn taps_click_by_finger_count() {
+        let left = play(
+            &mut trackpad(),
+            &[
+                (0, 0, Some((500, 500))),
+                (5, 0, Some((501, 500))),
+                (80, 0, None),
+            ],
+        );
+        assert_eq!(
+            left,
+            vec![
+                MouseEvent::Button(MouseButton::Left, KeyAction::Press),
+                MouseEvent::Button(MouseButton::Left, KeyAction::Release),
+            ]
+        );
+
+        let right = play(
+            &mut trackpad(),
+            &[
+                (0, 0, Some((500, 500))),
+                (10, 1, Some((800, 500))),
+                (90, 0, None),
+                (100, 1, None),
+            ],
+        );
+        assert_eq!(
+            right[0],
+            MouseEvent::Button(MouseButton::Right, KeyAction::Press)
+        );
+
+        // Too slow to be a tap
+        let slow = play(&mut trackpad(), &[(0, 0, Some((500, 500))), (400, 0, None)]);
+        assert!(slow.is_empty());
+    }
+
+    #[test]
+    fn two_fingers_scroll() {
+        let events = play(
+            &mut trackpad(),
+            &[
+                (0, 0, Some((500, 500))),
+                (0, 1, Some((800, 500))),
+                (10, 0, Some((500, 400))),
+                (10, 1, Some((800, 400))),
+                (20, 1, None),
+                // The remaining finger still scrolls instead of moving the cursor
+                (30, 0, Some((500, 350))),
+                (40, 0, None),
+            ],
+        );
+        let up = MouseEvent::Wheel {
+            notches: 1,
+            horizontal: false,
+        };
+        assert_eq!(events, vec![up, up, up]);
+    }
+
+    #[test]
+    fn right_edge_scrolls_vertically() {
+        let events = play(
+            &mut trackpad(),
+            &[
+                (0, 0, Some((1850, 300))),
+                (10, 0, Some((1850, 400))),
+                (20, 0, None),
+            ],
+        );
+        assert_eq!(
+            events,
+            vec![MouseEvent::Wheel {
+                notches: -2,
+                horizontal: false,
+            }]
+        );
+    }
+}
@@ -1,11 +1,15 @@
+use crate::keyboard::KeyboardView;
+use crate::morse::MorseView;
+use crate::output::StickyState;
+use crate::scanning::Highlight;
 use ratatui::{
     Frame,
     layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Style},
+    style::{Color, Modifier, Style},
     text::{Line, Span},
     widgets::{Block, Borders, Paragraph},
 };
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
 
 const MAX_LOG_LINES: usize = 10;
 
@@ -19,6 +23,19 @@ pub struct KeyInjectionState {
     pub last_key_code: Option<u32>,
     pub last_key_name: Option<String>,
     pub last_action: Option<String>,
+    pub latched: BTreeMap<u32, String>,
+    /// Sticky modifiers currently latched or locked
+    pub sticky: BTreeMap<u32, (String, StickyState)>,
+    pub holders: BTreeMap<u32, (String, Vec<String>)>,
+    /// On-screen keyboard while it is open
+    pub keyboard: Option<KeyboardView>,
+    /// Morse entry, when it is set up
+    pub morse: Option<MorseView>,
+    /// Switch-scanning item labels and columns, when scanning is set up
+    pub scan_items: Option<(Vec<String>, Option<usize>)>,
+    pub scan_highlight: Option<Highlight>,
+    /// Radial-menu sticks with their selected sector and key, and sector count
+    pub radial: BTreeMap<String, (Option<(usize, String)>, usize)>,
     pub log: VecDeque<String>,
     pub client_state: ClientState,
     pub client_addr: Option<String>,
@@ -30,6 +47,14 @@ impl KeyInjectionState {
             last_key_code: None,
             last_key_name: None,
             last_action: None,
+            latched: BTreeMap::new(),
+            sticky: BTreeMap::new(),
+            holders: BTreeMap::new(),
+            keyboard: None,
+            morse: None,
+            scan_items: None,
+            scan_highlight: None,
+            radial: BTreeMap::new(),
             log: VecDeque::new(),
             client_state: ClientState::Waiting,
             client_addr: None,
@@ -44,6 +69,50 @@ impl KeyInjectionState {
         self.add_log(format!("{}: {}", key_name, action));
     }
 
+    pub fn log_latched(&mut self, key_name: String, key_code: u32) {
+        self.latched.insert(key_code, key_name.clone());
+        self.add_log(format!("{}: latched", key_name));
+    }
+
+    pub fn log_unlatched(&mut self, key_name: String, key_code: u32) {
+        self.latched.remove(&key_code);
+        self.add_log(format!("{}: unlatched", key_name));
+    }
+
+    pub fn set_sticky(&mut self, key_name: String, key_code: u32, sticky: Option<StickyState>) {
+        match sticky {
+            Some(state) => {
+                let label = match state {
+                    StickyState::Latched => "latched",
+                    StickyState::Locked => "locked",
+                };
+                self.add_log(format!("{}: {}", key_name, label));
+                self.sticky.insert(key_code, (key_name, state));
+            }
+            None => {
+                if self.sticky.remove(&key_code).is_some() {
+                    self.add_log(format!("{}: sticky released", key_name));
+                }
+            }
+        }
+    }
+
+    pub fn set_holders(&mut self, key_name: String, key_code: u32, holders: Vec<String>) {
+        if holders.is_empty() {
+            self.holders.remove(&key_code);
+        } else {
+            self.holders.insert(key_code, (key_name, holders));
+        }
+    }
+
+    pub fn set_scan_items(&mut self, labels: Vec<String>, columns: Option<usize>) {
+        self.scan_items = Some((labels, columns));
+    }
+
+    pub fn set_radial(&mut self, stick: String, selected: Option<(usize, String)>, sectors: usize) {
+        self.radial.insert(stick, (selected, sectors));
+    }
+
     pub fn log_client_connected(&mut self, addr: String) {
         self.client_state = ClientState::Connected;
         self.client_addr = Some(addr.clone());
@@ -66,20 +135,72 @@ impl KeyInjectionState {
 }
 
 pub fn draw(f: &mut Frame, state: &KeyInjectionState) {
+    // Sticky modifiers get a full-width bar so they can't be missed
+    let sticky_height = if state.sticky.is_empty() { 0 } else { 3 };
+
     let chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Length(4), Constraint::Min(0)])
+        .constraints([
+            Constraint::Length(4),
+            Constraint::Length(sticky_height),
+            Constraint::Min(0),
+        ])
         .split(f.area());
 
     draw_status(f, state, chunks[0]);
+    draw_sticky(f, state, chunks[1]);
 
     let main_chunks = Layout::default()
         .direction(Direction::Horizontal)
         .constraints([Constraint::Percentage(30), Constraint::Percentage(70)])
-        .split(chunks[1]);
+        .split(chunks[2]);
+
+    let radial_height = if state.radial.is_empty() {
+        0
+    } else {
+        state.radial.len() as u16 + 2
+    };
+
+    // The scanning grid takes the top of the log side, one line per row
+    let scan_height = match &state.scan_items {
+        Some((labels, columns)) => {
+            labels.len().div_ceil(columns.unwrap_or(labels.len())) as u16 + 2
+        }
+        None => 0,
+    };
+    let keyboard_height = state
+        .keyboard
+        .as_ref()
+        .map_or(0, |keyboard| keyboard.rows.len() as u16 + 2);
+    let morse_height = if state.morse.is_some() { 3 } else { 0 };
+    let log_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(keyboard_height),
+            Constraint::Length(morse_height),
+            Constraint::Length(scan_height),
+            Constraint::Min(0),
+        ])
+        .split(main_chunks[1]);
+
+    let side_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(5),
+            Constraint::Length(radial_height),
+            Constraint::Min(0),
+            Constraint::Percentage(30),
+        ])
+        .split(main_chunks[0]);
 
-    draw_last_key(f, state, main_chunks[0]);
-    draw_log(f, state, main_chunks[1]);
+    draw_last_key(f, state, side_chunks[0]);
+    draw_radial(f, state, side_chunks[1]);
+    draw_holders(f, state, side_chunks[2]);
+    draw_latched(f, state, side_chunks[3]);
+    draw_keyboard(f, state, log_chunks[0]);
+    draw_morse(f, state, log_chunks[1]);
+    draw_scan(f, state, log_chunks[2]);
+    draw_log(f, state, log_chunks[3]);
 }
 
 fn draw_status(f: &mut Frame, state: &KeyInjectionState, area: Rect) {
@@ -110,16 +231,45 @@ fn draw_status(f: &mut Frame, state: &KeyInjectionState, area: Rect) {
     f.render_widget(paragraph, area);
 }
 
+fn draw_sticky(f: &mut Frame, state: &KeyInjectionState, area: Rect) {
+    if state.sticky.is_empty() {
+        return;
+    }
+
+    let mut spans = vec![];
+    for (key_name, sticky) in state.sticky.values() {
+        let (label, color) = match sticky {
+            StickyState::Latched => ("latched", Color::Yellow),
+            StickyState::Locked => ("LOCKED", Color::Red),
+        };
+        spans.push(Span::styled(
+            format!(" {} {} ", key_name.to_uppercase(), label),
+            Style::default()
+                .fg(Color::Black)
+                .bg(color)
+                .add_modifier(Modifier::BOLD),
+        ));
+        spans.push(Span::raw("  "));
+    }
+
+    let block = Block::default()
+        .title("Sticky Modifiers")
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Yellow));
+    let paragraph = Paragraph::new(Line::from(spans)).block(block);
+    f.render_widget(paragraph, area);
+}
+
 fn draw_last_key(f: &mut Frame, state: &KeyInjectionState, area: Rect) {
     let mut text = vec![];
 
     if let Some(key_name) = &state.last_key_name {
         text.push(Line::from(Span::styled(
-            format!("{}", key_name),
+            key_name.to_string(),
             Style::default().fg(Color::Cyan),
         )));
         
-        let action = state.last_action.as_ref().map(|s| s.as_str()).unwrap_or("unbound");
+        let action = state.last_action.as_deref().unwrap_or("unbound");
         let color = match action {
             "pressed" => Color::Green,
             "released" => Color::Red,
@@ -127,7 +277,7 @@ fn draw_last_key(f: &mut Frame, state: &KeyInjectionState, area: Rect) {
             _ => Color::Gray,
         };
         text.push(Line::from(Span::styled(
-            format!("{}", action),
+            action.to_string(),
             Style::default().fg(color),
         )));
         
@@ -152,6 +302,189 @@ fn draw_last_key(f: &mut Frame, state: &KeyInjectionState, area: Rect) {
     f.render_widget(paragraph, area);
 }
 
+fn draw_radial(f: &mut Frame, state: &KeyInjectionState, area: Rect) {
+    if state.radial.is_empty() {
+        return;
+    }
+
+    let text: Vec<Line> = state
+        .radial
+        .iter()
+        .map(|(stick, (selected, sectors))| match selected {
+            Some((sector, key_name)) => Line::from(vec![
+                Span::styled(format!("  {}: ", stick), Style::default().fg(Color::Gray)),
+                Span::styled(
+                    format!("{}/{} {}", sector + 1, sectors, key_name),
+                    Style::default().fg(Color::Green),
+                ),
+            ]),
+            None => Line::from(Span::styled(
+                format!("  {}: centre", stick),
+                Style::default().fg(Color::Gray),
+            )),
+        })
+        .collect();
+
+    let block = Block::default().title("Radial Menu").borders(Borders::ALL);
+    let paragraph = Paragraph::new(text).block(block);
+    f.render_widget(paragraph, area);
+}
+
+fn draw_keyboard(f: &mut Frame, state: &KeyInjectionState, area: Rect) {
+    let Some(keyboard) = &state.keyboard else {
+        return;
+    };
+
+    let text: Vec<Line> = keyboard
+        .rows
+        .iter()
+        .enumerate()
+        .map(|(row, characters)| {
+            let spans: Vec<Span> = characters
+                .iter()
+                .enumerate()
+                .map(|(column, character)| {
+                    let style = if keyboard.cursor == (row, column) {
+                        Style::default()
+                            .fg(Color::Black)
+                            .bg(Color::Green)
+                            .add_modifier(Modifier::BOLD)
+                    } else {
+                        Style::default().fg(Color::Gray)
+                    };
+                    Span::styled(format!(" {} ", character), style)
+                })
+                .collect();
+            Line::from(spans)
+        })
+        .collect();
+
+    let title = if keyboard.shift {
+        "Keyboard (shift)"
+    } else {
+        "Keyboard"
+    };
+    let block = Block::default().title(title).borders(Borders::ALL);
+    let paragraph = Paragraph::new(text).block(block);
+    f.render_widget(paragraph, area);
+}
+
+fn draw_morse(f: &mut Frame, state: &KeyInjectionState, area: Rect) {
+    let Some(morse) = &state.morse else {
+        return;
+    };
+
+    let text = Line::from(vec![
+        Span::styled(morse.text.clone(), Style::default().fg(Color::White)),
+        Span::raw(" "),
+        Span::styled(
+            morse.pattern.clone(),
+            Style::default()
+                .fg(Color::Green)
+                .add_modifier(Modifier::BOLD),
+        ),
+    ]);
+
+    let title = format!("Morse (dash {} ms)", morse.dash.as_millis());
+    let block = Block::default().title(title).borders(Borders::ALL);
+    let paragraph = Paragraph::new(text).block(block);
+    f.render_widget(paragraph, area);
+}
+
+fn draw_scan(f: &mut Frame, state: &KeyInjectionState, area: Rect) {
+    let Some((labels, columns)) = &state.scan_items else {
+        return;
+    };
+    let columns = columns.unwrap_or(labels.len()).max(1);
+
+    let text: Vec<Line> = labels
+        .chunks(columns)
+        .enumerate()
+        .map(|(row, items)| {
+            let row_highlighted = state.scan_highlight == Some(Highlight::Row(row));
+            let spans: Vec<Span> = items
+                .iter()
+                .enumerate()
+                .flat_map(|(column, label)| {
+                    let index = row * columns + column;
+                    let style = if state.scan_highlight == Some(Highlight::Item(index)) {
+                        Style::default()
+                            .fg(Color::Black)
+                            .bg(Color::Green)
+                            .add_modifier(Modifier::BOLD)
+                    } else if row_highlighted {
+                        Style::default().fg(Color::Black).bg(Color::Yellow)
+                    } else {
+                        Style::default().fg(Color::Gray)
+                    };
+                    [Span::styled(format!(" {} ", label), style), Span::raw(" ")]
+                })
+                .collect();
+            Line::from(spans)
+        })
+        .collect();
+
+    let title = if state.scan_highlight.is_some() {
+        "Scanning"
+    } else {
+        "Scanning (idle)"
+    };
+    let block = Block::default().title(title).borders(Borders::ALL);
+    let paragraph = Paragraph::new(text).block(block);
+    f.render_widget(paragraph, area);
+}
+
+fn draw_holders(f: &mut Frame, state: &KeyInjectionState, area: Rect) {
+    let text: Vec<Line> = if state.holders.is_empty() {
+        vec![Line::from(Span::styled(
+            "No keys held",
+            Style::default().fg(Color::Gray),
+        ))]
+    } else {
+        state
+            .holders
+            .values()
+            .map(|(key_name, holders)| {
+                Line::from(vec![
+                    Span::styled(
+                        format!("  {}: ", key_name),
+                        Style::default().fg(Color::Green),
+                    ),
+                    Span::styled(holders.join(", "), Style::default().fg(Color::Gray)),
+                ])
+            })
+            .collect()
+    };
+
+    let block = Block::default().title("Held Keys").borders(Borders::ALL);
+    let paragraph = Paragraph::new(text).block(block);
+    f.render_widget(paragraph, area);
+}
+
+fn draw_latched(f: &mut Frame, state: &KeyInjectionState, area: Rect) {
+    let text: Vec<Line> = if state.latched.is_empty() {
+        vec![Line::from(Span::styled(
+            "No keys latched",
+            Style::default().fg(Color::Gray),
+        ))]
+    } else {
+        state
+            .latched
+            .values()
+            .map(|key_name| {
+                Line::from(Span::styled(
+                    format!("  {}: ●", key_name),
+                    Style::default().fg(Color::Yellow),
+                ))
+            })
+            .collect()
+    };
+
+    let block = Block::default().title("Latched").borders(Borders::ALL);
+    let paragraph = Paragraph::new(text).block(block);
+    f.render_widget(paragraph, area);
+}
+
 fn draw_log(f: &mut Frame, state: &KeyInjectionState, area: Rect) {
     let log_lines: Vec<Line> = state
         .log